@@ -0,0 +1,188 @@
+use crate::replay;
+use crate::replayer;
+use byteorder::ReadBytesExt;
+use byteorder::WriteBytesExt;
+use prost::Message;
+use std::io::Read;
+use std::io::Write;
+
+/// Bundles every round of a match into one `.tangomatch` file.
+///
+/// Each round keeps its replay exactly as it was recorded, so rounds can be pulled back out without losing anything.
+pub struct Archive {
+    /// The replay metadata shared by every round of the match.
+    pub metadata: Vec<u8>,
+
+    /// The rounds, in the order they were played.
+    pub rounds: Vec<Round>,
+}
+
+pub struct Round {
+    pub number: u8,
+
+    /// How the round ended for the player who recorded the match, if known.
+    pub result: Option<replayer::BattleResult>,
+
+    /// The round's `.tangoreplay` file.
+    pub replay: Vec<u8>,
+}
+
+const HEADER: &[u8] = b"TOOM";
+const VERSION: u8 = 0x01;
+
+mod section {
+    pub const METADATA: u8 = 0x01;
+    pub const ROUND: u8 = 0x02;
+}
+
+/// Stands in for a result in the file if the round didn't finish.
+const NO_RESULT: i8 = i8::MIN;
+
+fn invalid_data(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+fn write_section(mut w: impl std::io::Write, kind: u8, payload: &[u8]) -> std::io::Result<()> {
+    w.write_u8(kind)?;
+    w.write_u32::<byteorder::LittleEndian>(payload.len() as u32)?;
+    w.write_all(payload)?;
+    Ok(())
+}
+
+fn read_section(mut r: impl std::io::Read) -> std::io::Result<Option<(u8, Vec<u8>)>> {
+    let kind = match r.read_u8() {
+        Ok(kind) => kind,
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+            return Ok(None);
+        }
+        Err(e) => {
+            return Err(e);
+        }
+    };
+    let mut payload = vec![0u8; r.read_u32::<byteorder::LittleEndian>()? as usize];
+    r.read_exact(&mut payload)?;
+    Ok(Some((kind, payload)))
+}
+
+/// Checks if a file starts like a match archive, as opposed to a single replay.
+pub fn is_archive(buf: &[u8]) -> bool {
+    buf.starts_with(HEADER)
+}
+
+impl Round {
+    pub fn decode_replay(&self) -> std::io::Result<replay::Replay> {
+        replay::Replay::decode(&self.replay[..])
+    }
+}
+
+/// The part of a round's metadata that is about the whole match.
+///
+/// A repaired round notes the repair in its metadata, but that is only about the round, so it's left out.
+fn match_metadata(metadata: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut metadata = tango_protos::replay::ReplayMetadata::decode(metadata)
+        .map_err(|e| invalid_data(&e.to_string()))?;
+    metadata.repair = None;
+    Ok(metadata.encode_to_vec())
+}
+
+impl Archive {
+    /// Puts loose round replays together into an archive.
+    ///
+    /// All the replays must be from the same match, and there must be no gaps between their round numbers.
+    pub fn new(rounds: Vec<Round>) -> std::io::Result<Self> {
+        let mut rounds = rounds;
+        rounds.sort_by_key(|round| round.number);
+
+        let mut metadata = None;
+        for (i, round) in rounds.iter().enumerate() {
+            if (round.number as usize) < i + 1 {
+                return Err(invalid_data(&format!(
+                    "round {} appears more than once",
+                    round.number
+                )));
+            }
+            if round.number as usize > i + 1 {
+                return Err(invalid_data(&format!("round {} is missing", i + 1)));
+            }
+
+            let replay = round.decode_replay()?;
+            let round_metadata = match_metadata(&replay.metadata)?;
+            match metadata.as_ref() {
+                None => {
+                    metadata = Some(round_metadata);
+                }
+                Some(metadata) => {
+                    if *metadata != round_metadata {
+                        return Err(invalid_data(&format!(
+                            "round {} is not from the same match as round 1",
+                            round.number
+                        )));
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            metadata: metadata.ok_or_else(|| invalid_data("no rounds"))?,
+            rounds,
+        })
+    }
+
+    pub fn encode(&self, mut w: impl std::io::Write) -> std::io::Result<()> {
+        w.write_all(HEADER)?;
+        w.write_u8(VERSION)?;
+
+        write_section(&mut w, section::METADATA, &self.metadata)?;
+
+        for round in self.rounds.iter() {
+            let mut payload = Vec::with_capacity(2 + round.replay.len());
+            payload.write_u8(round.number)?;
+            payload.write_i8(round.result.map(|r| r as i8).unwrap_or(NO_RESULT))?;
+            payload.write_all(&round.replay)?;
+            write_section(&mut w, section::ROUND, &payload)?;
+        }
+
+        w.flush()?;
+        Ok(())
+    }
+
+    pub fn decode(mut r: impl std::io::Read) -> std::io::Result<Self> {
+        let mut header = [0u8; 4];
+        r.read_exact(&mut header)?;
+        if &header != HEADER {
+            return Err(invalid_data("invalid header"));
+        }
+
+        if r.read_u8()? != VERSION {
+            return Err(invalid_data("invalid version"));
+        }
+
+        let mut metadata = None;
+        let mut rounds = vec![];
+        while let Some((kind, payload)) = read_section(&mut r)? {
+            match kind {
+                section::METADATA => {
+                    metadata = Some(payload);
+                }
+                section::ROUND => {
+                    let mut pr = &payload[..];
+                    let number = pr.read_u8()?;
+                    let result = replayer::BattleResult::from_i8(pr.read_i8()?);
+                    rounds.push(Round {
+                        number,
+                        result,
+                        replay: pr.to_vec(),
+                    });
+                }
+                _ => {
+                    // Sections we don't know about are skipped, so newer writers can add more.
+                }
+            }
+        }
+
+        Ok(Self {
+            metadata: metadata.ok_or_else(|| invalid_data("missing metadata"))?,
+            rounds,
+        })
+    }
+}
@@ -1,15 +1,22 @@
 use rand::Rng;
+use sha3::Digest;
 
 use crate::game;
 use crate::hooks;
 use crate::input;
 use crate::ipc;
+use crate::patch;
 use crate::protocol;
 use crate::replay;
 use crate::replayer;
+use crate::rtt;
 use crate::shadow;
+use crate::signing;
+use crate::spectator;
 use crate::transport;
 
+pub mod stats;
+
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub enum BattleResult {
     Loss,
@@ -23,9 +30,65 @@ pub struct CommittedState {
     pub packet: Vec<u8>,
 }
 
+/// How often, in ticks, peers exchange checksums of their committed states.
+pub const STATE_CHECKSUM_INTERVAL: u32 = 60;
+
+/// How often to measure the round trip time to the opponent during a match.
+const PING_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// How long to keep trying to reconnect to the opponent after the connection drops before giving up on the match.
+const RECONNECT_GRACE_PERIOD: std::time::Duration = std::time::Duration::from_secs(30);
+
+pub fn wram_checksum(state: &mgba::state::State) -> [u8; 32] {
+    sha3::Sha3_256::digest(state.wram()).into()
+}
+
+/// Why a match ended before it was over.
+///
+/// This is returned as an error from wherever the match ended, and also noted in the slot given to `Match::new`, so whoever started the match can pick the exit code once the replay has been dealt with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchEnded {
+    LostConnection,
+    OpponentLeft,
+    Desync,
+}
+
+impl MatchEnded {
+    pub fn exit_code(&self) -> ipc::protos::ExitCode {
+        match self {
+            MatchEnded::LostConnection => ipc::protos::ExitCode::LostConnection,
+            MatchEnded::OpponentLeft => ipc::protos::ExitCode::OpponentLeft,
+            MatchEnded::Desync => ipc::protos::ExitCode::Desync,
+        }
+    }
+
+    /// Notes that the match ended this way, unless it already ended some other way, and returns the error to bail out with.
+    fn record(self, ended: &parking_lot::Mutex<Option<MatchEnded>>) -> anyhow::Error {
+        ended.lock().get_or_insert(self);
+        self.into()
+    }
+}
+
+impl std::fmt::Display for MatchEnded {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MatchEnded::LostConnection => write!(f, "lost connection"),
+            MatchEnded::OpponentLeft => write!(f, "opponent left"),
+            MatchEnded::Desync => write!(f, "desync"),
+        }
+    }
+}
+
+impl std::error::Error for MatchEnded {}
+
 pub struct MatchInit {
-    pub dc: datachannel_wrapper::DataChannel,
-    pub peer_conn: datachannel_wrapper::PeerConnection,
+    pub tx: Box<dyn transport::Sender>,
+    pub rx: Box<dyn transport::Receiver>,
+    pub peer_conn: Option<datachannel_wrapper::PeerConnection>,
+    /// If set, the match survives the connection dropping for up to `RECONNECT_GRACE_PERIOD`.
+    pub reconnector: Option<Box<dyn transport::Reconnector>>,
+    pub is_offerer: bool,
+    pub rtt_tracker: rtt::Tracker,
     pub settings: Settings,
 }
 
@@ -33,13 +96,26 @@ pub struct Settings {
     pub replays_path: std::path::PathBuf,
     pub shadow_save_path: std::path::PathBuf,
     pub shadow_rom_path: std::path::PathBuf,
+    pub shadow_patch: Option<patch::Patch>,
     pub replay_metadata: Vec<u8>,
     pub match_type: (u8, u8),
     pub input_delay: u32,
     pub shadow_input_delay: u32,
+    pub auto_input_delay: bool,
     pub rng_seed: Vec<u8>,
     pub opponent_nickname: Option<String>,
     pub max_queue_length: usize,
+    pub spectator_listen_addr: Option<String>,
+    pub signing_keypair: std::sync::Arc<ed25519_dalek::Keypair>,
+    pub remote_public_key: [u8; 32],
+}
+
+/// A finished replay that can only be signed once the opponent's signature for it arrives.
+struct PendingSignature {
+    replay: Box<dyn replay::WriteSeek + Send>,
+    statement: signing::Statement,
+    local_player_index: u8,
+    local_signature: [u8; 64],
 }
 
 pub struct RoundState {
@@ -47,13 +123,24 @@ pub struct RoundState {
     pub round: Option<Round>,
     pub last_result: Option<BattleResult>,
     ipc_sender: std::sync::Arc<parking_lot::Mutex<ipc::Sender>>,
+    input_delay: u32,
+    shadow_input_delay: u32,
+    local_delay_proposals: std::collections::BTreeMap<u8, u32>,
+    remote_delay_proposals: std::collections::BTreeMap<u8, u32>,
+    pending_signatures: std::collections::BTreeMap<u8, PendingSignature>,
+    remote_signatures: std::collections::BTreeMap<u8, [u8; 64]>,
 }
 
 impl RoundState {
     pub async fn end_round(&mut self) -> anyhow::Result<()> {
         match self.round.take() {
-            Some(round) => {
+            Some(mut round) => {
                 log::info!("round ended at {:x}", round.current_tick);
+                if let Some(pending_signature) = round.pending_signature.take() {
+                    self.pending_signatures
+                        .insert(round.number, pending_signature);
+                    self.sign_replays()?;
+                }
                 self.ipc_sender
                     .lock()
                     .send(ipc::protos::FromCoreMessage {
@@ -80,13 +167,65 @@ impl RoundState {
     pub fn set_last_result(&mut self, last_result: BattleResult) {
         self.last_result = Some(last_result);
     }
+
+    pub fn add_remote_signature(
+        &mut self,
+        round_number: u8,
+        signature: [u8; 64],
+    ) -> anyhow::Result<()> {
+        self.remote_signatures.insert(round_number, signature);
+        self.sign_replays()
+    }
+
+    /// Writes both signatures into every replay that we have the opponent's signature for.
+    ///
+    /// Either side may finish a round first, so this is tried both when our round ends and when their signature arrives.
+    fn sign_replays(&mut self) -> anyhow::Result<()> {
+        let ready = self
+            .pending_signatures
+            .keys()
+            .filter(|number| self.remote_signatures.contains_key(number))
+            .cloned()
+            .collect::<Vec<_>>();
+        for number in ready {
+            let mut pending = self.pending_signatures.remove(&number).unwrap();
+            let remote_signature = self.remote_signatures.remove(&number).unwrap();
+
+            let remote_player_index = 1 - pending.local_player_index;
+            if let Err(e) = pending
+                .statement
+                .verify(remote_player_index, &remote_signature)
+            {
+                log::error!("not signing replay for round {}: {}", number, e);
+                continue;
+            }
+
+            let mut signatures = [[0u8; 64]; 2];
+            signatures[pending.local_player_index as usize] = pending.local_signature;
+            signatures[remote_player_index as usize] = remote_signature;
+            replay::append_signature(
+                &mut *pending.replay,
+                &signing::Footer {
+                    statement: pending.statement,
+                    signatures,
+                },
+            )?;
+            log::info!("signed replay for round {}", number);
+        }
+        Ok(())
+    }
 }
 
 pub struct Match {
     shadow: std::sync::Arc<parking_lot::Mutex<shadow::Shadow>>,
     rom: Vec<u8>,
+    patch: Option<patch::Patch>,
     hooks: &'static Box<dyn hooks::Hooks + Send + Sync>,
-    _peer_conn: datachannel_wrapper::PeerConnection,
+    _peer_conn: parking_lot::Mutex<Option<datachannel_wrapper::PeerConnection>>,
+    reconnector: Option<Box<dyn transport::Reconnector>>,
+    /// False while we're reconnecting to the opponent, during which the game is held in place.
+    connected_tx: tokio::sync::watch::Sender<bool>,
+    connected_rx: tokio::sync::watch::Receiver<bool>,
     transport: std::sync::Arc<tokio::sync::Mutex<transport::Transport>>,
     rng: tokio::sync::Mutex<rand_pcg::Mcg128Xsl64>,
     settings: Settings,
@@ -96,100 +235,61 @@ pub struct Match {
     round_started_tx: tokio::sync::mpsc::Sender<u8>,
     round_started_rx: tokio::sync::Mutex<tokio::sync::mpsc::Receiver<u8>>,
     transport_rendezvous_tx: tokio::sync::Mutex<Option<tokio::sync::oneshot::Sender<()>>>,
-}
-
-#[derive(Debug)]
-pub enum NegotiationError {
-    ExpectedHello,
-    ExpectedHola,
-    IdenticalCommitment,
-    ProtocolVersionMismatch,
-    MatchTypeMismatch,
-    IncompatibleGames,
-    InvalidCommitment,
-    Other(anyhow::Error),
-}
-
-impl From<anyhow::Error> for NegotiationError {
-    fn from(err: anyhow::Error) -> Self {
-        NegotiationError::Other(err)
-    }
-}
-
-impl From<datachannel_wrapper::Error> for NegotiationError {
-    fn from(err: datachannel_wrapper::Error) -> Self {
-        NegotiationError::Other(err.into())
-    }
-}
-
-impl From<std::io::Error> for NegotiationError {
-    fn from(err: std::io::Error) -> Self {
-        NegotiationError::Other(err.into())
-    }
-}
-
-impl std::fmt::Display for NegotiationError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            NegotiationError::ExpectedHello => write!(f, "expected hello"),
-            NegotiationError::ExpectedHola => write!(f, "expected hola"),
-            NegotiationError::IdenticalCommitment => write!(f, "identical commitment"),
-            NegotiationError::ProtocolVersionMismatch => write!(f, "protocol version mismatch"),
-            NegotiationError::MatchTypeMismatch => write!(f, "match type mismatch"),
-            NegotiationError::IncompatibleGames => write!(f, "game mismatch"),
-            NegotiationError::InvalidCommitment => write!(f, "invalid commitment"),
-            NegotiationError::Other(e) => write!(f, "other error: {}", e),
-        }
-    }
-}
-
-impl std::error::Error for NegotiationError {}
-
-pub enum NegotiationFailure {
-    ProtocolVersionMismatch,
-    MatchTypeMismatch,
-    IncompatibleGames,
-    Unknown,
-}
-
-pub enum NegotiationStatus {
-    Ready,
-    NotReady(NegotiationProgress),
-    Failed(NegotiationFailure),
-}
-
-#[derive(Clone, Debug)]
-pub enum NegotiationProgress {
-    NotStarted,
-    Signalling,
-    Handshaking,
+    spectator_broadcaster: Option<std::sync::Arc<spectator::Broadcaster>>,
+    rtt_tracker: parking_lot::Mutex<rtt::Tracker>,
+    ended: std::sync::Arc<parking_lot::Mutex<Option<MatchEnded>>>,
 }
 
 impl Match {
     pub fn new(
         rom: Vec<u8>,
+        patch: Option<patch::Patch>,
         hooks: &'static Box<dyn hooks::Hooks + Send + Sync>,
-        peer_conn: datachannel_wrapper::PeerConnection,
-        dc_tx: datachannel_wrapper::DataChannelSender,
+        peer_conn: Option<datachannel_wrapper::PeerConnection>,
+        reconnector: Option<Box<dyn transport::Reconnector>>,
+        tx: Box<dyn transport::Sender>,
         mut rng: rand_pcg::Mcg128Xsl64,
         is_offerer: bool,
         primary_thread_handle: mgba::thread::Handle,
         ipc_sender: std::sync::Arc<parking_lot::Mutex<ipc::Sender>>,
+        rtt_tracker: rtt::Tracker,
+        ended: std::sync::Arc<parking_lot::Mutex<Option<MatchEnded>>>,
         settings: Settings,
     ) -> anyhow::Result<std::sync::Arc<Self>> {
         let shadow_rom = std::fs::read(&settings.shadow_rom_path)?;
 
         let (round_started_tx, round_started_rx) = tokio::sync::mpsc::channel(1);
         let (transport_rendezvous_tx, transport_rendezvous_rx) = tokio::sync::oneshot::channel();
+        let (connected_tx, connected_rx) = tokio::sync::watch::channel(true);
         let did_polite_win_last_round = rng.gen::<bool>();
         let last_result = if did_polite_win_last_round == is_offerer {
             BattleResult::Win
         } else {
             BattleResult::Loss
         };
+
+        let spectator_broadcaster = if let Some(addr) = settings.spectator_listen_addr.as_ref() {
+            let listener = std::net::TcpListener::bind(addr)?;
+            listener.set_nonblocking(true)?;
+            let listener = tokio::net::TcpListener::from_std(listener)?;
+            let broadcaster = std::sync::Arc::new(spectator::Broadcaster::new());
+            tokio::task::spawn({
+                let broadcaster = broadcaster.clone();
+                async move {
+                    if let Err(e) = broadcaster.serve(listener).await {
+                        log::error!("spectator server ended: {}", e);
+                    }
+                }
+            });
+            Some(broadcaster)
+        } else {
+            None
+        };
+
         let match_ = std::sync::Arc::new(Self {
             shadow: std::sync::Arc::new(parking_lot::Mutex::new(shadow::Shadow::new(
                 &shadow_rom,
+                settings.shadow_patch.as_ref(),
                 &settings.shadow_save_path,
                 settings.match_type,
                 is_offerer,
@@ -197,25 +297,39 @@ impl Match {
                 rng.clone(),
             )?)),
             rom,
+            patch,
             hooks,
-            _peer_conn: peer_conn,
+            _peer_conn: parking_lot::Mutex::new(peer_conn),
             transport: std::sync::Arc::new(tokio::sync::Mutex::new(transport::Transport::new(
-                dc_tx,
+                tx,
                 transport_rendezvous_rx,
+                reconnector.is_some(),
             ))),
+            reconnector,
+            connected_tx,
+            connected_rx,
             transport_rendezvous_tx: tokio::sync::Mutex::new(Some(transport_rendezvous_tx)),
             rng: tokio::sync::Mutex::new(rng),
-            settings,
             round_state: tokio::sync::Mutex::new(RoundState {
                 number: 0,
                 round: None,
                 last_result: Some(last_result),
                 ipc_sender,
+                input_delay: settings.input_delay,
+                shadow_input_delay: settings.shadow_input_delay,
+                local_delay_proposals: std::collections::BTreeMap::new(),
+                remote_delay_proposals: std::collections::BTreeMap::new(),
+                pending_signatures: std::collections::BTreeMap::new(),
+                remote_signatures: std::collections::BTreeMap::new(),
             }),
+            settings,
             is_offerer,
             primary_thread_handle,
             round_started_tx,
             round_started_rx: tokio::sync::Mutex::new(round_started_rx),
+            spectator_broadcaster,
+            rtt_tracker: parking_lot::Mutex::new(rtt_tracker),
+            ended,
         });
         Ok(match_)
     }
@@ -230,22 +344,172 @@ impl Match {
         self.shadow.lock().advance_until_first_committed_state()
     }
 
-    pub async fn run(
+    async fn has_transport_rendezvoused(&self) -> bool {
+        self.transport_rendezvous_tx.lock().await.is_none()
+    }
+
+    /// Tells whoever reconnected where to resume from, and has them prove they're the opponent who started the match by signing a challenge with the key they gave in the handshake.
+    ///
+    /// Returns how many packets that must be delivered the opponent had received. Nothing is resent until this succeeds, as anyone who knows the link code can take the reconnect slot.
+    async fn exchange_resume(
         &self,
-        mut dc_rx: datachannel_wrapper::DataChannelReceiver,
-    ) -> anyhow::Result<()> {
+        tx: &mut dyn transport::Sender,
+        rx: &mut dyn transport::Receiver,
+        num_received: u64,
+    ) -> anyhow::Result<u64> {
+        let mut challenge = [0u8; 32];
+        rand::thread_rng().fill(&mut challenge);
+        tx.send(&protocol::Packet::Resume(protocol::Resume {
+            received: num_received,
+            challenge,
+        }))
+        .await?;
+        let resume = match rx.receive().await? {
+            Some(protocol::Packet::Resume(resume)) => resume,
+            p => anyhow::bail!("expected resume, got {:?}", p),
+        };
+
+        tx.send(&protocol::Packet::ResumeProof(protocol::ResumeProof {
+            signature: signing::sign_resume_challenge(
+                &self.settings.signing_keypair,
+                &resume.challenge,
+            )
+            .to_vec(),
+        }))
+        .await?;
+        let resume_proof = match rx.receive().await? {
+            Some(protocol::Packet::ResumeProof(resume_proof)) => resume_proof,
+            p => anyhow::bail!("expected resume proof, got {:?}", p),
+        };
+        signing::verify_resume_challenge(
+            &self.settings.remote_public_key,
+            &challenge,
+            &resume_proof.signature,
+        )?;
+
+        Ok(resume.received)
+    }
+
+    /// Gets a new connection to the opponent, and picks up where the old one left off.
+    ///
+    /// Gives up on the match entirely if we can't reconnect within the grace period, returning `MatchEnded::LostConnection`.
+    async fn reconnect(
+        &self,
+        reconnector: &dyn transport::Reconnector,
+        num_received: u64,
+    ) -> anyhow::Result<Box<dyn transport::Receiver>> {
+        let _ = self.connected_tx.send(false);
+        self.transport.lock().await.disconnect();
+
+        let ipc_sender = self.round_state.lock().await.ipc_sender.clone();
+        ipc_sender
+            .lock()
+            .send(ipc::protos::FromCoreMessage {
+                which: Some(ipc::protos::from_core_message::Which::ReconnectingEv(
+                    ipc::protos::from_core_message::ReconnectingEvent {},
+                )),
+            })
+            .await?;
+
+        let deadline = tokio::time::Instant::now() + RECONNECT_GRACE_PERIOD;
+        let (tx, rx, peer_conn, remote_received) = match tokio::time::timeout_at(deadline, async {
+            loop {
+                let (mut tx, mut rx, peer_conn) = match reconnector.reconnect().await {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        log::warn!("failed to reconnect, retrying: {}", e);
+                        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                        continue;
+                    }
+                };
+                match self.exchange_resume(&mut *tx, &mut *rx, num_received).await {
+                    Ok(remote_received) => break (tx, rx, peer_conn, remote_received),
+                    Err(e) => {
+                        log::warn!("failed to resume, reconnecting: {}", e);
+                    }
+                }
+            }
+        })
+        .await
+        {
+            Ok(conn) => conn,
+            Err(_) => {
+                log::error!("could not reconnect within the grace period, giving up");
+                return Err(MatchEnded::LostConnection.record(&self.ended));
+            }
+        };
+
+        self.transport
+            .lock()
+            .await
+            .resume(tx, remote_received)
+            .await?;
+        *self._peer_conn.lock() = peer_conn;
+
+        log::info!("reconnected, resuming match");
+        let _ = self.connected_tx.send(true);
+        ipc_sender
+            .lock()
+            .send(ipc::protos::FromCoreMessage {
+                which: Some(ipc::protos::from_core_message::Which::ReconnectedEv(
+                    ipc::protos::from_core_message::ReconnectedEvent {},
+                )),
+            })
+            .await?;
+
+        Ok(rx)
+    }
+
+    pub fn is_connected(&self) -> bool {
+        *self.connected_rx.borrow()
+    }
+
+    pub async fn run(&self, mut rx: Box<dyn transport::Receiver>) -> anyhow::Result<()> {
         let mut last_round_number = 0;
+        let mut ping_timer = tokio::time::interval(PING_INTERVAL);
+
+        // How many packets that must be delivered we've gotten from the opponent, so they know what to resend if we have to reconnect.
+        let mut num_received = 0u64;
+
         loop {
-            match protocol::Packet::deserialize(
-                match dc_rx.receive().await {
-                    None => {
-                        log::info!("data channel closed");
-                        break;
+            let p = tokio::select! {
+                p = rx.receive() => p?,
+                _ = ping_timer.tick() => {
+                    // Until the rendezvous, the transport is held by the first input waiting on it.
+                    if self.has_transport_rendezvoused().await {
+                        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?;
+                        let mut transport = self.transport.lock().await;
+                        transport.send_ping(now.as_nanos() as u64).await?;
+                        if self.reconnector.is_some() {
+                            transport.send_ack(num_received).await?;
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            let p = match p {
+                None => {
+                    // Before the rendezvous there's nothing to pick back up, and the first input is still holding the transport.
+                    if let (Some(reconnector), true) = (
+                        self.reconnector.as_ref(),
+                        self.has_transport_rendezvoused().await,
+                    ) {
+                        log::info!("transport closed, reconnecting");
+                        rx = self.reconnect(&**reconnector, num_received).await?;
+                        continue;
                     }
-                    Some(buf) => buf,
+                    log::info!("transport closed");
+                    break;
                 }
-                .as_slice(),
-            )? {
+                Some(p) => p,
+            };
+
+            if p.must_deliver() {
+                num_received += 1;
+            }
+
+            match p {
                 protocol::Packet::Input(input) => {
                     // We need to sync on the first input so we don't end up wildly out of sync.
                     if let Some(transport_rendezvous_tx) =
@@ -316,6 +580,107 @@ impl Match {
                         joyflags: input.joyflags as u16,
                     });
                 }
+                protocol::Packet::StateChecksum(state_checksum) => {
+                    let mut round_state = self.round_state.lock().await;
+                    if state_checksum.round_number != round_state.number {
+                        log::info!("round number mismatch, dropping state checksum");
+                        continue;
+                    }
+
+                    let round = match &mut round_state.round {
+                        None => {
+                            log::info!("no round in progress, dropping state checksum");
+                            continue;
+                        }
+                        Some(b) => b,
+                    };
+
+                    round.add_remote_checksum(state_checksum.tick, state_checksum.checksum);
+                    round.verify_checksums().await?;
+                }
+                protocol::Packet::Ping(ping) => {
+                    if !self.has_transport_rendezvoused().await {
+                        log::info!("transport not ready, dropping ping");
+                        continue;
+                    }
+                    self.transport.lock().await.send_pong(ping.ts).await?;
+                }
+                protocol::Packet::Pong(pong) => {
+                    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?;
+                    let rtt = now.saturating_sub(std::time::Duration::from_nanos(pong.ts));
+                    self.rtt_tracker.lock().add_sample(rtt);
+
+                    let ipc_sender = {
+                        let mut round_state = self.round_state.lock().await;
+                        if let Some(round) = round_state.round.as_mut() {
+                            round.stats.add_rtt_sample(rtt);
+                        }
+                        round_state.ipc_sender.clone()
+                    };
+                    ipc_sender
+                        .lock()
+                        .send(ipc::protos::FromCoreMessage {
+                            which: Some(
+                                ipc::protos::from_core_message::Which::ConnectionQualityEv(
+                                    ipc::protos::from_core_message::ConnectionQualityEvent {
+                                        rtt: rtt.as_nanos() as u64,
+                                    },
+                                ),
+                            ),
+                        })
+                        .await?;
+                }
+                protocol::Packet::DelayProposal(delay_proposal) => {
+                    self.round_state
+                        .lock()
+                        .await
+                        .remote_delay_proposals
+                        .insert(delay_proposal.round_number, delay_proposal.input_delay);
+                }
+                protocol::Packet::Signature(signature) => {
+                    let remote_signature = match <[u8; 64]>::try_from(signature.signature) {
+                        Ok(remote_signature) => remote_signature,
+                        Err(_) => {
+                            log::error!(
+                                "malformed signature for round {}, dropping",
+                                signature.round_number
+                            );
+                            continue;
+                        }
+                    };
+                    self.round_state
+                        .lock()
+                        .await
+                        .add_remote_signature(signature.round_number, remote_signature)?;
+                }
+                protocol::Packet::Ack(ack) => {
+                    self.transport.lock().await.ack(ack.received);
+                }
+                protocol::Packet::Goodbye(goodbye) => {
+                    log::info!("opponent left the match: {:?}", goodbye.reason);
+                    let ipc_sender = {
+                        let mut round_state = self.round_state.lock().await;
+                        if let Some(round) = round_state.round.as_mut() {
+                            round.record_goodbye(1 - round.local_player_index, goodbye.reason);
+                        }
+                        round_state.ipc_sender.clone()
+                    };
+                    ipc_sender
+                        .lock()
+                        .send(ipc::protos::FromCoreMessage {
+                            which: Some(ipc::protos::from_core_message::Which::GoodbyeEv(
+                                ipc::protos::from_core_message::GoodbyeEvent {
+                                    reason:
+                                        ipc::protos::from_core_message::goodbye_event::Reason::from(
+                                            goodbye.reason,
+                                        )
+                                        .into(),
+                                },
+                            )),
+                        })
+                        .await?;
+                    return Err(MatchEnded::OpponentLeft.record(&self.ended));
+                }
                 p => anyhow::bail!("unknown packet: {:?}", p),
             }
         }
@@ -323,7 +688,31 @@ impl Match {
         Ok(())
     }
 
+    /// Tells the opponent we're leaving the match and why, and records it in the replay of the round in progress, if any.
+    pub async fn say_goodbye(&self, reason: protocol::GoodbyeReason) -> anyhow::Result<()> {
+        // Until the rendezvous, the transport is held by the first input waiting on it. Nothing has been played yet, so the opponent seeing the connection close is just as good.
+        if !self.has_transport_rendezvoused().await {
+            return Ok(());
+        }
+
+        log::info!("leaving the match: {:?}", reason);
+        {
+            let mut round_state = self.round_state.lock().await;
+            if let Some(round) = round_state.round.as_mut() {
+                round.record_goodbye(round.local_player_index, reason);
+            }
+        }
+        self.transport.lock().await.send_goodbye(reason).await
+    }
+
+    /// Locks the round state, first waiting out any reconnection so the game doesn't carry on without the opponent.
     pub async fn lock_round_state(&self) -> tokio::sync::MutexGuard<'_, RoundState> {
+        let mut connected_rx = self.connected_rx.clone();
+        while !*connected_rx.borrow() {
+            if connected_rx.changed().await.is_err() {
+                break;
+            }
+        }
         self.round_state.lock().await
     }
 
@@ -342,6 +731,26 @@ impl Match {
     pub async fn start_round(self: &std::sync::Arc<Self>) -> anyhow::Result<()> {
         let mut round_state = self.round_state.lock().await;
         round_state.number += 1;
+        let number = round_state.number;
+
+        if self.settings.auto_input_delay {
+            // Both sides send their proposal for a round at the start of the round before it, ahead of any input for that round, so by now we either have both or the other side never sent one.
+            let local_proposal = round_state.local_delay_proposals.remove(&number);
+            let remote_proposal = round_state.remote_delay_proposals.remove(&number);
+            if let Some(input_delay) = rtt::agree_input_delay(local_proposal, remote_proposal) {
+                log::info!(
+                    "adjusting input delay: {} -> {}",
+                    round_state.input_delay,
+                    input_delay
+                );
+                round_state.input_delay = input_delay;
+                round_state.shadow_input_delay = input_delay;
+            }
+        }
+        round_state.local_delay_proposals.retain(|&n, _| n > number);
+        round_state
+            .remote_delay_proposals
+            .retain(|&n, _| n > number);
         let local_player_index = match round_state.last_result.take().unwrap() {
             BattleResult::Win => 0,
             BattleResult::Loss => 1,
@@ -365,21 +774,20 @@ impl Match {
         let (first_state_committed_local_packet, first_state_committed_rx) =
             tokio::sync::oneshot::channel();
 
-        let mut iq =
-            input::PairQueue::new(self.settings.max_queue_length, self.settings.input_delay);
+        let mut iq = input::PairQueue::new(self.settings.max_queue_length, round_state.input_delay);
         log::info!(
             "filling input delay: local = {}, remote = {}",
-            self.settings.input_delay,
-            self.settings.shadow_input_delay
+            round_state.input_delay,
+            round_state.shadow_input_delay
         );
-        for i in 0..self.settings.input_delay {
+        for i in 0..round_state.input_delay {
             iq.add_local_input(input::PartialInput {
                 local_tick: i,
                 remote_tick: 0,
                 joyflags: 0,
             });
         }
-        for i in 0..self.settings.shadow_input_delay {
+        for i in 0..round_state.shadow_input_delay {
             iq.add_remote_input(input::PartialInput {
                 local_tick: i,
                 remote_tick: 0,
@@ -387,6 +795,10 @@ impl Match {
             });
         }
 
+        // Any checksums the shadow took before this round are for the previous round.
+        self.shadow.lock().take_wram_checksums();
+
+        let ipc_sender = round_state.ipc_sender.clone();
         round_state.round = Some(Round {
             hooks: self.hooks,
             number: round_state.number,
@@ -394,7 +806,7 @@ impl Match {
             current_tick: 0,
             dtick: 0,
             iq,
-            remote_delay: self.settings.shadow_input_delay,
+            remote_delay: round_state.shadow_input_delay,
             last_committed_remote_input: input::Input {
                 local_tick: 0,
                 remote_tick: 0,
@@ -411,8 +823,24 @@ impl Match {
                 local_player_index,
                 self.hooks.packet_size() as u8,
             )?),
+            pending_signature: None,
+            signing_keypair: self.settings.signing_keypair.clone(),
+            state_hashes: None,
+            metadata_hash: signing::hash_metadata(
+                local_player_index,
+                &self.settings.replay_metadata,
+            )?,
+            public_keys: {
+                let local_public_key = self.settings.signing_keypair.public.to_bytes();
+                if local_player_index == 0 {
+                    [local_public_key, self.settings.remote_public_key]
+                } else {
+                    [self.settings.remote_public_key, local_public_key]
+                }
+            },
             replayer: replayer::Fastforwarder::new(
                 &self.rom,
+                self.patch.as_ref(),
                 self.hooks,
                 local_player_index,
                 &self.settings.opponent_nickname,
@@ -420,7 +848,30 @@ impl Match {
             primary_thread_handle: self.primary_thread_handle.clone(),
             transport: self.transport.clone(),
             shadow: self.shadow.clone(),
+            replay_metadata: self.settings.replay_metadata.clone(),
+            spectator_broadcaster: self.spectator_broadcaster.clone(),
+            ipc_sender,
+            remote_checksums: std::collections::BTreeMap::new(),
+            shadow_checksums: std::collections::BTreeMap::new(),
+            last_matching_checksum_tick: 0,
+            stats: stats::Collector::new(),
+            ended: self.ended.clone(),
         });
+
+        if self.settings.auto_input_delay {
+            let recommended_input_delay = self.rtt_tracker.lock().recommended_input_delay();
+            if let Some(input_delay) = recommended_input_delay {
+                round_state
+                    .local_delay_proposals
+                    .insert(number + 1, input_delay);
+                self.transport
+                    .lock()
+                    .await
+                    .send_delay_proposal(number + 1, input_delay)
+                    .await?;
+            }
+        }
+
         self.round_started_tx.send(round_state.number).await?;
         log::info!("round has started");
         Ok(())
@@ -440,11 +891,25 @@ pub struct Round {
     first_state_committed_rx: Option<tokio::sync::oneshot::Receiver<()>>,
     committed_state: Option<CommittedState>,
     replay_writer: Option<replay::Writer>,
+    pending_signature: Option<PendingSignature>,
+    signing_keypair: std::sync::Arc<ed25519_dalek::Keypair>,
+    /// P1's and P2's initial states, hashed once the first state is committed.
+    state_hashes: Option<[[u8; 32]; 2]>,
+    metadata_hash: [u8; 32],
+    public_keys: [[u8; 32]; 2],
     replayer: replayer::Fastforwarder,
     replay_filename: std::path::PathBuf,
     primary_thread_handle: mgba::thread::Handle,
     transport: std::sync::Arc<tokio::sync::Mutex<transport::Transport>>,
     shadow: std::sync::Arc<parking_lot::Mutex<shadow::Shadow>>,
+    replay_metadata: Vec<u8>,
+    spectator_broadcaster: Option<std::sync::Arc<spectator::Broadcaster>>,
+    ipc_sender: std::sync::Arc<parking_lot::Mutex<ipc::Sender>>,
+    remote_checksums: std::collections::BTreeMap<u32, [u8; 32]>,
+    shadow_checksums: std::collections::BTreeMap<u32, [u8; 32]>,
+    last_matching_checksum_tick: u32,
+    stats: stats::Collector,
+    ended: std::sync::Arc<parking_lot::Mutex<Option<MatchEnded>>>,
 }
 
 impl Round {
@@ -480,6 +945,23 @@ impl Round {
             .unwrap()
             .write_state(&remote_state)
             .expect("write remote state");
+        let mut state_hashes = [
+            signing::hash_state(&state),
+            signing::hash_state(&remote_state),
+        ];
+        if self.local_player_index != 0 {
+            state_hashes.reverse();
+        }
+        self.state_hashes = Some(state_hashes);
+        if let Some(spectator_broadcaster) = self.spectator_broadcaster.as_ref() {
+            spectator_broadcaster.publish(spectator::Packet::RoundStart(spectator::RoundStart {
+                round_number: self.number,
+                local_player_index: self.local_player_index,
+                metadata: self.replay_metadata.clone(),
+                local_state: state.as_slice().to_vec(),
+                remote_state: remote_state.as_slice().to_vec(),
+            }));
+        }
         self.committed_state = Some(CommittedState {
             state,
             tick: 0,
@@ -594,6 +1076,8 @@ impl Round {
                 }
             }),
         )?;
+        self.stats
+            .add_fastforward(&ff_result.output_pairs, commit_tick);
 
         for ip in &ff_result.output_pairs {
             if ip.local.local_tick >= commit_tick {
@@ -610,6 +1094,12 @@ impl Round {
                         .write_input(self.local_player_index, ip)
                         .expect("write input");
                 }
+                if let Some(spectator_broadcaster) = self.spectator_broadcaster.as_ref() {
+                    spectator_broadcaster.publish(spectator::Packet::Input(spectator::Input::new(
+                        self.number,
+                        ip,
+                    )));
+                }
             }
             self.last_committed_remote_input = ip.remote.clone();
         }
@@ -618,12 +1108,44 @@ impl Round {
             .expect("load dirty state");
         self.committed_state = Some(ff_result.committed_state);
 
+        self.shadow_checksums
+            .extend(self.shadow.lock().take_wram_checksums());
+        self.verify_checksums().await?;
+
+        for &(tick, checksum) in ff_result.state_checksums.iter() {
+            if ff_result
+                .round_result
+                .map(|rr| tick >= rr.tick)
+                .unwrap_or(false)
+            {
+                break;
+            }
+            self.transport
+                .lock()
+                .await
+                .send_state_checksum(self.number, tick, checksum)
+                .await?;
+        }
+
+        if ff_result.round_result.is_none() {
+            if let Some(replay_writer) = self.replay_writer.as_mut() {
+                if replay_writer.wants_keyframe() {
+                    let committed_state = self.committed_state.as_ref().unwrap();
+                    replay_writer
+                        .write_keyframe(committed_state.tick, &committed_state.state)
+                        .expect("write keyframe");
+                }
+            }
+        }
+
         self.dtick = last_local_input.lag() - self.last_committed_remote_input.lag();
 
         core.gba_mut()
             .sync_mut()
             .expect("set fps target")
             .set_fps_target(game::EXPECTED_FPS as f32 + self.tps_adjustment());
+        self.stats
+            .add_tps_adjustment(self.current_tick, self.tps_adjustment());
 
         let round_result = if let Some(round_result) = ff_result.round_result {
             round_result
@@ -635,13 +1157,46 @@ impl Round {
             return Ok(None);
         }
 
-        if let Some(replay_writer) = self.replay_writer.take() {
-            replay_writer.finish().expect("finish");
+        if let Some(spectator_broadcaster) = self.spectator_broadcaster.as_ref() {
+            spectator_broadcaster.publish(spectator::Packet::RoundEnd(spectator::RoundEnd {
+                round_number: self.number,
+            }));
+        }
+
+        let stats = self
+            .stats
+            .summarize(self.local_delay(), self.remote_delay());
+        self.report_stats(&stats).await?;
+
+        if let Some(mut replay_writer) = self.replay_writer.take() {
+            replay_writer.write_stats(&stats).expect("write stats");
+            let statement = signing::Statement {
+                round_number: self.number,
+                num_inputs: replay_writer.num_inputs(),
+                chain_hash: replay_writer.chain_hash(),
+                state_hashes: self.state_hashes.expect("state hashes"),
+                metadata_hash: self.metadata_hash,
+                public_keys: self.public_keys,
+            };
+            let local_signature = statement.sign(&self.signing_keypair);
+            let replay = replay_writer.finish().expect("finish");
             log::info!(
                 "replay finished at {:x} (real tick {:x})",
                 round_result.tick,
                 self.current_tick
             );
+
+            self.transport
+                .lock()
+                .await
+                .send_signature(self.number, local_signature)
+                .await?;
+            self.pending_signature = Some(PendingSignature {
+                replay,
+                statement,
+                local_player_index: self.local_player_index,
+                local_signature,
+            });
         }
 
         Ok(Some(match round_result.result {
@@ -651,6 +1206,144 @@ impl Round {
         }))
     }
 
+    async fn report_stats(&self, stats: &replay::Stats) -> anyhow::Result<()> {
+        log::info!("round {} stats: {:?}", self.number, stats);
+        self.ipc_sender
+            .lock()
+            .send(ipc::protos::FromCoreMessage {
+                which: Some(ipc::protos::from_core_message::Which::RoundStatsEv(
+                    ipc::protos::from_core_message::RoundStatsEvent {
+                        round_number: self.number as u32,
+                        input_delay: stats.input_delay,
+                        remote_input_delay: stats.remote_input_delay,
+                        num_rtt_samples: stats.num_rtt_samples,
+                        rtt_p50: stats.rtt_p50.unwrap_or(0),
+                        rtt_p90: stats.rtt_p90.unwrap_or(0),
+                        rtt_p99: stats.rtt_p99.unwrap_or(0),
+                        jitter: stats.jitter.unwrap_or(0),
+                        num_rollbacks: stats.num_rollbacks,
+                        frames_resimulated: stats.frames_resimulated,
+                        max_rollback_depth: stats.max_rollback_depth,
+                        max_prediction_depth: stats.max_prediction_depth,
+                        tps_adjustments: stats
+                            .tps_adjustments
+                            .iter()
+                            .map(|a| {
+                                ipc::protos::from_core_message::round_stats_event::TpsAdjustment {
+                                    tick: a.tick,
+                                    adjustment: a.adjustment,
+                                }
+                            })
+                            .collect(),
+                    },
+                )),
+            })
+            .await?;
+        Ok(())
+    }
+
+    pub fn add_remote_checksum(&mut self, tick: u32, checksum: [u8; 32]) {
+        self.remote_checksums.insert(tick, checksum);
+    }
+
+    /// Compares the checksums of the remote's committed states against what our shadow computed for the same ticks.
+    ///
+    /// If they differ, the remote is no longer playing the same battle as us: we report it and exit, as there is no way to recover.
+    pub async fn verify_checksums(&mut self) -> anyhow::Result<()> {
+        let mut desync_tick = None;
+        for (tick, remote_checksum) in self.remote_checksums.iter() {
+            let shadow_checksum = if let Some(shadow_checksum) = self.shadow_checksums.get(tick) {
+                shadow_checksum
+            } else {
+                continue;
+            };
+
+            if shadow_checksum != remote_checksum {
+                desync_tick = Some(*tick);
+                break;
+            }
+
+            // Both sides take these states at the same point in the same frame, so if they were ever out of step, it would show up as the very first checksum failing to match.
+            if self.last_matching_checksum_tick == 0 {
+                log::info!(
+                    "first state checksum of round {} matched at tick {}",
+                    self.number,
+                    tick
+                );
+            }
+            self.last_matching_checksum_tick = *tick;
+        }
+
+        if let Some(tick) = desync_tick {
+            self.report_desync(tick).await?;
+        }
+
+        // Checksums only ever arrive in tick order, so anything from before the latest checksum on the other side will never be matched.
+        if let Some((&tick, _)) = self.remote_checksums.iter().next_back() {
+            self.shadow_checksums = self.shadow_checksums.split_off(&(tick + 1));
+        }
+        if let Some((&tick, _)) = self.shadow_checksums.iter().next_back() {
+            self.remote_checksums = self.remote_checksums.split_off(&(tick + 1));
+        }
+
+        Ok(())
+    }
+
+    async fn report_desync(&mut self, tick: u32) -> anyhow::Result<()> {
+        log::error!(
+            "desync detected in round {} at tick {} (last matching tick was {})",
+            self.number,
+            tick,
+            self.last_matching_checksum_tick
+        );
+
+        // Flush whatever we have of the replay, so the desync can be investigated later.
+        self.record_goodbye(self.local_player_index, protocol::GoodbyeReason::Desync);
+        if let Err(e) = self
+            .transport
+            .lock()
+            .await
+            .send_goodbye(protocol::GoodbyeReason::Desync)
+            .await
+        {
+            log::error!("failed to tell opponent about desync: {}", e);
+        }
+
+        self.ipc_sender
+            .lock()
+            .send(ipc::protos::FromCoreMessage {
+                which: Some(ipc::protos::from_core_message::Which::DesyncEv(
+                    ipc::protos::from_core_message::DesyncEvent {
+                        round_number: self.number as u32,
+                        tick,
+                        last_matching_tick: self.last_matching_checksum_tick,
+                    },
+                )),
+            })
+            .await?;
+        Err(MatchEnded::Desync.record(&self.ended))
+    }
+
+    /// Finishes the replay early, noting that the given player left the match and why.
+    pub fn record_goodbye(&mut self, player_index: u8, reason: protocol::GoodbyeReason) {
+        let mut replay_writer = match self.replay_writer.take() {
+            Some(replay_writer) => replay_writer,
+            None => {
+                return;
+            }
+        };
+        if let Err(e) = replay_writer
+            .write_goodbye(&replay::Goodbye {
+                player_index,
+                reason,
+                tick: self.current_tick,
+            })
+            .and_then(|_| replay_writer.finish())
+        {
+            log::error!("failed to record goodbye in replay: {}", e);
+        }
+    }
+
     pub fn on_draw_result(&self) -> BattleResult {
         match self.local_player_index {
             0 => BattleResult::Win,
@@ -0,0 +1,108 @@
+use crate::{input, replay, rtt};
+
+/// Enough round trip times to cover even a very long round.
+const RTT_WINDOW_SIZE: usize = 60 * 60;
+
+/// How often, in ticks, to sample the TPS adjustment.
+const TPS_ADJUSTMENT_SAMPLE_INTERVAL: u32 = 60;
+
+/// Collects statistics about the connection over the course of a round.
+pub struct Collector {
+    rtt_tracker: rtt::Tracker,
+    num_rollbacks: u32,
+    frames_resimulated: u64,
+    max_rollback_depth: u32,
+    max_prediction_depth: u32,
+    tps_adjustments: Vec<replay::TpsAdjustment>,
+
+    /// The remote inputs the last fastforward predicted, by tick, to check against once they're committed.
+    predicted: std::collections::BTreeMap<u32, input::Input>,
+}
+
+impl Collector {
+    pub fn new() -> Self {
+        Self {
+            rtt_tracker: rtt::Tracker::new(RTT_WINDOW_SIZE),
+            num_rollbacks: 0,
+            frames_resimulated: 0,
+            max_rollback_depth: 0,
+            max_prediction_depth: 0,
+            tps_adjustments: vec![],
+            predicted: std::collections::BTreeMap::new(),
+        }
+    }
+
+    pub fn add_rtt_sample(&mut self, rtt: std::time::Duration) {
+        self.rtt_tracker.add_sample(rtt);
+    }
+
+    /// Records the inputs a fastforward ran with, where everything before `commit_tick` was committed and everything after was predicted.
+    ///
+    /// Re-emulating frames whose predictions held up changes nothing on screen, so only a committed remote input that differs from what was last predicted for its tick counts as a rollback. Its depth is how many frames had been emulated from that tick on with the wrong prediction.
+    pub fn add_fastforward(
+        &mut self,
+        input_pairs: &[input::Pair<input::Input, input::Input>],
+        commit_tick: u32,
+    ) {
+        let first_mispredicted_tick = input_pairs
+            .iter()
+            .take_while(|ip| ip.local.local_tick < commit_tick)
+            .find(|ip| {
+                self.predicted
+                    .get(&ip.local.local_tick)
+                    .map(|predicted| {
+                        predicted.joyflags != ip.remote.joyflags
+                            || predicted.packet != ip.remote.packet
+                    })
+                    .unwrap_or(false)
+            })
+            .map(|ip| ip.local.local_tick);
+
+        if let Some(tick) = first_mispredicted_tick {
+            let depth = self.predicted.range(tick..).count() as u32;
+            self.num_rollbacks += 1;
+            self.frames_resimulated += depth as u64;
+            self.max_rollback_depth = self.max_rollback_depth.max(depth);
+        }
+
+        self.predicted = input_pairs
+            .iter()
+            .filter(|ip| ip.local.local_tick >= commit_tick)
+            .map(|ip| (ip.local.local_tick, ip.remote.clone()))
+            .collect();
+        self.max_prediction_depth = self.max_prediction_depth.max(self.predicted.len() as u32);
+    }
+
+    pub fn add_tps_adjustment(&mut self, tick: u32, adjustment: f32) {
+        if tick % TPS_ADJUSTMENT_SAMPLE_INTERVAL != 0
+            || self.tps_adjustments.last().map(|a| a.tick) == Some(tick)
+        {
+            return;
+        }
+        self.tps_adjustments
+            .push(replay::TpsAdjustment { tick, adjustment });
+    }
+
+    pub fn summarize(&self, input_delay: u32, remote_input_delay: u32) -> replay::Stats {
+        replay::Stats {
+            input_delay,
+            remote_input_delay,
+            num_rtt_samples: self.rtt_tracker.num_samples() as u32,
+            rtt_p50: self.rtt_tracker.median().map(|d| d.as_nanos() as u64),
+            rtt_p90: self
+                .rtt_tracker
+                .percentile(0.9)
+                .map(|d| d.as_nanos() as u64),
+            rtt_p99: self
+                .rtt_tracker
+                .percentile(0.99)
+                .map(|d| d.as_nanos() as u64),
+            jitter: self.rtt_tracker.jitter().map(|d| d.as_nanos() as u64),
+            num_rollbacks: self.num_rollbacks,
+            frames_resimulated: self.frames_resimulated,
+            max_rollback_depth: self.max_rollback_depth,
+            max_prediction_depth: self.max_prediction_depth,
+            tps_adjustments: self.tps_adjustments.clone(),
+        }
+    }
+}
@@ -0,0 +1,170 @@
+#![windows_subsystem = "windows"]
+
+use clap::Parser;
+
+#[derive(clap::Parser)]
+struct Cli {
+    #[clap(subcommand)]
+    action: Action,
+}
+
+#[derive(clap::Parser)]
+struct CreateCli {
+    #[clap(parse(from_os_str))]
+    output_path: std::path::PathBuf,
+
+    /// The replays of each round. Round numbers are taken from the file names the core writes (`-round{n}-`), or from the order given otherwise.
+    #[clap(parse(from_os_str), required = true)]
+    replay_paths: Vec<std::path::PathBuf>,
+
+    /// If given, each round is played through to record its result.
+    #[clap(long, parse(from_os_str))]
+    rom_path: Option<std::path::PathBuf>,
+
+    #[clap(long, parse(from_os_str), requires = "rom-path")]
+    patch_path: Option<std::path::PathBuf>,
+}
+
+#[derive(clap::Parser)]
+struct ListCli {
+    #[clap(parse(from_os_str))]
+    path: std::path::PathBuf,
+}
+
+#[derive(clap::Subcommand)]
+enum Action {
+    Create(CreateCli),
+    List(ListCli),
+}
+
+fn main() -> Result<(), anyhow::Error> {
+    env_logger::Builder::from_default_env()
+        .filter(Some("tango_core"), log::LevelFilter::Info)
+        .filter(Some("matcharchive"), log::LevelFilter::Info)
+        .init();
+    mgba::log::init();
+
+    let args = Cli::parse();
+
+    match args.action {
+        Action::Create(args) => create(args),
+        Action::List(args) => list(args),
+    }
+}
+
+/// Reads the round number out of a replay file name as written by the core, e.g. `...-round2-p1.tangoreplay`.
+fn parse_round_number(path: &std::path::Path) -> Option<u8> {
+    let stem = path.file_stem()?.to_str()?;
+    let (_, rest) = stem.rsplit_once("-round")?;
+    let (number, _) = rest.split_once('-')?;
+    number.parse().ok()
+}
+
+fn create(args: CreateCli) -> Result<(), anyhow::Error> {
+    let rom = args.rom_path.as_ref().map(std::fs::read).transpose()?;
+    let patch = args
+        .patch_path
+        .as_ref()
+        .map(|path| tango_core::patch::Patch::read(path))
+        .transpose()?;
+
+    let mut rounds = vec![];
+    for (i, path) in args.replay_paths.iter().enumerate() {
+        let replay = std::fs::read(path)?;
+        let number = parse_round_number(path).unwrap_or(i as u8 + 1);
+
+        let result = if let Some(rom) = rom.as_ref() {
+            let result = tango_core::replayer::evaluate(
+                rom,
+                patch.as_ref(),
+                &tango_core::replay::Replay::decode(&replay[..])?,
+            )?
+            .map(|round_result| round_result.result);
+            log::info!(
+                "round {}: {}",
+                number,
+                match result {
+                    Some(tango_core::replayer::BattleResult::Win) => "win",
+                    Some(tango_core::replayer::BattleResult::Loss) => "loss",
+                    Some(tango_core::replayer::BattleResult::Draw) => "draw",
+                    None => "did not finish",
+                }
+            );
+            result
+        } else {
+            None
+        };
+
+        rounds.push(tango_core::archive::Round {
+            number,
+            result,
+            replay,
+        });
+    }
+
+    let archive = tango_core::archive::Archive::new(rounds)?;
+    archive.encode(std::io::BufWriter::new(std::fs::File::create(
+        &args.output_path,
+    )?))?;
+    log::info!(
+        "wrote {} rounds to {}",
+        archive.rounds.len(),
+        args.output_path.display()
+    );
+    Ok(())
+}
+
+#[derive(serde::Serialize)]
+struct ListedRound {
+    number: u8,
+    result: Option<tango_core::replayer::BattleResult>,
+    local_player_index: u8,
+    is_complete: bool,
+    num_inputs: u32,
+}
+
+#[derive(serde::Serialize)]
+struct Listing {
+    ts: u64,
+    link_code: String,
+    local_side: Option<tango_core::replay::SideInfo>,
+    remote_side: Option<tango_core::replay::SideInfo>,
+    rounds: Vec<ListedRound>,
+}
+
+fn list(args: ListCli) -> Result<(), anyhow::Error> {
+    let archive = tango_core::archive::Archive::decode(std::io::BufReader::new(
+        std::fs::File::open(&args.path)?,
+    ))?;
+
+    let infos = archive
+        .rounds
+        .iter()
+        .map(|round| round.decode_replay()?.info())
+        .collect::<Result<Vec<_>, _>>()?;
+    let first_info = infos
+        .first()
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("archive has no rounds"))?;
+
+    let listing = Listing {
+        ts: first_info.ts,
+        link_code: first_info.link_code,
+        local_side: first_info.local_side,
+        remote_side: first_info.remote_side,
+        rounds: archive
+            .rounds
+            .iter()
+            .zip(infos.into_iter())
+            .map(|(round, info)| ListedRound {
+                number: round.number,
+                result: round.result,
+                local_player_index: info.local_player_index,
+                is_complete: info.is_complete,
+                num_inputs: info.num_inputs,
+            })
+            .collect(),
+    };
+    serde_json::to_writer(std::io::stdout(), &listing)?;
+    Ok(())
+}
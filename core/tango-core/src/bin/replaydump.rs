@@ -31,6 +31,18 @@ struct VideoCli {
     #[clap(long)]
     assume_incomplete: bool,
 
+    /// Render both players' screens side by side.
+    #[clap(long)]
+    both_sides: bool,
+
+    /// ffmpeg muxes audio and video into the output. The other formats are written directly and put the audio in a separate WAV file.
+    #[clap(long, arg_enum, default_value = "ffmpeg")]
+    format: VideoFormat,
+
+    /// Where to write the WAV file for formats that don't include audio. Defaults to the output path with a .wav extension.
+    #[clap(long, parse(from_os_str))]
+    audio_output_path: Option<std::path::PathBuf>,
+
     #[clap(long, parse(from_os_str), default_value = "ffmpeg")]
     ffmpeg: std::path::PathBuf,
 
@@ -48,6 +60,16 @@ struct VideoCli {
     ffmpeg_mux_flags: String,
 }
 
+#[derive(clap::ArgEnum, Clone, Copy, PartialEq, Eq)]
+enum VideoFormat {
+    Ffmpeg,
+    Apng,
+    Y4m,
+}
+
+#[derive(clap::Parser)]
+struct InfoCli {}
+
 #[derive(clap::Parser)]
 struct EWRAMCli {}
 
@@ -72,14 +94,62 @@ struct StepCli {
     steps: u32,
 }
 
+#[derive(clap::Parser)]
+struct BisectCli {
+    #[clap(parse(from_os_str))]
+    rom_path: std::path::PathBuf,
+
+    /// The other player's replay of the same round.
+    #[clap(parse(from_os_str))]
+    other_path: std::path::PathBuf,
+
+    /// Differing bytes at most this far apart are reported as a single range.
+    #[clap(long, default_value = "16")]
+    merge_distance: usize,
+}
+
+#[derive(clap::Parser)]
+struct VerifyCli {}
+
+#[derive(clap::Parser)]
+struct StatsCli {
+    #[clap(parse(from_os_str))]
+    rom_path: std::path::PathBuf,
+
+    #[clap(long, parse(from_os_str))]
+    patch_path: Option<std::path::PathBuf>,
+
+    /// A JSON file giving the layout of the battle state. Without one, only the round's result and length are reported.
+    #[clap(long, parse(from_os_str))]
+    layout_path: Option<std::path::PathBuf>,
+}
+
+#[derive(clap::Parser)]
+struct RepairCli {
+    #[clap(parse(from_os_str))]
+    output_path: std::path::PathBuf,
+
+    /// If given, the repaired replay is played through to see if the round ended before the replay was cut off.
+    #[clap(long, parse(from_os_str))]
+    rom_path: Option<std::path::PathBuf>,
+
+    #[clap(long, parse(from_os_str), requires = "rom-path")]
+    patch_path: Option<std::path::PathBuf>,
+}
+
 #[derive(clap::Subcommand)]
 enum Action {
+    Info(InfoCli),
     Video(VideoCli),
     EWRAM(EWRAMCli),
     Text(TextCli),
     InputInfo(InputInfoCli),
     Eval(EvalCli),
     Step(StepCli),
+    Bisect(BisectCli),
+    Verify(VerifyCli),
+    Repair(RepairCli),
+    Stats(StatsCli),
 }
 
 fn main() -> Result<(), anyhow::Error> {
@@ -100,21 +170,42 @@ fn main() -> Result<(), anyhow::Error> {
     }
 
     match args.action {
-        Action::Video(args) => dump_video(args, replay),
+        Action::Info(info_args) => dump_info(info_args, replay, args.remote),
+        Action::Video(video_args) => dump_video(video_args, replay, args.remote),
         Action::EWRAM(args) => dump_ewram(args, replay),
         Action::Text(args) => dump_text(args, replay),
         Action::InputInfo(args) => dump_input_info(args, replay),
         Action::Eval(args) => dump_eval(args, replay),
         Action::Step(args) => dump_step(args, replay),
+        Action::Bisect(args) => dump_bisect(args, replay),
+        Action::Verify(verify_args) => dump_verify(verify_args, replay, &args.path),
+        Action::Repair(repair_args) => dump_repair(repair_args, &args.path),
+        Action::Stats(args) => dump_stats(args, replay),
+    }
+}
+
+fn dump_info(
+    _args: InfoCli,
+    replay: tango_core::replay::Replay,
+    remote: bool,
+) -> Result<(), anyhow::Error> {
+    let mut info = replay.info()?;
+    if remote {
+        // The metadata is always written from the point of view of the player who recorded the replay.
+        std::mem::swap(&mut info.local_side, &mut info.remote_side);
     }
+    serde_json::to_writer(std::io::stdout(), &info)?;
+    Ok(())
 }
 
-fn dump_video(args: VideoCli, replay: tango_core::replay::Replay) -> Result<(), anyhow::Error> {
+fn start_video_replayer(
+    rom: &[u8],
+    replay: &tango_core::replay::Replay,
+) -> Result<(mgba::core::Core, tango_core::replayer::State), anyhow::Error> {
     let mut core = mgba::core::Core::new_gba("tango_core")?;
     core.enable_video_buffer();
 
-    let rom = std::fs::read(&args.rom_path)?;
-    let vf = mgba::vfile::VFile::open_memory(&rom);
+    let vf = mgba::vfile::VFile::open_memory(rom);
     core.as_mut().load_rom(vf)?;
 
     core.as_mut().reset();
@@ -135,66 +226,135 @@ fn dump_video(args: VideoCli, replay: tango_core::replay::Replay) -> Result<(),
         traps.extend(hooks.replayer_traps(replayer_state.clone()));
         core.set_traps(traps);
     }
-    core.as_mut().load_state(&replay.local_state.unwrap())?;
+    core.as_mut().load_state(
+        replay
+            .local_state
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("replay has no local state"))?,
+    )?;
 
-    #[cfg(windows)]
-    const CREATE_NO_WINDOW: u32 = 0x08000000;
+    Ok((core, replayer_state))
+}
 
-    let video_output = tempfile::NamedTempFile::new()?;
-    let mut video_child = std::process::Command::new(&args.ffmpeg);
-    video_child
-        .stdin(std::process::Stdio::piped())
-        .args(&["-y"])
-        // Input args.
-        .args(&[
-            "-f",
-            "rawvideo",
-            "-pixel_format",
-            "rgba",
-            "-video_size",
-            "240x160",
-            "-framerate",
-            "16777216/280896",
-            "-i",
-            "pipe:",
-        ])
-        // Output args.
-        .args(shell_words::split(&args.ffmpeg_video_flags)?)
-        .args(&["-f", "mp4"])
-        .arg(&video_output.path());
-    #[cfg(windows)]
-    video_child.creation_flags(CREATE_NO_WINDOW);
-    let mut video_child = video_child.spawn()?;
+fn is_video_replayer_done(
+    replayer_state: &tango_core::replayer::State,
+    replay: &tango_core::replay::Replay,
+) -> bool {
+    let replayer_state = replayer_state.lock_inner();
+    (!replay.is_complete && replayer_state.input_pairs_left() == 0)
+        || replayer_state.is_round_ended()
+}
 
-    let audio_output = tempfile::NamedTempFile::new()?;
-    let mut audio_child = std::process::Command::new(&args.ffmpeg);
-    audio_child
-        .stdin(std::process::Stdio::piped())
-        .args(&["-y"])
-        // Input args.
-        .args(&["-f", "s16le", "-ar", "48k", "-ac", "2", "-i", "pipe:"])
-        // Output args.
-        .args(shell_words::split(&args.ffmpeg_audio_flags)?)
-        .args(&["-f", "mp4"])
-        .arg(&audio_output.path());
-    #[cfg(windows)]
-    audio_child.creation_flags(CREATE_NO_WINDOW);
-    let mut audio_child = audio_child.spawn()?;
+fn copy_video_buffer(core: &mgba::core::Core, vbuf: &mut [u8]) {
+    vbuf.copy_from_slice(core.video_buffer().unwrap());
+    for i in (0..vbuf.len()).step_by(4) {
+        vbuf[i + 3] = 0xff;
+    }
+}
 
-    const SAMPLE_RATE: f64 = 48000.0;
-    let mut samples = vec![0i16; SAMPLE_RATE as usize];
+/// The opponent's side of the replay, for rendering both sides at once.
+struct OtherSide {
+    core: mgba::core::Core,
+    replayer_state: tango_core::replayer::State,
+    replay: tango_core::replay::Replay,
+    sbs: tango_core::sidebyside::SideBySide,
+}
+
+fn dump_video(
+    args: VideoCli,
+    replay: tango_core::replay::Replay,
+    remote: bool,
+) -> Result<(), anyhow::Error> {
+    let rom = std::fs::read(&args.rom_path)?;
+
+    let (mut core, replayer_state) = start_video_replayer(&rom, &replay)?;
+
+    // The other side is driven from the same replay, seen from the opponent's point of view.
+    let mut other = if args.both_sides {
+        let other_replay = replay
+            .clone()
+            .into_remote()
+            .ok_or_else(|| anyhow::anyhow!("replay has no remote state"))?;
+        let (other_core, other_replayer_state) = start_video_replayer(&rom, &other_replay)?;
+
+        let info = replay.info()?;
+        let mut nicknames = [info.local_side, info.remote_side]
+            .map(|side| side.map(|side| side.nickname).unwrap_or_default());
+        if remote {
+            // The metadata is always written from the point of view of the player who recorded the replay.
+            nicknames.reverse();
+        }
+        let [nickname, other_nickname] = nicknames;
+
+        Some(OtherSide {
+            core: other_core,
+            replayer_state: other_replayer_state,
+            replay: other_replay,
+            sbs: tango_core::sidebyside::SideBySide::new(&nickname, &other_nickname)?,
+        })
+    } else {
+        None
+    };
+
+    let (width, height) = if other.is_some() {
+        (
+            tango_core::sidebyside::SideBySide::width(),
+            tango_core::sidebyside::SideBySide::height(),
+        )
+    } else {
+        (mgba::gba::SCREEN_WIDTH, mgba::gba::SCREEN_HEIGHT)
+    };
+
+    let (mut video_writer, mut audio_writer, ffmpeg_outputs) = match args.format {
+        VideoFormat::Ffmpeg => {
+            let (video_writer, audio_writer, video_output, audio_output) =
+                spawn_ffmpeg_writers(&args, width, height)?;
+            (
+                video_writer,
+                audio_writer,
+                Some((video_output, audio_output)),
+            )
+        }
+        VideoFormat::Apng => (
+            Box::new(tango_core::export::ApngWriter::new(
+                std::fs::File::options()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(&args.output_path)?,
+                width,
+                height,
+            )?) as Box<dyn tango_core::export::VideoWriter>,
+            open_wav_writer(&args)?,
+            None,
+        ),
+        VideoFormat::Y4m => (
+            Box::new(tango_core::export::Y4mWriter::new(
+                std::io::BufWriter::new(std::fs::File::create(&args.output_path)?),
+                width as usize,
+                height as usize,
+            )?) as Box<dyn tango_core::export::VideoWriter>,
+            open_wav_writer(&args)?,
+            None,
+        ),
+    };
+
+    let mut samples = vec![0i16; tango_core::export::SAMPLE_RATE as usize];
     let mut vbuf = vec![0u8; (mgba::gba::SCREEN_WIDTH * mgba::gba::SCREEN_HEIGHT * 4) as usize];
+    let mut other_vbuf = vbuf.clone();
     writeln!(
         std::io::stdout(),
         "{}",
         replayer_state.lock_inner().input_pairs_left()
     )?;
     loop {
-        {
-            let replayer_state = replayer_state.lock_inner();
-            if (!replay.is_complete && replayer_state.input_pairs_left() == 0)
-                || replayer_state.is_round_ended()
-            {
+        if is_video_replayer_done(&replayer_state, &replay) {
+            break;
+        }
+
+        if let Some(other) = other.as_ref() {
+            if is_video_replayer_done(&other.replayer_state, &other.replay) {
                 break;
             }
         }
@@ -205,11 +365,25 @@ fn dump_video(args: VideoCli, replay: tango_core::replay::Replay) -> Result<(),
             Err(err)?;
         }
 
+        if let Some(other) = other.as_mut() {
+            // Both games should tick once per frame, but run the other core only while it's behind just in case they don't line up exactly.
+            let tick = replayer_state.lock_inner().current_tick();
+            while other.replayer_state.lock_inner().current_tick() < tick
+                && !is_video_replayer_done(&other.replayer_state, &other.replay)
+            {
+                other.core.as_mut().run_frame();
+
+                if let Some(err) = other.replayer_state.lock_inner().take_error() {
+                    Err(err)?;
+                }
+            }
+        }
+
         let clock_rate = core.as_ref().frequency();
         let n = {
             let mut core = core.as_mut();
             let mut left = core.audio_channel(0);
-            left.set_rates(clock_rate as f64, SAMPLE_RATE);
+            left.set_rates(clock_rate as f64, tango_core::export::SAMPLE_RATE as f64);
             let n = left.samples_avail();
             left.read_samples(&mut samples[..(n * 2) as usize], left.samples_avail(), true);
             n
@@ -217,28 +391,19 @@ fn dump_video(args: VideoCli, replay: tango_core::replay::Replay) -> Result<(),
         {
             let mut core = core.as_mut();
             let mut right = core.audio_channel(1);
-            right.set_rates(clock_rate as f64, SAMPLE_RATE);
+            right.set_rates(clock_rate as f64, tango_core::export::SAMPLE_RATE as f64);
             right.read_samples(&mut samples[1..(n * 2) as usize], n, true);
         }
         let samples = &samples[..(n * 2) as usize];
 
-        vbuf.copy_from_slice(core.video_buffer().unwrap());
-        for i in (0..vbuf.len()).step_by(4) {
-            vbuf[i + 3] = 0xff;
+        copy_video_buffer(&core, &mut vbuf);
+        if let Some(other) = other.as_mut() {
+            copy_video_buffer(&other.core, &mut other_vbuf);
+            video_writer.write_frame(other.sbs.composite(&vbuf, &other_vbuf))?;
+        } else {
+            video_writer.write_frame(vbuf.as_slice())?;
         }
-        video_child
-            .stdin
-            .as_mut()
-            .unwrap()
-            .write_all(vbuf.as_slice())?;
-
-        let mut audio_bytes = vec![0u8; samples.len() * 2];
-        LittleEndian::write_i16_into(samples, &mut audio_bytes[..]);
-        audio_child
-            .stdin
-            .as_mut()
-            .unwrap()
-            .write_all(&audio_bytes)?;
+        audio_writer.write_samples(samples)?;
         writeln!(
             std::io::stdout(),
             "{}",
@@ -246,26 +411,222 @@ fn dump_video(args: VideoCli, replay: tango_core::replay::Replay) -> Result<(),
         )?;
     }
 
-    video_child.stdin = None;
-    video_child.wait()?;
-    audio_child.stdin = None;
-    audio_child.wait()?;
+    video_writer.finish()?;
+    audio_writer.finish()?;
+
+    if let Some((video_output, audio_output)) = ffmpeg_outputs {
+        let mut mux_child = std::process::Command::new(&args.ffmpeg);
+        mux_child
+            .args(&["-y"])
+            .args(&["-i"])
+            .arg(video_output.path())
+            .args(&["-i"])
+            .arg(audio_output.path())
+            .args(&["-c:v", "copy", "-c:a", "copy"])
+            .args(shell_words::split(&args.ffmpeg_mux_flags)?)
+            .arg(&args.output_path);
+        #[cfg(windows)]
+        mux_child.creation_flags(CREATE_NO_WINDOW);
+        let mut mux_child = mux_child.spawn()?;
+        mux_child.wait()?;
+    }
+
+    Ok(())
+}
+
+fn open_wav_writer(
+    args: &VideoCli,
+) -> Result<Box<dyn tango_core::export::AudioWriter>, anyhow::Error> {
+    let path = args
+        .audio_output_path
+        .clone()
+        .unwrap_or_else(|| args.output_path.with_extension("wav"));
+    Ok(Box::new(tango_core::export::WavWriter::new(
+        std::io::BufWriter::new(std::fs::File::create(&path)?),
+    )?))
+}
+
+#[cfg(windows)]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+/// Feeds raw frames or samples to an ffmpeg process through its stdin.
+struct FfmpegPipe {
+    child: std::process::Child,
+}
+
+impl FfmpegPipe {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        self.child.stdin.as_mut().unwrap().write_all(buf)
+    }
+
+    fn close(mut self) -> std::io::Result<()> {
+        self.child.stdin = None;
+        self.child.wait()?;
+        Ok(())
+    }
+}
+
+impl tango_core::export::VideoWriter for FfmpegPipe {
+    fn write_frame(&mut self, rgba: &[u8]) -> std::io::Result<()> {
+        self.write(rgba)
+    }
+
+    fn finish(self: Box<Self>) -> std::io::Result<()> {
+        self.close()
+    }
+}
+
+impl tango_core::export::AudioWriter for FfmpegPipe {
+    fn write_samples(&mut self, samples: &[i16]) -> std::io::Result<()> {
+        let mut audio_bytes = vec![0u8; samples.len() * 2];
+        LittleEndian::write_i16_into(samples, &mut audio_bytes[..]);
+        self.write(&audio_bytes)
+    }
+
+    fn finish(self: Box<Self>) -> std::io::Result<()> {
+        self.close()
+    }
+}
+
+/// Starts one ffmpeg process each for video and audio, which encode into temporary files to be muxed together afterwards.
+fn spawn_ffmpeg_writers(
+    args: &VideoCli,
+    width: u32,
+    height: u32,
+) -> Result<
+    (
+        Box<dyn tango_core::export::VideoWriter>,
+        Box<dyn tango_core::export::AudioWriter>,
+        tempfile::NamedTempFile,
+        tempfile::NamedTempFile,
+    ),
+    anyhow::Error,
+> {
+    let video_output = tempfile::NamedTempFile::new()?;
+    let mut video_child = std::process::Command::new(&args.ffmpeg);
+    video_child
+        .stdin(std::process::Stdio::piped())
+        .args(&["-y"])
+        // Input args.
+        .args(&[
+            "-f",
+            "rawvideo",
+            "-pixel_format",
+            "rgba",
+            "-video_size",
+            &format!("{}x{}", width, height),
+            "-framerate",
+            "16777216/280896",
+            "-i",
+            "pipe:",
+        ])
+        // Output args.
+        .args(shell_words::split(&args.ffmpeg_video_flags)?)
+        .args(&["-f", "mp4"])
+        .arg(&video_output.path());
+    #[cfg(windows)]
+    video_child.creation_flags(CREATE_NO_WINDOW);
+    let video_child = video_child.spawn()?;
 
-    let mut mux_child = std::process::Command::new(&args.ffmpeg);
-    mux_child
+    let audio_output = tempfile::NamedTempFile::new()?;
+    let mut audio_child = std::process::Command::new(&args.ffmpeg);
+    audio_child
+        .stdin(std::process::Stdio::piped())
         .args(&["-y"])
-        .args(&["-i"])
-        .arg(video_output.path())
-        .args(&["-i"])
-        .arg(audio_output.path())
-        .args(&["-c:v", "copy", "-c:a", "copy"])
-        .args(shell_words::split(&args.ffmpeg_mux_flags)?)
-        .arg(&args.output_path);
+        // Input args.
+        .args(&["-f", "s16le", "-ar", "48k", "-ac", "2", "-i", "pipe:"])
+        // Output args.
+        .args(shell_words::split(&args.ffmpeg_audio_flags)?)
+        .args(&["-f", "mp4"])
+        .arg(&audio_output.path());
     #[cfg(windows)]
-    mux_child.creation_flags(CREATE_NO_WINDOW);
-    let mut mux_child = mux_child.spawn()?;
-    mux_child.wait()?;
+    audio_child.creation_flags(CREATE_NO_WINDOW);
+    let audio_child = audio_child.spawn()?;
+
+    Ok((
+        Box::new(FfmpegPipe { child: video_child }),
+        Box::new(FfmpegPipe { child: audio_child }),
+        video_output,
+        audio_output,
+    ))
+}
+
+#[derive(serde::Serialize)]
+struct Verified {
+    round_number: u8,
+    num_inputs: u32,
+    p1_public_key: String,
+    p2_public_key: String,
+}
+
+fn dump_verify(
+    _args: VerifyCli,
+    replay: tango_core::replay::Replay,
+    path: &std::path::Path,
+) -> Result<(), anyhow::Error> {
+    let footer = tango_core::replay::ReplayReader::new(Box::new(std::fs::File::open(path)?))?
+        .signature()?
+        .ok_or_else(|| anyhow::anyhow!("replay is not signed"))?;
+    footer.verify(&replay)?;
+    serde_json::to_writer(
+        std::io::stdout(),
+        &Verified {
+            round_number: footer.statement.round_number,
+            num_inputs: footer.statement.num_inputs,
+            p1_public_key: hex::encode(footer.statement.public_keys[0]),
+            p2_public_key: hex::encode(footer.statement.public_keys[1]),
+        },
+    )?;
+    Ok(())
+}
+
+#[derive(serde::Serialize)]
+struct Repaired {
+    was_complete: bool,
+    num_inputs: u32,
+    truncated_at: u64,
+    result: Option<tango_core::replayer::BattleResult>,
+    result_tick: Option<u32>,
+}
+
+fn dump_repair(args: RepairCli, path: &std::path::Path) -> Result<(), anyhow::Error> {
+    let salvage = tango_core::replay::salvage(&std::fs::read(path)?)?;
+    let mut repaired = Repaired {
+        was_complete: salvage.was_complete,
+        num_inputs: salvage.num_inputs(),
+        truncated_at: salvage.truncated_at,
+        result: None,
+        result_tick: None,
+    };
+
+    if salvage.was_complete {
+        log::info!("replay is already complete, nothing to repair");
+    } else {
+        salvage.write_repaired(Box::new(std::fs::File::create(&args.output_path)?))?;
+        log::info!(
+            "salvaged {} input pairs into {}",
+            repaired.num_inputs,
+            args.output_path.display()
+        );
 
+        if let Some(rom_path) = args.rom_path.as_ref() {
+            let patch = args
+                .patch_path
+                .as_ref()
+                .map(|path| tango_core::patch::Patch::read(path))
+                .transpose()?;
+            if let Some(round_result) = tango_core::replayer::evaluate(
+                &std::fs::read(rom_path)?,
+                patch.as_ref(),
+                &tango_core::replay::Replay::decode(std::fs::File::open(&args.output_path)?)?,
+            )? {
+                repaired.result = Some(round_result.result);
+                repaired.result_tick = Some(round_result.tick);
+            }
+        }
+    }
+
+    serde_json::to_writer(std::io::stdout(), &repaired)?;
     Ok(())
 }
 
@@ -411,20 +772,157 @@ fn dump_input_info(
 }
 
 fn dump_eval(args: EvalCli, replay: tango_core::replay::Replay) -> Result<(), anyhow::Error> {
-    let mut core = mgba::core::Core::new_gba("tango_core")?;
     let rom = std::fs::read(&args.rom_path)?;
-    let vf = mgba::vfile::VFile::open_memory(&rom);
+
+    if let Some(result) = tango_core::replayer::evaluate(&rom, None, &replay)? {
+        println!("{}", result.result as u8);
+    }
+
+    Ok(())
+}
+
+fn dump_stats(args: StatsCli, replay: tango_core::replay::Replay) -> Result<(), anyhow::Error> {
+    let rom = std::fs::read(&args.rom_path)?;
+    let patch = args
+        .patch_path
+        .as_ref()
+        .map(|path| tango_core::patch::Patch::read(path))
+        .transpose()?;
+
+    let layout = args
+        .layout_path
+        .as_ref()
+        .map(
+            |path| -> anyhow::Result<tango_core::inspector::BattleStateLayout> {
+                Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+            },
+        )
+        .transpose()?;
+
+    let report = tango_core::inspector::inspect(&rom, patch.as_ref(), &replay, layout)?;
+    if report.battle.is_none() {
+        log::warn!("no battle state layout given, only the result is reported");
+    }
+    serde_json::to_writer(std::io::stdout(), &report)?;
+    Ok(())
+}
+
+const EWRAM_BASE: u32 = 0x02000000;
+const IWRAM_BASE: u32 = 0x03000000;
+
+#[derive(serde::Serialize)]
+struct BisectInput {
+    local_joyflags: u16,
+    local_packet: String,
+    remote_joyflags: u16,
+    remote_packet: String,
+}
+
+impl BisectInput {
+    fn new(
+        ip: &tango_core::input::Pair<tango_core::input::Input, tango_core::input::Input>,
+    ) -> Self {
+        Self {
+            local_joyflags: ip.local.joyflags,
+            local_packet: hex::encode(&ip.local.packet),
+            remote_joyflags: ip.remote.joyflags,
+            remote_packet: hex::encode(&ip.remote.packet),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct BisectMemoryDiff {
+    address: String,
+    len: usize,
+    ours: String,
+    theirs: String,
+}
+
+#[derive(serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum BisectDivergence {
+    Memory {
+        tick: u32,
+        diffs: Vec<BisectMemoryDiff>,
+    },
+    Input {
+        tick: u32,
+        ours: BisectInput,
+        theirs: BisectInput,
+    },
+    RxPacket {
+        tick: u32,
+        ours: BisectInput,
+        theirs: BisectInput,
+    },
+    Length {
+        tick: u32,
+        ours_ended: bool,
+        theirs_ended: bool,
+    },
+}
+
+#[derive(serde::Serialize)]
+struct BisectResult {
+    ticks_compared: u32,
+    divergence: Option<BisectDivergence>,
+}
+
+fn diff_memory(
+    ours: &[u8],
+    theirs: &[u8],
+    base: u32,
+    merge_distance: usize,
+) -> Vec<BisectMemoryDiff> {
+    let mut ranges: Vec<std::ops::Range<usize>> = vec![];
+    for (i, (x, y)) in ours.iter().zip(theirs.iter()).enumerate() {
+        if x == y {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(r) if i - r.end <= merge_distance => {
+                r.end = i + 1;
+            }
+            _ => {
+                ranges.push(i..i + 1);
+            }
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|r| BisectMemoryDiff {
+            address: format!("{:08x}", base + r.start as u32),
+            len: r.len(),
+            ours: hex::encode(&ours[r.clone()]),
+            theirs: hex::encode(&theirs[r]),
+        })
+        .collect()
+}
+
+fn start_bisect_replayer(
+    rom: &[u8],
+    replay: &tango_core::replay::Replay,
+) -> Result<(mgba::core::Core, tango_core::replayer::State), anyhow::Error> {
+    let mut core = mgba::core::Core::new_gba("tango_core")?;
+    let vf = mgba::vfile::VFile::open_memory(rom);
     core.as_mut().load_rom(vf)?;
     core.as_mut().reset();
 
-    let input_pairs = replay.input_pairs.clone();
+    let commit_tick = replay
+        .input_pairs
+        .first()
+        .map(|ip| ip.local.local_tick)
+        .unwrap_or(0);
 
     let replayer_state = tango_core::replayer::State::new(
         replay.local_player_index,
-        input_pairs,
-        0,
+        replay.input_pairs.clone(),
+        commit_tick,
         Box::new(|| {}),
     );
+
     let hooks = tango_core::hooks::get(core.as_mut()).unwrap();
     hooks.patch(core.as_mut());
     {
@@ -433,29 +931,180 @@ fn dump_eval(args: EvalCli, replay: tango_core::replay::Replay) -> Result<(), an
         traps.extend(hooks.replayer_traps(replayer_state.clone()));
         core.set_traps(traps);
     }
-    core.as_mut().load_state(&replay.local_state.unwrap())?;
+    core.as_mut().load_state(
+        replay
+            .local_state
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("replay has no local state"))?,
+    )?;
 
+    Ok((core, replayer_state))
+}
+
+/// Runs the core until the state at the replayer's commit tick is saved, or returns None if the replay runs out first.
+fn step_bisect_replayer(
+    core: &mut mgba::core::Core,
+    replayer_state: &tango_core::replayer::State,
+) -> Result<Option<tango_core::battle::CommittedState>, anyhow::Error> {
     loop {
         {
-            let replayer_state = replayer_state.lock_inner();
+            let mut replayer_state = replayer_state.lock_inner();
+            if let Some(state) = replayer_state.take_committed_state() {
+                let next_tick = state.tick + 1;
+                replayer_state.set_commit_tick(next_tick);
+                return Ok(Some(state));
+            }
+
+            if let Some(err) = replayer_state.take_error() {
+                return Err(err);
+            }
+
             if replayer_state.input_pairs_left() == 0 || replayer_state.is_round_ended() {
-                break;
+                return Ok(None);
             }
         }
 
         core.as_mut().run_frame();
+    }
+}
 
-        {
-            let mut replayer_state = replayer_state.lock_inner();
-            if let Some(err) = replayer_state.take_error() {
-                Err(err)?;
+fn bisect(
+    args: &BisectCli,
+    ours: &tango_core::replay::Replay,
+    theirs: &tango_core::replay::Replay,
+) -> Result<BisectResult, anyhow::Error> {
+    let rom = std::fs::read(&args.rom_path)?;
+
+    let (mut our_core, our_replayer_state) = start_bisect_replayer(&rom, ours)?;
+    let (mut their_core, their_replayer_state) = start_bisect_replayer(&rom, theirs)?;
+
+    let index_input_pairs = |replay: &tango_core::replay::Replay| {
+        replay
+            .input_pairs
+            .iter()
+            .map(|ip| (ip.local.local_tick, ip.clone()))
+            .collect::<std::collections::HashMap<_, _>>()
+    };
+    let mut our_input_pairs = index_input_pairs(ours);
+    let mut their_input_pairs = index_input_pairs(theirs);
+
+    let mut ticks_compared = 0;
+    loop {
+        let our_state = step_bisect_replayer(&mut our_core, &our_replayer_state)?;
+        let their_state = step_bisect_replayer(&mut their_core, &their_replayer_state)?;
+
+        let (our_state, their_state) = match (our_state, their_state) {
+            (Some(our_state), Some(their_state)) => (our_state, their_state),
+            (None, None) => {
+                return Ok(BisectResult {
+                    ticks_compared,
+                    divergence: None,
+                });
+            }
+            (our_state, their_state) => {
+                return Ok(BisectResult {
+                    ticks_compared,
+                    divergence: Some(BisectDivergence::Length {
+                        tick: our_state
+                            .as_ref()
+                            .or(their_state.as_ref())
+                            .map(|s| s.tick)
+                            .unwrap(),
+                        ours_ended: our_state.is_none(),
+                        theirs_ended: their_state.is_none(),
+                    }),
+                });
+            }
+        };
+
+        let tick = our_state.tick;
+        if their_state.tick != tick {
+            anyhow::bail!(
+                "replayers fell out of lockstep: {} != {}",
+                tick,
+                their_state.tick
+            );
+        }
+        ticks_compared += 1;
+
+        // Compare memory first: if it already differs, the inputs at this tick are a consequence, not the cause.
+        let mut diffs = diff_memory(
+            our_state.state.wram(),
+            their_state.state.wram(),
+            EWRAM_BASE,
+            args.merge_distance,
+        );
+        diffs.extend(diff_memory(
+            our_state.state.iwram(),
+            their_state.state.iwram(),
+            IWRAM_BASE,
+            args.merge_distance,
+        ));
+        if !diffs.is_empty() {
+            return Ok(BisectResult {
+                ticks_compared,
+                divergence: Some(BisectDivergence::Memory { tick, diffs }),
+            });
+        }
+
+        let our_ip = match our_input_pairs.remove(&tick) {
+            Some(ip) => ip,
+            None => {
+                continue;
             }
+        };
+        let their_ip = match their_input_pairs.remove(&tick) {
+            Some(ip) => ip,
+            None => {
+                continue;
+            }
+        };
+
+        if our_ip.local.joyflags != their_ip.local.joyflags
+            || our_ip.remote.joyflags != their_ip.remote.joyflags
+        {
+            return Ok(BisectResult {
+                ticks_compared,
+                divergence: Some(BisectDivergence::Input {
+                    tick,
+                    ours: BisectInput::new(&our_ip),
+                    theirs: BisectInput::new(&their_ip),
+                }),
+            });
+        }
+
+        if our_ip.local.packet != their_ip.local.packet
+            || our_ip.remote.packet != their_ip.remote.packet
+        {
+            return Ok(BisectResult {
+                ticks_compared,
+                divergence: Some(BisectDivergence::RxPacket {
+                    tick,
+                    ours: BisectInput::new(&our_ip),
+                    theirs: BisectInput::new(&their_ip),
+                }),
+            });
         }
     }
+}
 
-    if let Some(result) = replayer_state.lock_inner().round_result() {
-        println!("{}", result.result as u8);
+fn dump_bisect(args: BisectCli, replay: tango_core::replay::Replay) -> Result<(), anyhow::Error> {
+    let mut f = std::fs::File::open(&args.other_path)?;
+
+    // The other replay is viewed from the other player's side, so flip it around such that both replays describe the same player.
+    let other_replay = tango_core::replay::Replay::decode(&mut f)?
+        .into_remote()
+        .ok_or_else(|| anyhow::anyhow!("other replay has no remote state"))?;
+
+    if other_replay.local_player_index != replay.local_player_index {
+        anyhow::bail!(
+            "replays are not from opposite sides: local player index {} != {}",
+            replay.local_player_index,
+            other_replay.local_player_index
+        );
     }
 
+    let result = bisect(&args, &replay, &other_replay)?;
+    serde_json::to_writer(std::io::stdout(), &result)?;
     Ok(())
 }
@@ -1,21 +1,359 @@
 #![windows_subsystem = "windows"]
 
-pub const EXPECTED_FPS: f32 = 60.0;
+/// The GBA's actual frame rate, which is what replays are played back at when running at 1x.
+const FPS: f64 = tango_core::export::FRAME_RATE.0 as f64 / tango_core::export::FRAME_RATE.1 as f64;
+
+/// The speeds that playback can be switched between.
+const SPEEDS: &[f64] = &[0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+
+/// How often, in ticks, a snapshot is kept to seek back to.
+const SNAPSHOT_INTERVAL: u32 = 60;
+
+/// The most frames that will be run between two rendered frames, so a slow machine doesn't fall further and further behind.
+const MAX_FRAMES_PER_RENDER: f64 = 60.0;
+
+/// How much audio may be queued before new samples are dropped, in bytes.
+const MAX_QUEUED_AUDIO: u32 = tango_core::export::SAMPLE_RATE / 10 * 2 * 2;
 
 use clap::Parser;
 
+/// Plays back a replay.
+///
+/// Space pauses, period steps forward a frame and comma steps back a frame. Left and right seek by a second (ten with shift), home and end jump to the start and end of the round, and typing a tick and pressing enter seeks to it. Minus and equals change the speed, holding tab doubles it, H toggles the input display and S takes a screenshot. Page up and page down switch between the rounds of a match archive.
 #[derive(clap::Parser)]
 struct Cli {
     #[clap(long)]
     remote: bool,
 
+    /// Show both players' screens side by side.
+    #[clap(long)]
+    both_sides: bool,
+
+    /// Seek to this tick before starting playback.
+    #[clap(long)]
+    start_tick: Option<u32>,
+
     #[clap(parse(from_os_str))]
     rom_path: std::path::PathBuf,
 
+    /// A replay, or a match archive to play every round of in order.
     #[clap(parse(from_os_str))]
     path: std::path::PathBuf,
 }
 
+/// Runs the replay on its own core, keeping snapshots as it goes so it can be seeked backwards.
+struct Player {
+    core: mgba::core::Core,
+    replayer_state: tango_core::replayer::State,
+    input_pairs: Vec<tango_core::input::Pair<tango_core::input::Input, tango_core::input::Input>>,
+    is_complete: bool,
+    snapshots: std::collections::BTreeMap<u32, tango_core::battle::CommittedState>,
+    samples: Vec<i16>,
+}
+
+impl Player {
+    fn new(rom: &[u8], replay: &tango_core::replay::Replay) -> Result<Self, anyhow::Error> {
+        let mut core = mgba::core::Core::new_gba("tango_core")?;
+        let vf = mgba::vfile::VFile::open_memory(rom);
+        core.as_mut().load_rom(vf)?;
+
+        core.enable_video_buffer();
+
+        let hooks = tango_core::hooks::get(core.as_mut()).unwrap();
+        hooks.patch(core.as_mut());
+
+        let first_input_pair = replay
+            .input_pairs
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("replay has no inputs"))?;
+        let initial_snapshot = tango_core::battle::CommittedState {
+            state: replay
+                .local_state
+                .clone()
+                .ok_or_else(|| anyhow::anyhow!("replay has no local state"))?,
+            tick: first_input_pair.local.local_tick,
+            packet: first_input_pair.local.packet.clone(),
+        };
+
+        let replayer_state = tango_core::replayer::State::new(
+            replay.local_player_index,
+            replay.input_pairs.clone(),
+            initial_snapshot.tick + SNAPSHOT_INTERVAL,
+            Box::new(|| {}),
+        );
+        let mut traps = hooks.common_traps();
+        traps.extend(hooks.replayer_traps(replayer_state.clone()));
+        core.set_traps(traps);
+
+        core.as_mut().load_state(&initial_snapshot.state)?;
+
+        Ok(Self {
+            core,
+            replayer_state,
+            input_pairs: replay.input_pairs.clone(),
+            is_complete: replay.is_complete,
+            snapshots: std::collections::BTreeMap::from([(
+                initial_snapshot.tick,
+                initial_snapshot,
+            )]),
+            samples: vec![0i16; tango_core::export::SAMPLE_RATE as usize],
+        })
+    }
+
+    fn current_tick(&self) -> u32 {
+        self.replayer_state.lock_inner().current_tick()
+    }
+
+    fn is_finished(&self) -> bool {
+        let replayer_state = self.replayer_state.lock_inner();
+        (!self.is_complete && replayer_state.input_pairs_left() == 0)
+            || replayer_state.is_round_ended()
+    }
+
+    fn last_tick(&self) -> u32 {
+        self.input_pairs
+            .last()
+            .map(|ip| ip.local.local_tick)
+            .unwrap_or(0)
+    }
+
+    /// The inputs that were most recently fed to the game.
+    fn last_input_pair(
+        &self,
+    ) -> Option<&tango_core::input::Pair<tango_core::input::Input, tango_core::input::Input>> {
+        let tick = self.current_tick().checked_sub(1)?;
+        self.input_pairs
+            .binary_search_by_key(&tick, |ip| ip.local.local_tick)
+            .ok()
+            .map(|i| &self.input_pairs[i])
+    }
+
+    /// Runs a single frame. Audio is only queued if a queue is given: otherwise it is thrown away.
+    fn run_frame(
+        &mut self,
+        audio_queue: Option<&sdl2::audio::AudioQueue<i16>>,
+    ) -> Result<(), anyhow::Error> {
+        self.core.as_mut().run_frame();
+
+        {
+            let mut replayer_state = self.replayer_state.lock_inner();
+            if let Some(err) = replayer_state.take_error() {
+                return Err(err);
+            }
+
+            if let Some(state) = replayer_state.take_committed_state() {
+                replayer_state.set_commit_tick(state.tick + SNAPSHOT_INTERVAL);
+                self.snapshots.insert(state.tick, state);
+            }
+        }
+
+        let clock_rate = self.core.as_ref().frequency();
+        let n = {
+            let mut core = self.core.as_mut();
+            let mut left = core.audio_channel(0);
+            left.set_rates(clock_rate as f64, tango_core::export::SAMPLE_RATE as f64);
+            let n = left.samples_avail();
+            left.read_samples(&mut self.samples[..(n * 2) as usize], n, true);
+            n
+        };
+        {
+            let mut core = self.core.as_mut();
+            let mut right = core.audio_channel(1);
+            right.set_rates(clock_rate as f64, tango_core::export::SAMPLE_RATE as f64);
+            right.read_samples(&mut self.samples[1..(n * 2) as usize], n, true);
+        }
+
+        if let Some(audio_queue) = audio_queue {
+            if audio_queue.size() < MAX_QUEUED_AUDIO {
+                audio_queue
+                    .queue_audio(&self.samples[..(n * 2) as usize])
+                    .map_err(|e| anyhow::anyhow!("failed to queue audio: {}", e))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Plays up to the given tick, or until the replay ends.
+    ///
+    /// If the tick has already been played, this goes back to the last snapshot before it and plays forward from there.
+    fn seek(&mut self, tick: u32) -> Result<(), anyhow::Error> {
+        // Nothing before the first snapshot can be played.
+        let tick = tick.max(*self.snapshots.keys().next().unwrap());
+        let current_tick = self.current_tick();
+        if let Some((&snapshot_tick, snapshot)) = self.snapshots.range(..=tick).next_back() {
+            if tick < current_tick || snapshot_tick > current_tick {
+                self.core.as_mut().load_state(&snapshot.state)?;
+                let start = self
+                    .input_pairs
+                    .partition_point(|ip| ip.local.local_tick < snapshot_tick);
+                let mut replayer_state = self.replayer_state.lock_inner();
+                replayer_state.rewind(snapshot, self.input_pairs[start..].to_vec());
+                replayer_state.set_commit_tick(snapshot_tick + SNAPSHOT_INTERVAL);
+            }
+        }
+
+        while self.current_tick() < tick && !self.is_finished() {
+            self.run_frame(None)?;
+        }
+        Ok(())
+    }
+}
+
+/// Either a single player, or both sides of the replay kept in lockstep and drawn side by side.
+struct View {
+    local_player_index: u8,
+    player: Player,
+    other: Option<(Player, tango_core::sidebyside::SideBySide)>,
+    vbuf: Vec<u8>,
+    other_vbuf: Vec<u8>,
+}
+
+impl View {
+    fn new(
+        rom: &[u8],
+        replay: &tango_core::replay::Replay,
+        remote: bool,
+        both_sides: bool,
+    ) -> Result<Self, anyhow::Error> {
+        let info = replay.info()?;
+        let mut nicknames = [info.local_side, info.remote_side]
+            .map(|side| side.map(|side| side.nickname).unwrap_or_default());
+
+        let other_replay = replay.clone().into_remote().unwrap();
+        let (replay, other_replay) = if remote {
+            // The metadata is always written from the point of view of the player who recorded the replay.
+            nicknames.reverse();
+            (&other_replay, replay)
+        } else {
+            (replay, &other_replay)
+        };
+
+        Ok(Self {
+            local_player_index: replay.local_player_index,
+            player: Player::new(rom, replay)?,
+            other: if both_sides {
+                let [nickname, other_nickname] = &nicknames;
+                Some((
+                    Player::new(rom, other_replay)?,
+                    tango_core::sidebyside::SideBySide::new(nickname, other_nickname)?,
+                ))
+            } else {
+                None
+            },
+            vbuf: vec![0u8; (mgba::gba::SCREEN_WIDTH * mgba::gba::SCREEN_HEIGHT * 4) as usize],
+            other_vbuf: vec![
+                0u8;
+                (mgba::gba::SCREEN_WIDTH * mgba::gba::SCREEN_HEIGHT * 4) as usize
+            ],
+        })
+    }
+
+    fn width(&self) -> u32 {
+        if self.other.is_some() {
+            tango_core::sidebyside::SideBySide::width()
+        } else {
+            mgba::gba::SCREEN_WIDTH
+        }
+    }
+
+    fn height(&self) -> u32 {
+        if self.other.is_some() {
+            tango_core::sidebyside::SideBySide::height()
+        } else {
+            mgba::gba::SCREEN_HEIGHT
+        }
+    }
+
+    /// Where the screens start, below the nicknames if there are any.
+    fn screen_top(&self) -> i32 {
+        if self.other.is_some() {
+            tango_core::sidebyside::LABEL_HEIGHT as i32
+        } else {
+            0
+        }
+    }
+
+    fn current_tick(&self) -> u32 {
+        self.player.current_tick()
+    }
+
+    fn last_tick(&self) -> u32 {
+        self.player.last_tick()
+    }
+
+    fn last_input_pair(
+        &self,
+    ) -> Option<&tango_core::input::Pair<tango_core::input::Input, tango_core::input::Input>> {
+        self.player.last_input_pair()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.player.is_finished()
+            || self
+                .other
+                .as_ref()
+                .map(|(other, _)| other.is_finished())
+                .unwrap_or(false)
+    }
+
+    /// Runs a frame on both sides. Only this side's audio is played.
+    fn run_frame(
+        &mut self,
+        audio_queue: Option<&sdl2::audio::AudioQueue<i16>>,
+    ) -> Result<(), anyhow::Error> {
+        self.player.run_frame(audio_queue)?;
+        if let Some((other, _)) = self.other.as_mut() {
+            // Both games should tick once per frame, but run the other side only while it's behind just in case they don't line up exactly.
+            let tick = self.player.current_tick();
+            while other.current_tick() < tick && !other.is_finished() {
+                other.run_frame(None)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn seek(&mut self, tick: u32) -> Result<(), anyhow::Error> {
+        self.player.seek(tick)?;
+        if let Some((other, _)) = self.other.as_mut() {
+            other.seek(self.player.current_tick())?;
+        }
+        Ok(())
+    }
+
+    /// Returns the current frame, as RGBA.
+    fn frame(&mut self) -> &[u8] {
+        copy_video_buffer(&self.player.core, &mut self.vbuf);
+        if let Some((other, sbs)) = self.other.as_mut() {
+            copy_video_buffer(&other.core, &mut self.other_vbuf);
+            sbs.composite(&self.vbuf, &self.other_vbuf)
+        } else {
+            &self.vbuf
+        }
+    }
+}
+
+fn copy_video_buffer(core: &mgba::core::Core, vbuf: &mut [u8]) {
+    vbuf.copy_from_slice(core.video_buffer().unwrap());
+    for i in (0..vbuf.len()).step_by(4) {
+        vbuf[i + 3] = 0xff;
+    }
+}
+
+/// Describes which buttons are held down, in the order they appear in joyflags.
+fn describe_joyflags(joyflags: u16) -> String {
+    const BUTTONS: &[&str] = &[
+        "A", "B", "SELECT", "START", "RIGHT", "LEFT", "UP", "DOWN", "R", "L",
+    ];
+    BUTTONS
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| joyflags & (1 << i) != 0)
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 fn main() -> Result<(), anyhow::Error> {
     env_logger::Builder::from_default_env()
         .filter(Some("tango_core"), log::LevelFilter::Info)
@@ -25,112 +363,65 @@ fn main() -> Result<(), anyhow::Error> {
 
     let args = Cli::parse();
 
-    let mut f = std::fs::File::open(args.path.clone())?;
-
-    let replay = tango_core::replay::Replay::decode(&mut f)?;
+    let buf = std::fs::read(&args.path)?;
+    let replays = if tango_core::archive::is_archive(&buf) {
+        tango_core::archive::Archive::decode(&buf[..])?
+            .rounds
+            .iter()
+            .map(|round| round.decode_replay())
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        vec![tango_core::replay::Replay::decode(&buf[..])?]
+    };
+    let first_replay = replays
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("match archive has no rounds"))?;
 
     log::info!(
         "replay is for {} (crc32 = {:08x})",
-        replay.local_state.as_ref().unwrap().rom_title(),
-        replay.local_state.as_ref().unwrap().rom_crc32()
+        first_replay.local_state.as_ref().unwrap().rom_title(),
+        first_replay.local_state.as_ref().unwrap().rom_crc32()
     );
 
-    let sdl = sdl2::init().unwrap();
-    let video = sdl.video().unwrap();
-
-    let mut core = mgba::core::Core::new_gba("tango_core")?;
-
     let rom = std::fs::read(&args.rom_path)?;
-    let vf = mgba::vfile::VFile::open_memory(&rom);
-    core.as_mut().load_rom(vf)?;
 
-    core.enable_video_buffer();
+    let mut round_index = 0;
+    let mut view = View::new(&rom, &replays[round_index], args.remote, args.both_sides)?;
 
-    let vbuf = std::sync::Arc::new(parking_lot::Mutex::new(vec![
-        0u8;
-        (mgba::gba::SCREEN_WIDTH * mgba::gba::SCREEN_HEIGHT * 4)
-            as usize
-    ]));
+    if let Some(start_tick) = args.start_tick {
+        view.seek(start_tick)?;
+    }
 
+    let sdl = sdl2::init().unwrap();
+    let video = sdl.video().unwrap();
     let audio = sdl.audio().unwrap();
 
     let window = video
-        .window(
-            "tango replayview",
-            mgba::gba::SCREEN_WIDTH * 3,
-            mgba::gba::SCREEN_HEIGHT * 3,
-        )
+        .window("tango replayview", view.width() * 3, view.height() * 3)
         .opengl()
         .resizable()
         .build()
         .unwrap();
 
-    let hooks = tango_core::hooks::get(core.as_mut()).unwrap();
-    hooks.patch(core.as_mut());
-    let local_player_index = if !args.remote {
-        replay.local_player_index
-    } else {
-        1 - replay.local_player_index
-    };
-
-    let mut input_pairs = replay.input_pairs.clone();
-    if args.remote {
-        for pair in input_pairs.iter_mut() {
-            std::mem::swap(&mut pair.local, &mut pair.remote);
-        }
-    }
-
-    let replayer_state = tango_core::replayer::State::new(
-        local_player_index,
-        input_pairs,
-        0,
-        Box::new(|| {
-            std::process::exit(0);
-        }),
-    );
-    let mut traps = hooks.common_traps();
-    traps.extend(hooks.replayer_traps(replayer_state.clone()));
-    core.set_traps(traps);
-
-    let thread = mgba::thread::Thread::new(core);
-    thread.start().expect("start thread");
-    let thread_handle = thread.handle();
-    thread_handle.pause();
-    thread_handle.lock_audio().sync_mut().set_fps_target(60.0);
-    {
-        let vbuf = vbuf.clone();
-        thread.set_frame_callback(move |_core, video_buffer| {
-            let mut vbuf = vbuf.lock();
-            vbuf.copy_from_slice(video_buffer);
-            for i in (0..vbuf.len()).step_by(4) {
-                vbuf[i + 3] = 0xff;
-            }
-        });
-    }
-
-    let device = audio
-        .open_playback(
+    let audio_queue = audio
+        .open_queue::<i16, _>(
             None,
             &sdl2::audio::AudioSpecDesired {
-                freq: Some(48000),
+                freq: Some(tango_core::export::SAMPLE_RATE as i32),
                 channels: Some(2),
                 samples: Some(512),
             },
-            |spec| {
-                tango_core::audio::mgba_stretch_stream::MGBAStretchStream::new(
-                    thread.handle(),
-                    spec.freq,
-                )
-            },
         )
         .unwrap();
-    device.resume();
+    audio_queue.resume();
 
-    thread.handle().run_on_core(move |mut core| {
-        core.load_state(replay.local_state.as_ref().unwrap())
-            .expect("load state");
-    });
-    thread.handle().unpause();
+    let ttf = sdl2::ttf::init().unwrap();
+    let font = ttf
+        .load_font_from_rwops(
+            sdl2::rwops::RWops::from_bytes(include_bytes!("../fonts/04B_03__.TTF")).unwrap(),
+            8,
+        )
+        .unwrap();
 
     let mut event_loop = sdl.event_pump().unwrap();
     {
@@ -141,7 +432,7 @@ fn main() -> Result<(), anyhow::Error> {
             .build()
             .unwrap();
         canvas
-            .set_logical_size(mgba::gba::SCREEN_WIDTH, mgba::gba::SCREEN_HEIGHT)
+            .set_logical_size(view.width(), view.height())
             .unwrap();
         canvas.set_integer_scale(true).unwrap();
 
@@ -149,76 +440,244 @@ fn main() -> Result<(), anyhow::Error> {
         let mut texture = texture_creator
             .create_texture_streaming(
                 sdl2::pixels::PixelFormatEnum::ABGR8888,
-                mgba::gba::SCREEN_WIDTH,
-                mgba::gba::SCREEN_HEIGHT,
+                view.width(),
+                view.height(),
             )
             .unwrap();
 
         let mut input_state = sdl2_input_helper::State::new();
 
-        let mut take_screenshot_pressed = false;
+        let mut paused = false;
+        let mut speed_index = SPEEDS.iter().position(|s| *s == 1.0).unwrap();
+        let mut show_hud = true;
+        // Digits typed so far for seeking to a tick, confirmed with enter.
+        let mut seek_tick_input = String::new();
+        let mut frames_owed = 0.0;
+        let mut last_render = std::time::Instant::now();
+        let mut was_finished = false;
+
         'toplevel: loop {
             let mut taking_screenshot = false;
+            let mut next_round = false;
+            let mut previous_round = false;
             for event in event_loop.poll_iter() {
-                match event {
+                input_state.handle_event(&event);
+
+                let (scancode, keymod) = match event {
                     sdl2::event::Event::Quit { .. } => break 'toplevel,
+                    sdl2::event::Event::TextInput { text, .. } => {
+                        seek_tick_input.extend(text.chars().filter(|c| c.is_ascii_digit()));
+                        continue;
+                    }
+                    sdl2::event::Event::KeyDown {
+                        scancode: Some(scancode),
+                        keymod,
+                        ..
+                    } => (scancode, keymod),
+                    _ => {
+                        continue;
+                    }
+                };
+
+                let seek_distance = if keymod
+                    .intersects(sdl2::keyboard::Mod::LSHIFTMOD | sdl2::keyboard::Mod::RSHIFTMOD)
+                {
+                    SNAPSHOT_INTERVAL * 10
+                } else {
+                    SNAPSHOT_INTERVAL
+                };
+
+                match scancode {
+                    sdl2::keyboard::Scancode::Space => {
+                        paused = !paused;
+                    }
+                    sdl2::keyboard::Scancode::Period => {
+                        paused = true;
+                        if !view.is_finished() {
+                            view.run_frame(None)?;
+                        }
+                    }
+                    sdl2::keyboard::Scancode::Comma => {
+                        paused = true;
+                        view.seek(view.current_tick().saturating_sub(1))?;
+                    }
+                    sdl2::keyboard::Scancode::Left => {
+                        view.seek(view.current_tick().saturating_sub(seek_distance))?;
+                    }
+                    sdl2::keyboard::Scancode::Right => {
+                        view.seek(view.current_tick() + seek_distance)?;
+                    }
+                    sdl2::keyboard::Scancode::Home => {
+                        view.seek(0)?;
+                    }
+                    sdl2::keyboard::Scancode::End => {
+                        view.seek(u32::MAX)?;
+                    }
+                    sdl2::keyboard::Scancode::Minus => {
+                        speed_index = speed_index.saturating_sub(1);
+                    }
+                    sdl2::keyboard::Scancode::Equals => {
+                        speed_index = (speed_index + 1).min(SPEEDS.len() - 1);
+                    }
+                    sdl2::keyboard::Scancode::PageDown => {
+                        next_round = true;
+                    }
+                    sdl2::keyboard::Scancode::PageUp => {
+                        previous_round = true;
+                    }
+                    sdl2::keyboard::Scancode::H => {
+                        show_hud = !show_hud;
+                    }
+                    sdl2::keyboard::Scancode::S => {
+                        taking_screenshot = true;
+                    }
+                    sdl2::keyboard::Scancode::Return => {
+                        if let Ok(tick) = seek_tick_input.parse() {
+                            view.seek(tick)?;
+                        }
+                        seek_tick_input.clear();
+                    }
+                    sdl2::keyboard::Scancode::Backspace => {
+                        seek_tick_input.pop();
+                    }
+                    sdl2::keyboard::Scancode::Escape => {
+                        seek_tick_input.clear();
+                    }
                     _ => {}
                 }
+            }
 
-                if input_state.handle_event(&event) {
-                    let last_take_screenshot_pressed = take_screenshot_pressed;
-                    take_screenshot_pressed =
-                        input_state.is_key_pressed(sdl2::keyboard::Scancode::S);
-                    taking_screenshot = take_screenshot_pressed && !last_take_screenshot_pressed;
-                }
+            let now = std::time::Instant::now();
+            let elapsed = now - last_render;
+            last_render = now;
 
-                let audio_guard = thread_handle.lock_audio();
-                audio_guard.sync_mut().set_fps_target(
-                    if input_state.is_key_pressed(sdl2::keyboard::Scancode::Tab) {
-                        EXPECTED_FPS * 2.0
-                    } else {
-                        EXPECTED_FPS
-                    },
-                );
+            let mut finished = view.is_finished();
+            if finished && !was_finished {
+                log::info!("replay ended at tick {}", view.current_tick());
+            }
+            was_finished = finished;
+
+            // Match archives carry on into the next round.
+            if ((finished && !paused) || next_round) && round_index + 1 < replays.len() {
+                round_index += 1;
+                log::info!("playing round {}/{}", round_index + 1, replays.len());
+                view = View::new(&rom, &replays[round_index], args.remote, args.both_sides)?;
+                finished = false;
+                was_finished = false;
+            } else if previous_round && round_index > 0 {
+                round_index -= 1;
+                log::info!("playing round {}/{}", round_index + 1, replays.len());
+                view = View::new(&rom, &replays[round_index], args.remote, args.both_sides)?;
+                finished = false;
+                was_finished = false;
             }
 
-            let vbuf = {
-                let mut replayer_state = replayer_state.lock_inner();
-                if let Some(err) = replayer_state.take_error() {
-                    Err(err)?;
+            let speed = SPEEDS[speed_index]
+                * if input_state.is_key_pressed(sdl2::keyboard::Scancode::Tab) {
+                    2.0
+                } else {
+                    1.0
+                };
+
+            if paused || finished {
+                frames_owed = 0.0;
+            } else {
+                frames_owed =
+                    (frames_owed + elapsed.as_secs_f64() * FPS * speed).min(MAX_FRAMES_PER_RENDER);
+                while frames_owed >= 1.0 && !view.is_finished() {
+                    // Audio only makes sense at normal speed: anything else would either starve or flood the queue.
+                    view.run_frame(if speed == 1.0 {
+                        Some(&audio_queue)
+                    } else {
+                        None
+                    })?;
+                    frames_owed -= 1.0;
                 }
+            }
 
-                if (!replay.is_complete && replayer_state.input_pairs_left() == 0)
-                    || replayer_state.is_round_ended()
-                {
-                    break 'toplevel;
-                }
+            let current_tick = view.current_tick();
+            let (width, height) = (view.width(), view.height());
+            let vbuf = view.frame();
+
+            if taking_screenshot {
+                let ss_f = std::fs::File::create(format!(
+                    "{}-tick{}.png",
+                    args.path.clone().with_extension("").to_str().unwrap(),
+                    current_tick
+                ))?;
+                let mut encoder = png::Encoder::new(ss_f, width, height);
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                let mut writer = encoder.write_header().unwrap();
+                writer.write_image_data(vbuf)?;
+            }
+
+            texture.update(None, vbuf, width as usize * 4).unwrap();
+            canvas.clear();
+            canvas.copy(&texture, None, None).unwrap();
+
+            if show_hud {
+                let mut lines = vec![format!(
+                    "{}tick {}/{} {}x{}{}",
+                    if replays.len() > 1 {
+                        format!("round {}/{} ", round_index + 1, replays.len())
+                    } else {
+                        "".to_string()
+                    },
+                    current_tick,
+                    view.last_tick(),
+                    speed,
+                    if finished {
+                        " (ended)"
+                    } else if paused {
+                        " (paused)"
+                    } else {
+                        ""
+                    },
+                    if !seek_tick_input.is_empty() {
+                        format!(" -> {}", seek_tick_input)
+                    } else {
+                        "".to_string()
+                    }
+                )];
 
-                let current_tick = replayer_state.current_tick();
-
-                let vbuf = vbuf.lock();
-                if taking_screenshot {
-                    let ss_f = std::fs::File::create(format!(
-                        "{}-tick{}.png",
-                        args.path.clone().with_extension("").to_str().unwrap(),
-                        current_tick
-                    ))?;
-                    let mut encoder =
-                        png::Encoder::new(ss_f, mgba::gba::SCREEN_WIDTH, mgba::gba::SCREEN_HEIGHT);
-                    encoder.set_color(png::ColorType::Rgba);
-                    encoder.set_depth(png::BitDepth::Eight);
-                    let mut writer = encoder.write_header().unwrap();
-                    writer.write_image_data(&*vbuf)?;
+                if let Some(ip) = view.last_input_pair() {
+                    let (p1, p2) = if view.local_player_index == 0 {
+                        (&ip.local, &ip.remote)
+                    } else {
+                        (&ip.remote, &ip.local)
+                    };
+                    lines.push(format!("P1: {}", describe_joyflags(p1.joyflags)));
+                    lines.push(format!("P2: {}", describe_joyflags(p2.joyflags)));
                 }
 
-                vbuf
-            };
+                for (i, line) in lines.iter().enumerate() {
+                    let surface = font
+                        .render(line)
+                        .shaded(
+                            sdl2::pixels::Color::RGBA(255, 255, 255, 255),
+                            sdl2::pixels::Color::RGBA(0, 0, 0, 255),
+                        )
+                        .unwrap();
+                    let texture = texture_creator
+                        .create_texture_from_surface(&surface)
+                        .unwrap();
+                    let sdl2::render::TextureQuery { width, height, .. } = texture.query();
+                    canvas
+                        .copy(
+                            &texture,
+                            None,
+                            Some(sdl2::rect::Rect::new(
+                                1,
+                                view.screen_top() + 1 + i as i32 * 8,
+                                width,
+                                height,
+                            )),
+                        )
+                        .unwrap();
+                }
+            }
 
-            texture
-                .update(None, &*vbuf, mgba::gba::SCREEN_WIDTH as usize * 4)
-                .unwrap();
-            canvas.clear();
-            canvas.copy(&texture, None, None).unwrap();
             canvas.present();
         }
     }
@@ -0,0 +1,337 @@
+#![windows_subsystem = "windows"]
+
+pub const EXPECTED_FPS: f32 = 60.0;
+
+/// If fewer than this many inputs are available, playback is paused.
+const MIN_BUFFERED_INPUTS: usize = 10;
+
+/// Once paused, playback resumes once this many inputs are available.
+const RESUME_BUFFERED_INPUTS: usize = 60;
+
+use clap::Parser;
+
+#[derive(clap::Parser)]
+struct Cli {
+    /// Watch from the perspective of the broadcaster's opponent.
+    #[clap(long)]
+    remote: bool,
+
+    /// How far behind the live match to play, in seconds.
+    #[clap(long, default_value = "3.0")]
+    delay: f32,
+
+    #[clap(parse(from_os_str))]
+    rom_path: std::path::PathBuf,
+
+    addr: String,
+}
+
+struct SpectatedRound {
+    round_number: u8,
+    replayer_state: tango_core::replayer::State,
+    thread: mgba::thread::Thread,
+    _device: sdl2::audio::AudioDevice<tango_core::audio::mgba_stretch_stream::MGBAStretchStream>,
+    input_ended: bool,
+    paused: bool,
+}
+
+fn start_round(
+    rom: &[u8],
+    audio: &sdl2::AudioSubsystem,
+    vbuf: std::sync::Arc<parking_lot::Mutex<Vec<u8>>>,
+    round_start: tango_core::spectator::RoundStart,
+    remote: bool,
+    first_input_pair: tango_core::input::Pair<tango_core::input::Input, tango_core::input::Input>,
+) -> anyhow::Result<SpectatedRound> {
+    let mut core = mgba::core::Core::new_gba("tango_core")?;
+    let vf = mgba::vfile::VFile::open_memory(rom);
+    core.as_mut().load_rom(vf)?;
+    core.enable_video_buffer();
+
+    let hooks =
+        tango_core::hooks::get(core.as_mut()).ok_or_else(|| anyhow::anyhow!("unsupported game"))?;
+    hooks.patch(core.as_mut());
+
+    let (local_player_index, state) = if !remote {
+        (round_start.local_player_index, round_start.local_state)
+    } else {
+        (1 - round_start.local_player_index, round_start.remote_state)
+    };
+    let state = mgba::state::State::from_slice(&state);
+
+    log::info!(
+        "round {} is for {} (crc32 = {:08x})",
+        round_start.round_number,
+        state.rom_title(),
+        state.rom_crc32()
+    );
+
+    let replayer_state = tango_core::replayer::State::new(
+        local_player_index,
+        vec![first_input_pair],
+        0,
+        Box::new(|| {}),
+    );
+    let mut traps = hooks.common_traps();
+    traps.extend(hooks.replayer_traps(replayer_state.clone()));
+    core.set_traps(traps);
+
+    let thread = mgba::thread::Thread::new(core);
+    thread.start()?;
+    let thread_handle = thread.handle();
+    thread_handle.pause();
+    thread_handle
+        .lock_audio()
+        .sync_mut()
+        .set_fps_target(EXPECTED_FPS);
+    thread.set_frame_callback(move |_core, video_buffer| {
+        let mut vbuf = vbuf.lock();
+        vbuf.copy_from_slice(video_buffer);
+        for i in (0..vbuf.len()).step_by(4) {
+            vbuf[i + 3] = 0xff;
+        }
+    });
+
+    let device = audio
+        .open_playback(
+            None,
+            &sdl2::audio::AudioSpecDesired {
+                freq: Some(48000),
+                channels: Some(2),
+                samples: Some(512),
+            },
+            |spec| {
+                tango_core::audio::mgba_stretch_stream::MGBAStretchStream::new(
+                    thread.handle(),
+                    spec.freq,
+                )
+            },
+        )
+        .map_err(|e| anyhow::anyhow!("open audio: {}", e))?;
+    device.resume();
+
+    thread_handle.run_on_core(move |mut core| {
+        core.load_state(&state).expect("load state");
+    });
+    thread_handle.unpause();
+
+    Ok(SpectatedRound {
+        round_number: round_start.round_number,
+        replayer_state,
+        thread,
+        _device: device,
+        input_ended: false,
+        paused: false,
+    })
+}
+
+fn main() -> Result<(), anyhow::Error> {
+    env_logger::Builder::from_default_env()
+        .filter(Some("tango_core"), log::LevelFilter::Info)
+        .filter(Some("spectateview"), log::LevelFilter::Info)
+        .init();
+    mgba::log::init();
+
+    let args = Cli::parse();
+    let delay = std::time::Duration::from_secs_f32(args.delay);
+
+    let rom = std::fs::read(&args.rom_path)?;
+
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+
+    let mut receiver = rt.block_on(tango_core::spectator::Receiver::connect(&args.addr))?;
+    log::info!("connected to {}", args.addr);
+
+    let (packets_tx, packets_rx) = std::sync::mpsc::channel();
+    rt.spawn(async move {
+        loop {
+            match receiver.receive().await {
+                Ok(Some(packet)) => {
+                    if packets_tx
+                        .send((std::time::Instant::now(), packet))
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+                Ok(None) => {
+                    log::info!("broadcaster closed the connection");
+                    break;
+                }
+                Err(e) => {
+                    log::error!("spectator receive failed: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+
+    let sdl = sdl2::init().unwrap();
+    let video = sdl.video().unwrap();
+    let audio = sdl.audio().unwrap();
+
+    let vbuf = std::sync::Arc::new(parking_lot::Mutex::new(vec![
+        0u8;
+        (mgba::gba::SCREEN_WIDTH * mgba::gba::SCREEN_HEIGHT * 4)
+            as usize
+    ]));
+
+    let window = video
+        .window(
+            "tango spectateview",
+            mgba::gba::SCREEN_WIDTH * 3,
+            mgba::gba::SCREEN_HEIGHT * 3,
+        )
+        .opengl()
+        .resizable()
+        .build()
+        .unwrap();
+
+    let mut event_loop = sdl.event_pump().unwrap();
+    let mut canvas = window.into_canvas().present_vsync().build().unwrap();
+    canvas
+        .set_logical_size(mgba::gba::SCREEN_WIDTH, mgba::gba::SCREEN_HEIGHT)
+        .unwrap();
+    canvas.set_integer_scale(true).unwrap();
+
+    let texture_creator = canvas.texture_creator();
+    let mut texture = texture_creator
+        .create_texture_streaming(
+            sdl2::pixels::PixelFormatEnum::ABGR8888,
+            mgba::gba::SCREEN_WIDTH,
+            mgba::gba::SCREEN_HEIGHT,
+        )
+        .unwrap();
+
+    let mut pending = std::collections::VecDeque::new();
+    let mut disconnected = false;
+    let mut round_start: Option<tango_core::spectator::RoundStart> = None;
+    let mut round: Option<SpectatedRound> = None;
+
+    'toplevel: loop {
+        for event in event_loop.poll_iter() {
+            match event {
+                sdl2::event::Event::Quit { .. } => break 'toplevel,
+                _ => {}
+            }
+        }
+
+        loop {
+            match packets_rx.try_recv() {
+                Ok(packet) => {
+                    pending.push_back(packet);
+                }
+                Err(std::sync::mpsc::TryRecvError::Empty) => {
+                    break;
+                }
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    disconnected = true;
+                    break;
+                }
+            }
+        }
+
+        // Only release packets once they are old enough, so spectators stay a fixed amount of time behind the players.
+        let now = std::time::Instant::now();
+        while pending
+            .front()
+            .map(|(received_at, _)| now.duration_since(*received_at) >= delay)
+            .unwrap_or(false)
+        {
+            let (_, packet) = pending.pop_front().unwrap();
+            match packet {
+                tango_core::spectator::Packet::RoundStart(rs) => {
+                    log::info!("round {} is starting", rs.round_number);
+                    round = None;
+                    round_start = Some(rs);
+                }
+                tango_core::spectator::Packet::Input(input) => {
+                    let round_number = input.round_number;
+                    let mut ip = input.into_pair();
+                    if args.remote {
+                        std::mem::swap(&mut ip.local, &mut ip.remote);
+                    }
+
+                    if let Some(round) = round.as_mut() {
+                        if round.round_number == round_number {
+                            round.replayer_state.lock_inner().push_input_pair(ip);
+                        }
+                        continue;
+                    }
+
+                    let rs = match round_start.take() {
+                        Some(rs) if rs.round_number == round_number => rs,
+                        _ => {
+                            continue;
+                        }
+                    };
+                    round = Some(start_round(
+                        &rom,
+                        &audio,
+                        vbuf.clone(),
+                        rs,
+                        args.remote,
+                        ip,
+                    )?);
+                }
+                tango_core::spectator::Packet::RoundEnd(re) => {
+                    if let Some(round) = round.as_mut() {
+                        if round.round_number == re.round_number {
+                            round.input_ended = true;
+                        }
+                    }
+                }
+                tango_core::spectator::Packet::Hello(_) => {}
+            }
+        }
+
+        let mut round_over = false;
+        if let Some(round) = round.as_mut() {
+            let mut replayer_state = round.replayer_state.lock_inner();
+            if let Some(err) = replayer_state.take_error() {
+                Err(err)?;
+            }
+
+            if replayer_state.is_round_ended()
+                || (round.input_ended && replayer_state.input_pairs_left() == 0)
+            {
+                round_over = true;
+            } else if !round.input_ended && replayer_state.input_pairs_left() < MIN_BUFFERED_INPUTS
+            {
+                // We've nearly caught up to the broadcaster: wait for more inputs to come in, as the emulator thread must never run dry.
+                if !round.paused {
+                    round.thread.handle().pause();
+                    round.paused = true;
+                }
+            } else if round.paused
+                && (round.input_ended
+                    || replayer_state.input_pairs_left() >= RESUME_BUFFERED_INPUTS)
+            {
+                round.thread.handle().unpause();
+                round.paused = false;
+            }
+        }
+        if round_over {
+            log::info!("round ended");
+            round = None;
+        }
+
+        if disconnected && pending.is_empty() && round.is_none() {
+            break 'toplevel;
+        }
+
+        {
+            let vbuf = vbuf.lock();
+            texture
+                .update(None, &*vbuf, mgba::gba::SCREEN_WIDTH as usize * 4)
+                .unwrap();
+        }
+        canvas.clear();
+        canvas.copy(&texture, None, None).unwrap();
+        canvas.present();
+    }
+
+    Ok(())
+}
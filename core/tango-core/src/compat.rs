@@ -0,0 +1,59 @@
+use crate::protocol;
+
+/// Which games can play against each other, by ROM name. This mirrors the families in the launcher's `roms.json5`: different regions of the same game are not compatible.
+const NETPLAY_FAMILIES: &[(&[u8; 16], &str)] = &[
+    (b"MEGAMAN6_FXXBR6E", "bn6"),
+    (b"MEGAMAN6_GXXBR5E", "bn6"),
+    (b"ROCKEXE6_RXXBR6J", "exe6"),
+    (b"ROCKEXE6_GXXBR5J", "exe6"),
+    (b"MEGAMAN5_TP_BRBE", "bn5"),
+    (b"MEGAMAN5_TC_BRKE", "bn5"),
+    (b"ROCKEXE5_TOBBRBJ", "exe5"),
+    (b"ROCKEXE5_TOCBRKJ", "exe5"),
+    (b"ROCKEXE4.5ROBR4J", "exe45"),
+    (b"MEGAMANBN4BMB4BE", "bn4"),
+    (b"MEGAMANBN4RSB4WE", "bn4"),
+    (b"ROCK_EXE4_BMB4BJ", "exe4"),
+    (b"ROCK_EXE4_RSB4WJ", "exe4"),
+    (b"MEGA_EXE3_BLA3XE", "bn3"),
+    (b"MEGA_EXE3_WHA6BE", "bn3"),
+    (b"ROCK_EXE3_BKA3XJ", "exe3"),
+    (b"ROCKMAN_EXE3A6BJ", "exe3"),
+    (b"MEGAMAN_EXE2AE2E", "bn2"),
+    (b"ROCKMAN_EXE2AE2J", "exe2"),
+    (b"MEGAMAN_BN\0\0AREE", "bn1"),
+    (b"ROCKMAN_EXE\0AREJ", "exe1"),
+];
+
+pub fn netplay_family(title: &[u8; 16]) -> Option<&'static str> {
+    NETPLAY_FAMILIES
+        .iter()
+        .find(|(t, _)| *t == title)
+        .map(|(_, family)| *family)
+}
+
+/// Reads the title and revision out of the ROM header, the same way `hooks::get` identifies a game.
+pub fn read_game_info(rom: &[u8]) -> Option<protocol::GameInfo> {
+    Some(protocol::GameInfo {
+        title: rom.get(0xa0..0xb0)?.try_into().unwrap(),
+        revision: *rom.get(0xbc)?,
+        crc32: crc32fast::hash(rom),
+    })
+}
+
+/// Works out the netplay compatibility to announce in our hola.
+///
+/// The launcher's word is taken if it gave one. Otherwise an unpatched game falls back to its ROM's family, but a patched one is left unknown, since the patch may have moved it out of that family.
+pub fn netplay_compatibility(
+    from_launcher: Option<&str>,
+    game_info: &protocol::GameInfo,
+    patched: bool,
+) -> Option<String> {
+    if let Some(compatibility) = from_launcher {
+        return Some(compatibility.to_string());
+    }
+    if patched {
+        return None;
+    }
+    netplay_family(&game_info.title).map(|family| family.to_string())
+}
@@ -0,0 +1,198 @@
+use byteorder::ReadBytesExt;
+use byteorder::WriteBytesExt;
+use std::io::Read;
+use std::io::Seek;
+use std::io::Write;
+
+/// The GBA runs at 16777216 cycles per second and 280896 cycles per frame.
+pub const FRAME_RATE: (u32, u32) = (16777216, 280896);
+
+pub const SAMPLE_RATE: u32 = 48000;
+
+/// Takes RGBA frames as they come out of the emulator.
+pub trait VideoWriter {
+    fn write_frame(&mut self, rgba: &[u8]) -> std::io::Result<()>;
+
+    fn finish(self: Box<Self>) -> std::io::Result<()>;
+}
+
+/// Takes interleaved stereo samples at `SAMPLE_RATE`.
+pub trait AudioWriter {
+    fn write_samples(&mut self, samples: &[i16]) -> std::io::Result<()>;
+
+    fn finish(self: Box<Self>) -> std::io::Result<()>;
+}
+
+/// Writes uncompressed YUV 4:4:4 video, which most tools (including ffmpeg, when it is available later) can read.
+pub struct Y4mWriter<W: std::io::Write> {
+    w: W,
+    width: usize,
+    height: usize,
+    buf: Vec<u8>,
+}
+
+impl<W: std::io::Write> Y4mWriter<W> {
+    pub fn new(mut w: W, width: usize, height: usize) -> std::io::Result<Self> {
+        writeln!(
+            w,
+            "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C444",
+            width, height, FRAME_RATE.0, FRAME_RATE.1
+        )?;
+        Ok(Self {
+            w,
+            width,
+            height,
+            buf: vec![0u8; width * height * 3],
+        })
+    }
+}
+
+impl<W: std::io::Write> VideoWriter for Y4mWriter<W> {
+    fn write_frame(&mut self, rgba: &[u8]) -> std::io::Result<()> {
+        let n = self.width * self.height;
+        let (y, uv) = self.buf.split_at_mut(n);
+        let (u, v) = uv.split_at_mut(n);
+        for (i, px) in rgba.chunks_exact(4).take(n).enumerate() {
+            // BT.601, limited range.
+            let (r, g, b) = (px[0] as i32, px[1] as i32, px[2] as i32);
+            y[i] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+            u[i] = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+            v[i] = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+        }
+        self.w.write_all(b"FRAME\n")?;
+        self.w.write_all(&self.buf)?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> std::io::Result<()> {
+        self.w.flush()
+    }
+}
+
+/// Writes an animated PNG.
+///
+/// APNG wants the number of frames up front, which we don't know until the replay is done, so it is filled in once we have finished.
+pub struct ApngWriter {
+    writer: png::Writer<std::io::BufWriter<std::fs::File>>,
+    file: std::fs::File,
+    num_frames: u32,
+}
+
+impl ApngWriter {
+    pub fn new(file: std::fs::File, width: u32, height: u32) -> std::io::Result<Self> {
+        let mut encoder =
+            png::Encoder::new(std::io::BufWriter::new(file.try_clone()?), width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_animated(u32::MAX, 0).map_err(png_error)?;
+        // As close to FRAME_RATE as the frame delay can get: it only has 16 bits on each side.
+        encoder.set_frame_delay(100, 5973).map_err(png_error)?;
+        Ok(Self {
+            writer: encoder.write_header().map_err(png_error)?,
+            file,
+            num_frames: 0,
+        })
+    }
+}
+
+impl VideoWriter for ApngWriter {
+    fn write_frame(&mut self, rgba: &[u8]) -> std::io::Result<()> {
+        self.writer.write_image_data(rgba).map_err(png_error)?;
+        self.num_frames += 1;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> std::io::Result<()> {
+        let Self {
+            writer,
+            mut file,
+            num_frames,
+        } = *self;
+        // Dropping the writer writes out the IEND chunk and flushes everything to the file.
+        drop(writer);
+
+        file.seek(std::io::SeekFrom::Start(8))?;
+        loop {
+            let len = file.read_u32::<byteorder::BigEndian>()?;
+            let mut kind = [0u8; 4];
+            file.read_exact(&mut kind)?;
+            if &kind != b"acTL" {
+                file.seek(std::io::SeekFrom::Current(len as i64 + 4))?;
+                continue;
+            }
+
+            let mut data = vec![0u8; len as usize];
+            file.read_exact(&mut data)?;
+            data[..4].copy_from_slice(&num_frames.to_be_bytes());
+
+            let mut hasher = crc32fast::Hasher::new();
+            hasher.update(&kind);
+            hasher.update(&data);
+
+            file.seek(std::io::SeekFrom::Current(-(len as i64)))?;
+            file.write_all(&data)?;
+            file.write_u32::<byteorder::BigEndian>(hasher.finalize())?;
+            return Ok(());
+        }
+    }
+}
+
+fn png_error(e: png::EncodingError) -> std::io::Error {
+    std::io::Error::other(e)
+}
+
+/// Writes 16-bit stereo PCM.
+pub struct WavWriter<W: std::io::Write + std::io::Seek> {
+    w: W,
+    data_len: u32,
+}
+
+const WAV_HEADER_SIZE: u32 = 44;
+
+impl<W: std::io::Write + std::io::Seek> WavWriter<W> {
+    pub fn new(mut w: W) -> std::io::Result<Self> {
+        const NUM_CHANNELS: u16 = 2;
+        const BITS_PER_SAMPLE: u16 = 16;
+        let block_align = NUM_CHANNELS * BITS_PER_SAMPLE / 8;
+
+        w.write_all(b"RIFF")?;
+        // Filled in by finish.
+        w.write_u32::<byteorder::LittleEndian>(0)?;
+        w.write_all(b"WAVE")?;
+
+        w.write_all(b"fmt ")?;
+        w.write_u32::<byteorder::LittleEndian>(16)?;
+        // PCM.
+        w.write_u16::<byteorder::LittleEndian>(1)?;
+        w.write_u16::<byteorder::LittleEndian>(NUM_CHANNELS)?;
+        w.write_u32::<byteorder::LittleEndian>(SAMPLE_RATE)?;
+        w.write_u32::<byteorder::LittleEndian>(SAMPLE_RATE * block_align as u32)?;
+        w.write_u16::<byteorder::LittleEndian>(block_align)?;
+        w.write_u16::<byteorder::LittleEndian>(BITS_PER_SAMPLE)?;
+
+        w.write_all(b"data")?;
+        // Filled in by finish.
+        w.write_u32::<byteorder::LittleEndian>(0)?;
+
+        Ok(Self { w, data_len: 0 })
+    }
+}
+
+impl<W: std::io::Write + std::io::Seek> AudioWriter for WavWriter<W> {
+    fn write_samples(&mut self, samples: &[i16]) -> std::io::Result<()> {
+        for sample in samples {
+            self.w.write_i16::<byteorder::LittleEndian>(*sample)?;
+        }
+        self.data_len += samples.len() as u32 * 2;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> std::io::Result<()> {
+        self.w.seek(std::io::SeekFrom::Start(4))?;
+        self.w
+            .write_u32::<byteorder::LittleEndian>(WAV_HEADER_SIZE - 8 + self.data_len)?;
+        self.w.seek(std::io::SeekFrom::Start(40))?;
+        self.w.write_u32::<byteorder::LittleEndian>(self.data_len)?;
+        self.w.flush()
+    }
+}
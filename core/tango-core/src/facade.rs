@@ -1,4 +1,5 @@
 use crate::battle;
+use crate::protocol;
 
 struct InnerFacade {
     match_: std::sync::Arc<tokio::sync::Mutex<Option<std::sync::Arc<battle::Match>>>>,
@@ -22,12 +23,32 @@ impl Facade {
         self.0.borrow().match_.lock().await.clone()
     }
 
-    pub async fn abort_match(&self) {
-        *self.0.borrow().match_.lock().await = None;
-        self.0.borrow().cancellation_token.cancel();
+    /// Leaves the match, telling the opponent why first.
+    pub async fn abort_match(&self, reason: protocol::GoodbyeReason) {
+        let (match_, cancellation_token) = {
+            let inner = self.0.borrow();
+            (inner.match_.clone(), inner.cancellation_token.clone())
+        };
+        abort_match(&match_, &cancellation_token, reason).await;
     }
 
     pub async fn end_match(&self) {
         std::process::exit(0);
     }
 }
+
+/// Like `Facade::abort_match`, for callers off the emulator thread.
+pub async fn abort_match(
+    match_: &tokio::sync::Mutex<Option<std::sync::Arc<battle::Match>>>,
+    cancellation_token: &tokio_util::sync::CancellationToken,
+    reason: protocol::GoodbyeReason,
+) {
+    let inner_match = match_.lock().await.clone();
+    if let Some(inner_match) = inner_match {
+        if let Err(e) = inner_match.say_goodbye(reason).await {
+            log::error!("failed to say goodbye: {}", e);
+        }
+    }
+    *match_.lock().await = None;
+    cancellation_token.cancel();
+}
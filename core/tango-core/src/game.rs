@@ -1,4 +1,4 @@
-use crate::{audio, battle, facade, hooks, ipc, tps};
+use crate::{audio, battle, facade, hooks, ipc, patch, protocol, tps};
 use parking_lot::Mutex;
 use rand::SeedableRng;
 use std::sync::Arc;
@@ -88,62 +88,31 @@ impl InputMapping {
     }
 }
 
-pub struct Game {
-    rt: tokio::runtime::Runtime,
-    ipc_sender: Arc<Mutex<ipc::Sender>>,
-    fps_counter: Arc<Mutex<tps::Counter>>,
-    emu_tps_counter: Arc<Mutex<tps::Counter>>,
-    match_: Option<std::sync::Arc<tokio::sync::Mutex<Option<Arc<battle::Match>>>>>,
-    event_loop: sdl2::EventPump,
-    game_controller: sdl2::GameControllerSubsystem,
-    canvas: sdl2::render::Canvas<sdl2::video::Window>,
-    _audio_device: sdl2::audio::AudioDevice<audio::mgba_stretch_stream::MGBAStretchStream>,
-    vbuf: Arc<Mutex<Vec<u8>>>,
-    joyflags: Arc<std::sync::atomic::AtomicU32>,
-    input_mapping: InputMapping,
+pub mod headless;
+
+/// The emulator thread and the match it is playing, if any.
+///
+/// This is shared between the windowed and headless frontends: the frontend only decides where video, audio and input go.
+struct Session {
     thread: mgba::thread::Thread,
+    match_: Option<std::sync::Arc<tokio::sync::Mutex<Option<Arc<battle::Match>>>>>,
+    /// Set if the match ended before it was over, at which point the frontend should stop.
+    match_ended: Arc<Mutex<Option<battle::MatchEnded>>>,
 }
 
-impl Game {
-    pub fn new(
-        rt: tokio::runtime::Runtime,
+impl Session {
+    fn new(
+        handle: tokio::runtime::Handle,
         ipc_sender: Arc<Mutex<ipc::Sender>>,
-        window_title: String,
-        input_mapping: InputMapping,
+        mut ipc_receiver: ipc::Receiver,
         rom_path: std::path::PathBuf,
+        patch: Option<patch::Patch>,
         save_path: std::path::PathBuf,
-        window_scale: u32,
         match_init: Option<battle::MatchInit>,
-    ) -> Result<Game, anyhow::Error> {
-        let handle = rt.handle().clone();
-
-        let vbuf = Arc::new(Mutex::new(vec![
-            0u8;
-            (mgba::gba::SCREEN_WIDTH * mgba::gba::SCREEN_HEIGHT * 4)
-                as usize
-        ]));
-
-        let sdl = sdl2::init().unwrap();
-        let video = sdl.video().unwrap();
-        let game_controller = sdl.game_controller().unwrap();
-        let audio = sdl.audio().unwrap();
-
-        let event_loop = sdl.event_pump().unwrap();
-
-        let window = video
-            .window(
-                &format!("Tango: {}", window_title),
-                mgba::gba::SCREEN_WIDTH * window_scale,
-                mgba::gba::SCREEN_HEIGHT * window_scale,
-            )
-            .opengl()
-            .resizable()
-            .build()
-            .unwrap();
-
-        let fps_counter = Arc::new(Mutex::new(tps::Counter::new(30)));
-        let emu_tps_counter = Arc::new(Mutex::new(tps::Counter::new(10)));
-
+        joyflags: Arc<std::sync::atomic::AtomicU32>,
+        emu_tps_counter: Arc<Mutex<tps::Counter>>,
+        on_frame: impl Fn(&[u8]) + Send + 'static,
+    ) -> Result<Session, anyhow::Error> {
         let mut core = mgba::core::Core::new_gba("tango")?;
         core.enable_video_buffer();
 
@@ -151,6 +120,11 @@ impl Game {
         let rom_vf = mgba::vfile::VFile::open_memory(&rom);
         core.as_mut().load_rom(rom_vf)?;
 
+        if let Some(patch) = patch.as_ref() {
+            patch.apply(core.as_mut())?;
+            log::info!("applied {:?} patch", patch.format());
+        }
+
         log::info!(
             "loaded game: {} rev {}",
             std::str::from_utf8(&core.as_mut().full_rom_name()).unwrap(),
@@ -172,11 +146,10 @@ impl Game {
         let hooks = hooks::get(core.as_mut()).unwrap();
         hooks.patch(core.as_mut());
 
-        let joyflags = Arc::new(std::sync::atomic::AtomicU32::new(0));
-
         let cancellation_token = tokio_util::sync::CancellationToken::new();
 
         let inner_match = std::sync::Arc::new(tokio::sync::Mutex::new(None));
+        let match_ended = Arc::new(Mutex::new(None));
         if let Some(match_init) = match_init.as_ref() {
             let _ = std::fs::create_dir_all(match_init.settings.replays_path.parent().unwrap());
             let mut traps = hooks.common_traps();
@@ -194,43 +167,78 @@ impl Game {
         let thread = mgba::thread::Thread::new(core);
 
         let match_ = if let Some(match_init) = match_init {
-            let (dc_rx, dc_tx) = match_init.dc.split();
+            let battle::MatchInit {
+                tx,
+                rx,
+                peer_conn,
+                reconnector,
+                is_offerer,
+                rtt_tracker,
+                settings,
+            } = match_init;
 
             {
                 let inner_match = inner_match.clone();
                 handle.block_on(async {
-                    let is_offerer = match_init.peer_conn.local_description().unwrap().sdp_type
-                        == datachannel_wrapper::SdpType::Offer;
-                    let rng_seed = match_init
-                        .settings
-                        .rng_seed
-                        .clone()
-                        .try_into()
-                        .expect("rng seed");
+                    let rng_seed = settings.rng_seed.clone().try_into().expect("rng seed");
                     *inner_match.lock().await = Some(
                         battle::Match::new(
                             rom,
+                            patch,
                             hooks,
-                            match_init.peer_conn,
-                            dc_tx,
+                            peer_conn,
+                            reconnector,
+                            tx,
                             rand_pcg::Mcg128Xsl64::from_seed(rng_seed),
                             is_offerer,
                             thread.handle(),
                             ipc_sender.clone(),
-                            match_init.settings,
+                            rtt_tracker,
+                            match_ended.clone(),
+                            settings,
                         )
                         .expect("new match"),
                     );
                 });
             }
 
+            {
+                let inner_match = inner_match.clone();
+                let cancellation_token = cancellation_token.clone();
+                handle.spawn(async move {
+                    loop {
+                        let msg = match ipc_receiver.receive().await {
+                            Ok(msg) => msg,
+                            Err(e) => {
+                                log::info!("no more ipc requests during match: {}", e);
+                                break;
+                            }
+                        };
+                        match msg.which {
+                            Some(ipc::protos::to_core_message::Which::ForfeitReq(_)) => {
+                                facade::abort_match(
+                                    &inner_match,
+                                    &cancellation_token,
+                                    protocol::GoodbyeReason::Forfeit,
+                                )
+                                .await;
+                                break;
+                            }
+                            p => {
+                                log::warn!("unexpected ipc request during match: {:?}", p);
+                            }
+                        }
+                    }
+                });
+            }
+
             {
                 let inner_match = inner_match.clone();
                 handle.spawn(async move {
                     {
                         let inner_match = inner_match.lock().await.clone().unwrap();
                         tokio::select! {
-                            Err(e) = inner_match.run(dc_rx) => {
+                            Err(e) = inner_match.run(rx) => {
                                 log::info!("match thread ending: {:?}", e);
                             }
                             _ = cancellation_token.cancelled() => {
@@ -246,6 +254,13 @@ impl Game {
             None
         };
 
+        thread.set_frame_callback(move |mut core, video_buffer| {
+            on_frame(video_buffer);
+            core.set_keys(joyflags.load(std::sync::atomic::Ordering::Relaxed));
+            let mut emu_tps_counter = emu_tps_counter.lock();
+            emu_tps_counter.mark();
+        });
+
         thread.start()?;
         thread
             .handle()
@@ -253,6 +268,101 @@ impl Game {
             .sync_mut()
             .set_fps_target(EXPECTED_FPS);
 
+        Ok(Session {
+            thread,
+            match_,
+            match_ended,
+        })
+    }
+}
+
+pub struct Game {
+    rt: tokio::runtime::Runtime,
+    ipc_sender: Arc<Mutex<ipc::Sender>>,
+    fps_counter: Arc<Mutex<tps::Counter>>,
+    emu_tps_counter: Arc<Mutex<tps::Counter>>,
+    match_: Option<std::sync::Arc<tokio::sync::Mutex<Option<Arc<battle::Match>>>>>,
+    match_ended: Arc<Mutex<Option<battle::MatchEnded>>>,
+    event_loop: sdl2::EventPump,
+    game_controller: sdl2::GameControllerSubsystem,
+    canvas: sdl2::render::Canvas<sdl2::video::Window>,
+    _audio_device: sdl2::audio::AudioDevice<audio::mgba_stretch_stream::MGBAStretchStream>,
+    vbuf: Arc<Mutex<Vec<u8>>>,
+    joyflags: Arc<std::sync::atomic::AtomicU32>,
+    input_mapping: InputMapping,
+    thread: mgba::thread::Thread,
+}
+
+impl Game {
+    pub fn new(
+        rt: tokio::runtime::Runtime,
+        ipc_sender: Arc<Mutex<ipc::Sender>>,
+        ipc_receiver: ipc::Receiver,
+        window_title: String,
+        input_mapping: InputMapping,
+        rom_path: std::path::PathBuf,
+        patch: Option<patch::Patch>,
+        save_path: std::path::PathBuf,
+        window_scale: u32,
+        match_init: Option<battle::MatchInit>,
+    ) -> Result<Game, anyhow::Error> {
+        let handle = rt.handle().clone();
+
+        let vbuf = Arc::new(Mutex::new(vec![
+            0u8;
+            (mgba::gba::SCREEN_WIDTH * mgba::gba::SCREEN_HEIGHT * 4)
+                as usize
+        ]));
+
+        let sdl = sdl2::init().unwrap();
+        let video = sdl.video().unwrap();
+        let game_controller = sdl.game_controller().unwrap();
+        let audio = sdl.audio().unwrap();
+
+        let event_loop = sdl.event_pump().unwrap();
+
+        let window = video
+            .window(
+                &format!("Tango: {}", window_title),
+                mgba::gba::SCREEN_WIDTH * window_scale,
+                mgba::gba::SCREEN_HEIGHT * window_scale,
+            )
+            .opengl()
+            .resizable()
+            .build()
+            .unwrap();
+
+        let fps_counter = Arc::new(Mutex::new(tps::Counter::new(30)));
+        let emu_tps_counter = Arc::new(Mutex::new(tps::Counter::new(10)));
+
+        let joyflags = Arc::new(std::sync::atomic::AtomicU32::new(0));
+
+        let Session {
+            thread,
+            match_,
+            match_ended,
+        } = Session::new(
+            handle,
+            ipc_sender.clone(),
+            ipc_receiver,
+            rom_path,
+            patch,
+            save_path,
+            match_init,
+            joyflags.clone(),
+            emu_tps_counter.clone(),
+            {
+                let vbuf = vbuf.clone();
+                move |video_buffer| {
+                    let mut vbuf = vbuf.lock();
+                    vbuf.copy_from_slice(video_buffer);
+                    for i in (0..vbuf.len()).step_by(4) {
+                        vbuf[i + 3] = 0xff;
+                    }
+                }
+            },
+        )?;
+
         let audio_device = audio
             .open_playback(
                 None,
@@ -269,22 +379,6 @@ impl Game {
         log::info!("audio spec: {:?}", audio_device.spec());
         audio_device.resume();
 
-        {
-            let joyflags = joyflags.clone();
-            let vbuf = vbuf.clone();
-            let emu_tps_counter = emu_tps_counter.clone();
-            thread.set_frame_callback(move |mut core, video_buffer| {
-                let mut vbuf = vbuf.lock();
-                vbuf.copy_from_slice(video_buffer);
-                for i in (0..vbuf.len()).step_by(4) {
-                    vbuf[i + 3] = 0xff;
-                }
-                core.set_keys(joyflags.load(std::sync::atomic::Ordering::Relaxed));
-                let mut emu_tps_counter = emu_tps_counter.lock();
-                emu_tps_counter.mark();
-            });
-        }
-
         let mut canvas = window
             .into_canvas()
             .accelerated()
@@ -309,6 +403,7 @@ impl Game {
             vbuf,
             joyflags,
             match_,
+            match_ended,
             thread,
         })
     }
@@ -427,6 +522,10 @@ impl Game {
                 );
             }
 
+            if self.match_ended.lock().is_some() {
+                break 'toplevel;
+            }
+
             texture
                 .update(
                     None,
@@ -448,6 +547,11 @@ impl Game {
                     self.rt.block_on(async {
                         if let Some(match_) = &*match_.lock().await {
                             lines.push("match active".to_string());
+                            if !match_.is_connected() {
+                                // The round state is held until we've reconnected, so don't wait on it here.
+                                lines.push("reconnecting...".to_string());
+                                return 0.0;
+                            }
                             let round_state = match_.lock_round_state().await;
                             if let Some(round) = round_state.round.as_ref() {
                                 lines.push(format!(
@@ -505,6 +609,21 @@ impl Game {
             self.fps_counter.lock().mark();
         }
 
+        if let Some(match_ended) = *self.match_ended.lock() {
+            log::info!("match ended early: {}", match_ended);
+            return Err(match_ended.into());
+        }
+
+        if let Some(match_) = self.match_.as_ref() {
+            self.rt.block_on(async {
+                if let Some(match_) = &*match_.lock().await {
+                    if let Err(e) = match_.say_goodbye(protocol::GoodbyeReason::UserQuit).await {
+                        log::error!("failed to say goodbye: {}", e);
+                    }
+                }
+            });
+        }
+
         Ok(())
     }
 }
@@ -0,0 +1,241 @@
+use crate::{audio, battle, ipc, patch, tps};
+use parking_lot::Mutex;
+use sdl2::audio::AudioCallback;
+use std::sync::Arc;
+
+const SAMPLE_RATE: i32 = 48000;
+const NUM_CHANNELS: usize = 2;
+
+/// A sequence of inputs to hold for a given number of frames each, looped forever.
+///
+/// Each line is of the form `<frames> <keys>`, where keys is a `+`-separated list of `A`, `B`, `L`, `R`, `UP`, `DOWN`, `LEFT`, `RIGHT`, `START` and `SELECT`, or `-` for no keys. Blank lines and lines starting with `#` are ignored.
+#[derive(Clone, Debug)]
+pub struct InputScript {
+    steps: Vec<(u32, u32)>,
+    step: usize,
+    frame: u32,
+}
+
+impl InputScript {
+    pub fn parse(s: &str) -> anyhow::Result<Self> {
+        let mut steps = vec![];
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (frames, keys) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| anyhow::anyhow!("line {}: expected <frames> <keys>", i + 1))?;
+            let frames = frames
+                .parse::<u32>()
+                .map_err(|e| anyhow::anyhow!("line {}: invalid frame count: {}", i + 1, e))?;
+
+            let keys = keys.trim();
+            let joyflags = if keys == "-" {
+                0
+            } else {
+                keys.split('+')
+                    .map(|key| {
+                        Ok(match key.trim().to_ascii_uppercase().as_str() {
+                            "A" => mgba::input::keys::A,
+                            "B" => mgba::input::keys::B,
+                            "L" => mgba::input::keys::L,
+                            "R" => mgba::input::keys::R,
+                            "UP" => mgba::input::keys::UP,
+                            "DOWN" => mgba::input::keys::DOWN,
+                            "LEFT" => mgba::input::keys::LEFT,
+                            "RIGHT" => mgba::input::keys::RIGHT,
+                            "START" => mgba::input::keys::START,
+                            "SELECT" => mgba::input::keys::SELECT,
+                            key => anyhow::bail!("line {}: unknown key: {}", i + 1, key),
+                        })
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?
+                    .into_iter()
+                    .fold(0, |acc, key| acc | key)
+            };
+
+            if frames > 0 {
+                steps.push((frames, joyflags));
+            }
+        }
+
+        Ok(Self {
+            steps,
+            step: 0,
+            frame: 0,
+        })
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            steps: vec![],
+            step: 0,
+            frame: 0,
+        }
+    }
+
+    /// Returns the keys to hold for the next frame.
+    pub fn next(&mut self) -> u32 {
+        let (frames, joyflags) = if let Some(step) = self.steps.get(self.step) {
+            *step
+        } else {
+            return 0;
+        };
+
+        self.frame += 1;
+        if self.frame >= frames {
+            self.frame = 0;
+            self.step = (self.step + 1) % self.steps.len();
+        }
+        joyflags
+    }
+}
+
+/// Runs the core without any window or audio device, e.g. for automated testing.
+pub struct HeadlessGame {
+    rt: tokio::runtime::Runtime,
+    ipc_sender: Arc<Mutex<ipc::Sender>>,
+    match_: Option<std::sync::Arc<tokio::sync::Mutex<Option<Arc<battle::Match>>>>>,
+    match_ended: Arc<Mutex<Option<battle::MatchEnded>>>,
+    thread: mgba::thread::Thread,
+}
+
+impl HeadlessGame {
+    pub fn new(
+        rt: tokio::runtime::Runtime,
+        ipc_sender: Arc<Mutex<ipc::Sender>>,
+        ipc_receiver: ipc::Receiver,
+        input_script: InputScript,
+        rom_path: std::path::PathBuf,
+        patch: Option<patch::Patch>,
+        save_path: std::path::PathBuf,
+        match_init: Option<battle::MatchInit>,
+    ) -> Result<HeadlessGame, anyhow::Error> {
+        let handle = rt.handle().clone();
+
+        let joyflags = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let emu_tps_counter = Arc::new(Mutex::new(tps::Counter::new(10)));
+
+        let super::Session {
+            thread,
+            match_,
+            match_ended,
+        } = super::Session::new(
+            handle,
+            ipc_sender.clone(),
+            ipc_receiver,
+            rom_path,
+            patch,
+            save_path,
+            match_init,
+            joyflags.clone(),
+            emu_tps_counter,
+            {
+                let joyflags = joyflags.clone();
+                let input_script = Mutex::new(input_script);
+                move |_| {
+                    joyflags.store(
+                        input_script.lock().next(),
+                        std::sync::atomic::Ordering::Relaxed,
+                    );
+                }
+            },
+        )?;
+
+        Ok(HeadlessGame {
+            rt,
+            ipc_sender,
+            match_,
+            match_ended,
+            thread,
+        })
+    }
+
+    pub fn run(self) -> anyhow::Result<()> {
+        self.run_for(None)
+    }
+
+    /// Runs until the match ends, or until the given number of frames have gone by.
+    pub fn run_for(self, max_frames: Option<u64>) -> anyhow::Result<()> {
+        log::info!("running headless...");
+        self.rt.block_on(async {
+            self.ipc_sender
+                .lock()
+                .send(ipc::protos::FromCoreMessage {
+                    which: Some(ipc::protos::from_core_message::Which::StateEv(
+                        ipc::protos::from_core_message::StateEvent {
+                            state: ipc::protos::from_core_message::state_event::State::Running
+                                .into(),
+                        },
+                    )),
+                })
+                .await?;
+            anyhow::Result::<()>::Ok(())
+        })?;
+
+        let thread_handle = self.thread.handle();
+
+        // There is no audio device to pull samples out of the core, so we do it ourselves once per frame: the core is paced by audio sync, so this is what keeps it running at EXPECTED_FPS (plus any adjustment the match makes).
+        let mut stream =
+            audio::mgba_stretch_stream::MGBAStretchStream::new(thread_handle.clone(), SAMPLE_RATE);
+        let frame_duration = std::time::Duration::from_secs_f32(1.0 / super::EXPECTED_FPS);
+        let mut buf =
+            vec![0i16; (SAMPLE_RATE as f32 / super::EXPECTED_FPS) as usize * NUM_CHANNELS];
+
+        let mut next_frame = std::time::Instant::now();
+        let mut num_frames = 0u64;
+        loop {
+            if max_frames
+                .map(|max_frames| num_frames >= max_frames)
+                .unwrap_or(false)
+            {
+                log::info!("ran for {} frames, exiting", num_frames);
+                break;
+            }
+            num_frames += 1;
+
+            next_frame += frame_duration;
+            let now = std::time::Instant::now();
+            if next_frame > now {
+                std::thread::sleep(next_frame - now);
+            } else {
+                // We've fallen behind, so don't try to catch up all at once.
+                next_frame = now;
+            }
+
+            stream.callback(&mut buf);
+
+            if thread_handle.has_crashed() {
+                // HACK: No better way to lock the core.
+                let audio_guard = thread_handle.lock_audio();
+                panic!(
+                    "mgba thread crashed!\nlr = {:08x}, pc = {:08x}",
+                    audio_guard.core().gba().cpu().gpr(14),
+                    audio_guard.core().gba().cpu().thumb_pc()
+                );
+            }
+
+            if thread_handle.has_exited() {
+                break;
+            }
+
+            // Without a window to close, the only way a headless match ends is by the match itself ending.
+            if let Some(match_) = self.match_.as_ref() {
+                if self.rt.block_on(async { match_.lock().await.is_none() }) {
+                    log::info!("match ended, exiting");
+                    break;
+                }
+            }
+        }
+
+        if let Some(match_ended) = *self.match_ended.lock() {
+            log::info!("match ended early: {}", match_ended);
+            return Err(match_ended.into());
+        }
+
+        Ok(())
+    }
+}
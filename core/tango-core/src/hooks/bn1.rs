@@ -4,7 +4,7 @@ mod offsets;
 use byteorder::ByteOrder;
 use rand::Rng;
 
-use crate::{battle, facade, hooks, input, replayer, shadow};
+use crate::{battle, facade, hooks, input, protocol, replayer, shadow};
 
 #[derive(Clone)]
 pub struct BN2 {
@@ -326,7 +326,7 @@ impl hooks::Hooks for BN2 {
                                 }
                                 return;
                             }
-                            facade.abort_match().await;
+                            facade.abort_match(protocol::GoodbyeReason::Crash).await;
                         });
                     }),
                 )
@@ -819,6 +819,12 @@ impl hooks::Hooks for BN2 {
                             );
                         }
 
+                        if replayer_state.wants_state_checksum() {
+                            replayer_state.add_state_checksum(
+                                &core.save_state().expect("save checksum state"),
+                            );
+                        }
+
                         let ip = match replayer_state.peek_input_pair() {
                             Some(ip) => ip.clone(),
                             None => {
@@ -3,7 +3,7 @@ mod offsets;
 
 use byteorder::ByteOrder;
 
-use crate::{battle, facade, hooks, input, replayer, shadow};
+use crate::{battle, facade, hooks, input, protocol, replayer, shadow};
 
 #[derive(Clone)]
 pub struct BN2 {
@@ -412,7 +412,7 @@ impl hooks::Hooks for BN2 {
                                 }
                                 return;
                             }
-                            facade.abort_match().await;
+                            facade.abort_match(protocol::GoodbyeReason::Crash).await;
                         });
                     }),
                 )
@@ -933,6 +933,12 @@ impl hooks::Hooks for BN2 {
                             );
                         }
 
+                        if replayer_state.wants_state_checksum() {
+                            replayer_state.add_state_checksum(
+                                &core.save_state().expect("save checksum state"),
+                            );
+                        }
+
                         let ip = match replayer_state.peek_input_pair() {
                             Some(ip) => ip.clone(),
                             None => {
@@ -3,7 +3,7 @@ mod offsets;
 
 use byteorder::ByteOrder;
 
-use crate::{battle, facade, hooks, input, replayer, shadow};
+use crate::{battle, facade, hooks, input, protocol, replayer, shadow};
 
 #[derive(Clone)]
 pub struct BN3 {
@@ -468,7 +468,7 @@ impl hooks::Hooks for BN3 {
                                 }
                                 return;
                             }
-                            facade.abort_match().await;
+                            facade.abort_match(protocol::GoodbyeReason::Crash).await;
                         });
                     }),
                 )
@@ -1051,6 +1051,12 @@ impl hooks::Hooks for BN3 {
                             );
                         }
 
+                        if replayer_state.wants_state_checksum() {
+                            replayer_state.add_state_checksum(
+                                &core.save_state().expect("save checksum state"),
+                            );
+                        }
+
                         let ip = match replayer_state.peek_input_pair() {
                             Some(ip) => ip.clone(),
                             None => {
@@ -1,7 +1,7 @@
 mod munger;
 mod offsets;
 
-use crate::{battle, facade, hooks, input, replayer, shadow};
+use crate::{battle, facade, hooks, input, protocol, replayer, shadow};
 
 #[derive(Clone)]
 pub struct BN4 {
@@ -465,7 +465,9 @@ impl hooks::Hooks for BN4 {
                                 }
                                 return;
                             }
-                            facade.abort_match().await;
+                            facade
+                                .abort_match(protocol::GoodbyeReason::Crash)
+                                .await;
                         });
                     }),
                 )
@@ -911,6 +913,12 @@ impl hooks::Hooks for BN4 {
                             );
                         }
 
+                        if replayer_state.wants_state_checksum() {
+                            replayer_state.add_state_checksum(
+                                &core.save_state().expect("save checksum state"),
+                            );
+                        }
+
                         let ip = match replayer_state.peek_input_pair() {
                             Some(ip) => ip.clone(),
                             None => {
@@ -1,4 +1,4 @@
-use crate::{battle, facade, hooks, input, replayer, shadow};
+use crate::{battle, facade, hooks, input, protocol, replayer, shadow};
 
 mod munger;
 mod offsets;
@@ -477,7 +477,9 @@ impl hooks::Hooks for BN5 {
                                 }
                                 return;
                             }
-                            facade.abort_match().await;
+                            facade
+                                .abort_match(protocol::GoodbyeReason::Crash)
+                                .await;
                         });
                     }),
                 )
@@ -986,6 +988,12 @@ impl hooks::Hooks for BN5 {
                             );
                         }
 
+                        if replayer_state.wants_state_checksum() {
+                            replayer_state.add_state_checksum(
+                                &core.save_state().expect("save checksum state"),
+                            );
+                        }
+
                         let ip = match replayer_state.peek_input_pair() {
                             Some(ip) => ip.clone(),
                             None => {
@@ -1,4 +1,4 @@
-use crate::{battle, facade, hooks, input, replayer, shadow};
+use crate::{battle, facade, hooks, input, protocol, replayer, shadow};
 
 mod munger;
 mod offsets;
@@ -488,7 +488,9 @@ impl hooks::Hooks for BN6 {
                                 }
                                 return;
                             }
-                            facade.abort_match().await;
+                            facade
+                                .abort_match(protocol::GoodbyeReason::Crash)
+                                .await;
                         });
                     }),
                 )
@@ -999,6 +1001,12 @@ impl hooks::Hooks for BN6 {
                             );
                         }
 
+                        if replayer_state.wants_state_checksum() {
+                            replayer_state.add_state_checksum(
+                                &core.save_state().expect("save checksum state"),
+                            );
+                        }
+
                         let ip = match replayer_state.peek_input_pair() {
                             Some(ip) => ip.clone(),
                             None => {
@@ -1,7 +1,7 @@
 mod munger;
 mod offsets;
 
-use crate::{battle, facade, hooks, input, replayer, shadow};
+use crate::{battle, facade, hooks, input, protocol, replayer, shadow};
 
 #[derive(Clone)]
 pub struct EXE45 {
@@ -467,7 +467,9 @@ impl hooks::Hooks for EXE45 {
                                 }
                                 return;
                             }
-                            facade.abort_match().await;
+                            facade
+                                .abort_match(protocol::GoodbyeReason::Crash)
+                                .await;
                         });
                     }),
                 )
@@ -945,6 +947,12 @@ impl hooks::Hooks for EXE45 {
                             );
                         }
 
+                        if replayer_state.wants_state_checksum() {
+                            replayer_state.add_state_checksum(
+                                &core.save_state().expect("save checksum state"),
+                            );
+                        }
+
                         let ip = match replayer_state.peek_input_pair() {
                             Some(ip) => ip.clone(),
                             None => {
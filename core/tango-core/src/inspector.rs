@@ -0,0 +1,179 @@
+use crate::{patch, replayer};
+
+/// Where to find the parts of a game's battle state that the inspector watches.
+///
+/// Everything that differs per player is given for P1 then P2, as offsets from `battle_state`.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct BattleStateLayout {
+    /// Location of the battle state struct in memory.
+    pub battle_state: u32,
+
+    /// Each navi's current HP, as a u16.
+    pub hp: [u32; 2],
+
+    /// Each navi's maximum HP, as a u16.
+    pub max_hp: [u32; 2],
+
+    /// A u8 that is nonzero while the custom screen is open.
+    pub custom_screen_open: u32,
+
+    /// The ID of the chip each navi is using right now as a u16, or 0 if it isn't using one.
+    pub chip_in_use: [u32; 2],
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct HpSample {
+    pub tick: u32,
+    pub hp: [u16; 2],
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct DamageEvent {
+    pub tick: u32,
+    /// The player who took the damage.
+    pub player_index: u8,
+    pub amount: u16,
+    pub remaining_hp: u16,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct ChipUse {
+    pub tick: u32,
+    pub player_index: u8,
+    pub chip: u16,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct CustomScreen {
+    pub opened_at: u32,
+    /// Unset if the round ended with the custom screen still open.
+    pub closed_at: Option<u32>,
+}
+
+/// What happened in the battle, as seen in the game's memory.
+#[derive(serde::Serialize, Clone, Debug, Default)]
+pub struct Battle {
+    pub max_hp: [u16; 2],
+
+    /// Each navi's HP, sampled every time either of them changes.
+    pub hp: Vec<HpSample>,
+    pub damage: Vec<DamageEvent>,
+    pub chips: Vec<ChipUse>,
+    pub custom_screens: Vec<CustomScreen>,
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct Report {
+    pub local_player_index: u8,
+    pub num_ticks: u32,
+    pub result: Option<replayer::BattleResult>,
+    pub result_tick: Option<u32>,
+
+    /// Unset if no battle state layout was given.
+    pub battle: Option<Battle>,
+}
+
+struct Inspector {
+    layout: BattleStateLayout,
+    battle: Battle,
+    last_hp: Option<[u16; 2]>,
+    last_chip_in_use: [u16; 2],
+    custom_screen_open: bool,
+}
+
+impl Inspector {
+    fn new(layout: BattleStateLayout) -> Self {
+        Self {
+            layout,
+            battle: Battle::default(),
+            last_hp: None,
+            last_chip_in_use: [0, 0],
+            custom_screen_open: false,
+        }
+    }
+
+    fn observe(&mut self, mut core: mgba::core::CoreMutRef, tick: u32) {
+        let base = self.layout.battle_state;
+
+        let hp = self
+            .layout
+            .hp
+            .map(|offset| core.raw_read_16(base + offset, -1));
+        self.battle.max_hp = self
+            .layout
+            .max_hp
+            .map(|offset| core.raw_read_16(base + offset, -1));
+        if self.last_hp != Some(hp) {
+            if let Some(last_hp) = self.last_hp {
+                for (i, (&last, &current)) in last_hp.iter().zip(hp.iter()).enumerate() {
+                    if current < last {
+                        self.battle.damage.push(DamageEvent {
+                            tick,
+                            player_index: i as u8,
+                            amount: last - current,
+                            remaining_hp: current,
+                        });
+                    }
+                }
+            }
+            self.battle.hp.push(HpSample { tick, hp });
+            self.last_hp = Some(hp);
+        }
+
+        for (i, &offset) in self.layout.chip_in_use.iter().enumerate() {
+            let chip = core.raw_read_16(base + offset, -1);
+            if chip != 0 && chip != self.last_chip_in_use[i] {
+                self.battle.chips.push(ChipUse {
+                    tick,
+                    player_index: i as u8,
+                    chip,
+                });
+            }
+            self.last_chip_in_use[i] = chip;
+        }
+
+        let custom_screen_open = core.raw_read_8(base + self.layout.custom_screen_open, -1) != 0;
+        if custom_screen_open != self.custom_screen_open {
+            if custom_screen_open {
+                self.battle.custom_screens.push(CustomScreen {
+                    opened_at: tick,
+                    closed_at: None,
+                });
+            } else if let Some(custom_screen) = self.battle.custom_screens.last_mut() {
+                custom_screen.closed_at = Some(tick);
+            }
+            self.custom_screen_open = custom_screen_open;
+        }
+    }
+}
+
+/// Plays a replay through on a fresh core, watching the battle unfold.
+///
+/// The battle itself is only watched if a layout of its battle state is given: none are known for any game yet.
+pub fn inspect(
+    rom: &[u8],
+    patch: Option<&patch::Patch>,
+    replay: &crate::replay::Replay,
+    layout: Option<BattleStateLayout>,
+) -> anyhow::Result<Report> {
+    let mut playback = replayer::Playback::new(rom, patch, replay)?;
+
+    let mut inspector = layout.map(Inspector::new);
+
+    while playback.step()? {
+        if let Some(inspector) = inspector.as_mut() {
+            let tick = playback.state().lock_inner().current_tick();
+            inspector.observe(playback.core_mut(), tick);
+        }
+    }
+
+    let replayer_state = playback.state().lock_inner();
+    let round_result = replayer_state.round_result();
+    Ok(Report {
+        local_player_index: replay.local_player_index,
+        num_ticks: replayer_state.current_tick(),
+        result: round_result.map(|round_result| round_result.result),
+        result_tick: round_result.map(|round_result| round_result.tick),
+        battle: inspector.map(|inspector| inspector.battle),
+    })
+}
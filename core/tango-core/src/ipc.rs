@@ -7,6 +7,18 @@ pub mod protos {
     include!(concat!(env!("OUT_DIR"), "/tango.ipc.rs"));
 }
 
+impl From<crate::protocol::GoodbyeReason> for protos::from_core_message::goodbye_event::Reason {
+    fn from(reason: crate::protocol::GoodbyeReason) -> Self {
+        match reason {
+            crate::protocol::GoodbyeReason::Forfeit => Self::Forfeit,
+            crate::protocol::GoodbyeReason::UserQuit => Self::UserQuit,
+            crate::protocol::GoodbyeReason::Desync => Self::Desync,
+            crate::protocol::GoodbyeReason::VersionError => Self::VersionError,
+            crate::protocol::GoodbyeReason::Crash => Self::Crash,
+        }
+    }
+}
+
 pub struct Sender {
     writer: std::pin::Pin<Box<dyn tokio::io::AsyncWrite + Send + 'static>>,
 }
@@ -18,6 +30,12 @@ impl Sender {
         }
     }
 
+    pub fn new_from_writer(writer: impl tokio::io::AsyncWrite + Send + 'static) -> Self {
+        Sender {
+            writer: Box::pin(writer),
+        }
+    }
+
     pub async fn send(&mut self, req: protos::FromCoreMessage) -> anyhow::Result<()> {
         let buf = req.encode_to_vec();
         self.writer.write_u32_le(buf.len() as u32).await?;
@@ -41,14 +59,25 @@ impl Receiver {
         }
     }
 
+    pub fn new_from_reader(reader: impl tokio::io::AsyncRead + Send + 'static) -> Self {
+        Receiver {
+            reader: Box::pin(reader),
+            buf: bytes::BytesMut::new(),
+        }
+    }
+
     pub async fn receive(&mut self) -> anyhow::Result<protos::ToCoreMessage> {
         while self.buf.len() < 4 {
-            self.reader.read_buf(&mut self.buf).await?;
+            if self.reader.read_buf(&mut self.buf).await? == 0 {
+                anyhow::bail!("ipc channel closed");
+            }
         }
         let size = byteorder::LittleEndian::read_u32(&self.buf[0..4]) as usize;
 
         while self.buf.len() < 4 + size {
-            self.reader.read_buf(&mut self.buf).await?;
+            if self.reader.read_buf(&mut self.buf).await? == 0 {
+                anyhow::bail!("ipc channel closed");
+            }
         }
         let resp = protos::ToCoreMessage::decode(&self.buf[4..4 + size])?;
 
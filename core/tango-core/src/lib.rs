@@ -1,18 +1,27 @@
 #[macro_use]
 extern crate lazy_static;
 
+pub mod archive;
 pub mod audio;
 pub mod battle;
+pub mod compat;
+pub mod export;
 pub mod facade;
 pub mod game;
 pub mod hooks;
 pub mod input;
+pub mod inspector;
 pub mod ipc;
 pub mod negotiation;
+pub mod patch;
 pub mod protocol;
 pub mod replay;
 pub mod replayer;
+pub mod rtt;
 pub mod shadow;
+pub mod sidebyside;
 pub mod signaling;
+pub mod signing;
+pub mod spectator;
 pub mod tps;
 pub mod transport;
@@ -48,33 +48,8 @@ fn parse_physical_input(input: &PhysicalInput) -> Option<tango_core::game::Physi
     }
 }
 
-#[derive(clap::Parser)]
-struct Cli {
-    #[clap(long)]
-    input_mapping: String,
-
-    #[clap(long)]
-    signaling_connect_addr: String,
-
-    #[clap(long)]
-    ice_servers: Vec<String>,
-
-    #[clap(long)]
-    session_id: Option<String>,
-}
-
-fn main() -> Result<(), anyhow::Error> {
-    env_logger::Builder::from_default_env()
-        .filter(Some("tango_core"), log::LevelFilter::Info)
-        .filter(Some("datachannel"), log::LevelFilter::Info)
-        .filter(Some("mgba"), log::LevelFilter::Info)
-        .init();
-
-    log::info!("welcome to tango-core {}!", git_version::git_version!());
-
-    let args = Cli::parse();
-
-    let raw_input_mapping = serde_json::from_str::<InputMapping>(&args.input_mapping)?;
+fn parse_input_mapping(raw: &str) -> anyhow::Result<tango_core::game::InputMapping> {
+    let raw_input_mapping = serde_json::from_str::<InputMapping>(raw)?;
     let input_mapping = tango_core::game::InputMapping {
         up: raw_input_mapping
             .up
@@ -134,6 +109,77 @@ fn main() -> Result<(), anyhow::Error> {
     };
 
     log::info!("input mapping: {:?}", input_mapping);
+    Ok(input_mapping)
+}
+
+/// How many round trip times to keep when recommending an input delay.
+const RTT_WINDOW_SIZE: usize = 60;
+
+/// Reconnects to the opponent through the same signaling session the match was negotiated on.
+struct SessionReconnector {
+    session_id: String,
+    signaling_connect_addr: String,
+    ice_servers: Vec<String>,
+}
+
+#[async_trait::async_trait]
+impl tango_core::transport::Reconnector for SessionReconnector {
+    async fn reconnect(
+        &self,
+    ) -> anyhow::Result<(
+        Box<dyn tango_core::transport::Sender>,
+        Box<dyn tango_core::transport::Receiver>,
+        Option<datachannel_wrapper::PeerConnection>,
+    )> {
+        let (tx, rx, peer_conn) = tango_core::negotiation::reconnect(
+            &self.session_id,
+            &self.signaling_connect_addr,
+            &self.ice_servers,
+        )
+        .await?;
+        Ok((tx, rx, Some(peer_conn)))
+    }
+}
+
+#[derive(clap::Parser)]
+struct Cli {
+    #[clap(long, required_unless_present = "headless")]
+    input_mapping: Option<String>,
+
+    #[clap(long)]
+    signaling_connect_addr: String,
+
+    #[clap(long)]
+    ice_servers: Vec<String>,
+
+    #[clap(long)]
+    session_id: Option<String>,
+
+    /// Run without a window or audio device.
+    #[clap(long)]
+    headless: bool,
+
+    /// When running headless, a script of inputs to play back (see game::headless::InputScript).
+    #[clap(long, parse(from_os_str))]
+    input_script: Option<std::path::PathBuf>,
+}
+
+fn main() -> Result<(), anyhow::Error> {
+    env_logger::Builder::from_default_env()
+        .filter(Some("tango_core"), log::LevelFilter::Info)
+        .filter(Some("datachannel"), log::LevelFilter::Info)
+        .filter(Some("mgba"), log::LevelFilter::Info)
+        .init();
+
+    log::info!("welcome to tango-core {}!", git_version::git_version!());
+
+    let args = Cli::parse();
+
+    let input_mapping = if let Some(input_mapping) = args.input_mapping.as_ref() {
+        Some(parse_input_mapping(input_mapping)?)
+    } else {
+        None
+    };
 
     let rt = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
@@ -142,11 +188,12 @@ fn main() -> Result<(), anyhow::Error> {
     let mut ipc_sender = tango_core::ipc::Sender::new_from_stdout();
     let mut ipc_receiver = tango_core::ipc::Receiver::new_from_stdin();
 
-    let (window_title, rom_path, save_path, window_scale, pvp_init) = if let Some(session_id) =
-        &args.session_id
+    let (window_title, rom_path, patch, save_path, window_scale, pvp_init) = if let Some(
+        session_id,
+    ) = &args.session_id
     {
         rt.block_on(async {
-            let (dc, peer_conn) = match tango_core::negotiation::negotiate(
+            let (mut tx, mut rx, peer_conn) = match tango_core::negotiation::negotiate(
                 &mut ipc_sender,
                 session_id,
                 &args.signaling_connect_addr,
@@ -165,30 +212,50 @@ fn main() -> Result<(), anyhow::Error> {
                         tango_core::negotiation::Error::ProtocolVersionTooNew => {
                             std::process::exit(ExitCode::ProtocolVersionTooNew as i32);
                         }
-                        tango_core::negotiation::Error::Other(_) => {
+                        _ => {
                             return Err(err.into());
                         }
                     }
                 }
             };
 
-            let (mut dc_rx, mut dc_tx) = dc.split();
-
             let mut ping_timer = tokio::time::interval(std::time::Duration::from_secs(1));
-            let mut hola_received = false;
+            let mut rtt_tracker = tango_core::rtt::Tracker::new(RTT_WINDOW_SIZE);
+            let mut remote_hola = None;
 
-            let start_req = loop {
+            let rng_commitment = tango_core::negotiation::RngCommitment::generate();
+            let (start_req, patch, local_hola, signing_keypair) = loop {
                 tokio::select! {
                     msg = ipc_receiver.receive() => {
                         match msg?.which {
                             Some(tango_core::ipc::protos::to_core_message::Which::SmuggleReq(tango_core::ipc::protos::to_core_message::SmuggleRequest { data })) => {
-                                dc_tx.send(&tango_core::protocol::Packet::Smuggle(tango_core::protocol::Smuggle {
+                                tx.send(&tango_core::protocol::Packet::Smuggle(tango_core::protocol::Smuggle {
                                     data,
-                                }).serialize()?).await?;
+                                })).await?;
                             },
                             Some(tango_core::ipc::protos::to_core_message::Which::StartReq(start_req)) => {
-                                dc_tx.send(&tango_core::protocol::Packet::Hola(tango_core::protocol::Hola {}).serialize()?).await?;
-                                break start_req;
+                                let recommended_input_delay = if start_req.settings.as_ref().map(|settings| settings.auto_input_delay).unwrap_or(false) {
+                                    rtt_tracker.recommended_input_delay()
+                                } else {
+                                    None
+                                };
+                                let patch = start_req.patch_path.as_ref().map(|path| tango_core::patch::Patch::read(std::path::Path::new(path))).transpose()?;
+                                let signing_keypair = tango_core::signing::load_or_generate_keypair(start_req.settings.as_ref().and_then(|settings| settings.signing_key_path.as_ref()).map(std::path::Path::new))?;
+                                let game_info = tango_core::compat::read_game_info(&std::fs::read(&start_req.rom_path)?).ok_or_else(|| anyhow::anyhow!("rom is too short"))?;
+                                let local_hola = tango_core::protocol::Hola {
+                                    recommended_input_delay,
+                                    patch_hash: patch.as_ref().map(|patch| patch.hash()),
+                                    netplay_compatibility: tango_core::compat::netplay_compatibility(start_req.netplay_compatibility.as_deref(), &game_info, patch.is_some()),
+                                    game_info,
+                                    match_type: start_req.settings.as_ref().map(|settings| (settings.match_type as u8, settings.match_subtype as u8)).unwrap_or((0, 0)),
+                                    rng_commitment: rng_commitment.commitment(),
+                                    public_key: signing_keypair.public.to_bytes(),
+                                };
+                                tx.send(&tango_core::protocol::Packet::Hola(local_hola.clone())).await?;
+                                break (start_req, patch, local_hola, signing_keypair);
+                            },
+                            Some(p) => {
+                                anyhow::bail!("unexpected ipc request: {:?}", p);
                             },
                             None => {
                                 anyhow::bail!("ipc channel closed");
@@ -198,17 +265,17 @@ fn main() -> Result<(), anyhow::Error> {
 
                     _ = ping_timer.tick() => {
                         let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?;
-                        dc_tx.send(&tango_core::protocol::Packet::Ping(tango_core::protocol::Ping {
+                        tx.send(&tango_core::protocol::Packet::Ping(tango_core::protocol::Ping {
                             ts: now.as_nanos() as u64,
-                        }).serialize()?).await?;
+                        })).await?;
                     }
 
-                    msg = dc_rx.receive() => {
-                        match msg {
+                    msg = rx.receive() => {
+                        match msg? {
                             Some(msg) => {
-                                match tango_core::protocol::Packet::deserialize(&msg)? {
-                                    tango_core::protocol::Packet::Hola(_) => {
-                                        hola_received = true;
+                                match msg {
+                                    tango_core::protocol::Packet::Hola(hola) => {
+                                        remote_hola = Some(hola);
                                     }
                                     tango_core::protocol::Packet::Smuggle(tango_core::protocol::Smuggle {
                                         data,
@@ -222,18 +289,20 @@ fn main() -> Result<(), anyhow::Error> {
                                     tango_core::protocol::Packet::Ping(tango_core::protocol::Ping {
                                         ts
                                     }) => {
-                                        dc_tx.send(&tango_core::protocol::Packet::Pong(tango_core::protocol::Pong {
+                                        tx.send(&tango_core::protocol::Packet::Pong(tango_core::protocol::Pong {
                                             ts
-                                        }).serialize()?).await?;
+                                        })).await?;
                                     },
                                     tango_core::protocol::Packet::Pong(tango_core::protocol::Pong {
                                         ts
                                     }) => {
                                         let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?;
                                         let then = std::time::Duration::from_nanos(ts);
+                                        let rtt = now.saturating_sub(then);
+                                        rtt_tracker.add_sample(rtt);
                                         ipc_sender.send(tango_core::ipc::protos::FromCoreMessage {
                                             which: Some(tango_core::ipc::protos::from_core_message::Which::ConnectionQualityEv(tango_core::ipc::protos::from_core_message::ConnectionQualityEvent {
-                                                rtt: (now - then).as_nanos() as u64,
+                                                rtt: rtt.as_nanos() as u64,
                                             }))
                                         }).await?;
                                     },
@@ -250,14 +319,16 @@ fn main() -> Result<(), anyhow::Error> {
                 }
             };
 
-            if !hola_received {
+            let remote_hola = if let Some(remote_hola) = remote_hola {
+                remote_hola
+            } else {
                 // If we haven't received an Hola, pull packets until we do.
                 loop {
-                    match dc_rx.receive().await {
+                    match rx.receive().await? {
                         Some(msg) => {
-                            match tango_core::protocol::Packet::deserialize(&msg)? {
-                                tango_core::protocol::Packet::Hola(_) => {
-                                    break;
+                            match msg {
+                                tango_core::protocol::Packet::Hola(hola) => {
+                                    break hola;
                                 }
                                 tango_core::protocol::Packet::Ping(_) => {
                                     // Ignore stray pings.
@@ -275,14 +346,90 @@ fn main() -> Result<(), anyhow::Error> {
                         },
                     }
                 }
+            };
+
+            let mut settings = start_req.settings.unwrap();
+
+            let shadow_patch = settings.shadow_patch_path.as_ref().map(|path| tango_core::patch::Patch::read(std::path::Path::new(path))).transpose()?;
+            let shadow_game_info = tango_core::compat::read_game_info(&std::fs::read(&settings.shadow_rom_path)?).ok_or_else(|| anyhow::anyhow!("shadow rom is too short"))?;
+            if let Err(err) = tango_core::negotiation::check_hola(&local_hola, &remote_hola, &shadow_game_info, shadow_patch.as_ref().map(|patch| patch.hash())) {
+                log::error!("opponent cannot play this match: {}, local = {:?}, remote = {:?}, shadow = {:?}", err, local_hola, remote_hola, shadow_game_info);
+                if let Err(e) = tx.send(&tango_core::protocol::Packet::Goodbye(tango_core::protocol::Goodbye {
+                    reason: tango_core::protocol::GoodbyeReason::VersionError,
+                })).await {
+                    log::error!("failed to say goodbye: {}", e);
+                }
+                std::process::exit(match err {
+                    tango_core::negotiation::Error::MatchTypeMismatch => ExitCode::MatchTypeMismatch,
+                    tango_core::negotiation::Error::IncompatibleGames => ExitCode::IncompatibleGames,
+                    tango_core::negotiation::Error::GameMismatch => ExitCode::GameMismatch,
+                    tango_core::negotiation::Error::PatchMismatch => ExitCode::PatchMismatch,
+                    _ => ExitCode::Unknown,
+                } as i32);
+            }
+
+            tx.send(&tango_core::protocol::Packet::Reveal(tango_core::protocol::Reveal {
+                nonce: rng_commitment.nonce(),
+            })).await?;
+            let remote_reveal = loop {
+                match rx.receive().await? {
+                    Some(msg) => {
+                        match msg {
+                            tango_core::protocol::Packet::Reveal(reveal) => {
+                                break reveal;
+                            }
+                            tango_core::protocol::Packet::Goodbye(goodbye) => {
+                                log::info!("opponent left the match: {:?}", goodbye.reason);
+                                ipc_sender
+                                    .send(tango_core::ipc::protos::FromCoreMessage {
+                                        which: Some(tango_core::ipc::protos::from_core_message::Which::GoodbyeEv(
+                                            tango_core::ipc::protos::from_core_message::GoodbyeEvent {
+                                                reason: tango_core::ipc::protos::from_core_message::goodbye_event::Reason::from(goodbye.reason).into(),
+                                            },
+                                        )),
+                                    })
+                                    .await?;
+                                std::process::exit(ExitCode::OpponentLeft as i32);
+                            }
+                            tango_core::protocol::Packet::Ping(_) => {
+                                // Ignore stray pings.
+                            }
+                            tango_core::protocol::Packet::Pong(_) => {
+                                // Ignore stray pongs.
+                            }
+                            p => {
+                                anyhow::bail!("unexpected packet: {:?}", p);
+                            }
+                        }
+                    }
+                    None => {
+                        std::process::exit(ExitCode::LostConnection as i32);
+                    },
+                }
+            };
+            settings.rng_seed = match rng_commitment.reveal(&remote_hola.rng_commitment, &remote_reveal.nonce) {
+                Ok(rng_seed) => rng_seed.to_vec(),
+                Err(err) => {
+                    log::error!("could not agree on rng seed: {}", err);
+                    std::process::exit(ExitCode::InvalidCommitment as i32);
+                }
+            };
+
+            settings.replay_metadata = tango_core::patch::record_in_replay_metadata(&settings.replay_metadata, patch.as_ref().map(|patch| patch.hash()), remote_hola.patch_hash)?;
+
+            if let Some(input_delay) = tango_core::rtt::agree_input_delay(local_hola.recommended_input_delay, remote_hola.recommended_input_delay) {
+                log::info!("agreed on input delay: {}", input_delay);
+                settings.input_delay = input_delay;
+                settings.shadow_input_delay = input_delay;
             }
 
             Ok((
                 start_req.window_title,
                 start_req.rom_path,
+                patch,
                 start_req.save_path,
                 start_req.window_scale,
-                Some((peer_conn, dc_rx.unsplit(dc_tx), start_req.settings.unwrap()))
+                Some((peer_conn, tx, rx, rtt_tracker, settings, shadow_patch, signing_keypair, remote_hola.public_key))
             ))
         })?
     } else {
@@ -305,6 +452,11 @@ fn main() -> Result<(), anyhow::Error> {
                     Ok((
                         start_req.window_title,
                         start_req.rom_path,
+                        start_req
+                            .patch_path
+                            .as_ref()
+                            .map(|path| tango_core::patch::Patch::read(std::path::Path::new(path)))
+                            .transpose()?,
                         start_req.save_path,
                         start_req.window_scale,
                         None,
@@ -322,34 +474,95 @@ fn main() -> Result<(), anyhow::Error> {
 
     mgba::log::init();
 
-    let g = tango_core::game::Game::new(
-        rt,
-        std::sync::Arc::new(parking_lot::Mutex::new(ipc_sender)),
-        window_title,
-        input_mapping,
-        rom_path.into(),
-        save_path.into(),
-        window_scale,
-        match pvp_init {
-            None => None,
-            Some((peer_conn, dc, settings)) => Some(tango_core::battle::MatchInit {
-                dc,
-                peer_conn,
-                settings: tango_core::battle::Settings {
-                    replay_metadata: settings.replay_metadata,
-                    replays_path: settings.replays_path.into(),
-                    shadow_save_path: settings.shadow_save_path.into(),
-                    shadow_rom_path: settings.shadow_rom_path.into(),
-                    match_type: (settings.match_type as u8, settings.match_subtype as u8),
-                    input_delay: settings.input_delay,
-                    shadow_input_delay: settings.shadow_input_delay,
-                    rng_seed: settings.rng_seed,
-                    opponent_nickname: settings.opponent_nickname,
-                    max_queue_length: settings.max_queue_length as usize,
-                },
+    let ipc_sender = std::sync::Arc::new(parking_lot::Mutex::new(ipc_sender));
+    let match_init = match pvp_init {
+        None => None,
+        Some((
+            peer_conn,
+            tx,
+            rx,
+            rtt_tracker,
+            settings,
+            shadow_patch,
+            signing_keypair,
+            remote_public_key,
+        )) => Some(tango_core::battle::MatchInit {
+            tx,
+            rx,
+            rtt_tracker,
+            is_offerer: peer_conn.local_description().unwrap().sdp_type
+                == datachannel_wrapper::SdpType::Offer,
+            peer_conn: Some(peer_conn),
+            reconnector: args.session_id.clone().map(|session_id| {
+                Box::new(SessionReconnector {
+                    session_id,
+                    signaling_connect_addr: args.signaling_connect_addr.clone(),
+                    ice_servers: args.ice_servers.clone(),
+                }) as Box<dyn tango_core::transport::Reconnector>
             }),
-        },
-    )?;
-    g.run()?;
+            settings: tango_core::battle::Settings {
+                replay_metadata: settings.replay_metadata,
+                replays_path: settings.replays_path.into(),
+                shadow_save_path: settings.shadow_save_path.into(),
+                shadow_rom_path: settings.shadow_rom_path.into(),
+                shadow_patch,
+                match_type: (settings.match_type as u8, settings.match_subtype as u8),
+                input_delay: settings.input_delay,
+                shadow_input_delay: settings.shadow_input_delay,
+                auto_input_delay: settings.auto_input_delay,
+                rng_seed: settings.rng_seed,
+                opponent_nickname: settings.opponent_nickname,
+                max_queue_length: settings.max_queue_length as usize,
+                spectator_listen_addr: settings.spectator_listen_addr,
+                signing_keypair: std::sync::Arc::new(signing_keypair),
+                remote_public_key,
+            },
+        }),
+    };
+
+    if args.headless {
+        let input_script = if let Some(input_script) = args.input_script.as_ref() {
+            tango_core::game::headless::InputScript::parse(&std::fs::read_to_string(input_script)?)?
+        } else {
+            tango_core::game::headless::InputScript::empty()
+        };
+        let g = tango_core::game::headless::HeadlessGame::new(
+            rt,
+            ipc_sender,
+            ipc_receiver,
+            input_script,
+            rom_path.into(),
+            patch,
+            save_path.into(),
+            match_init,
+        )?;
+        exit_if_match_ended(g.run())?;
+    } else {
+        let g = tango_core::game::Game::new(
+            rt,
+            ipc_sender,
+            ipc_receiver,
+            window_title,
+            input_mapping.expect("input mapping"),
+            rom_path.into(),
+            patch,
+            save_path.into(),
+            window_scale,
+            match_init,
+        )?;
+        exit_if_match_ended(g.run())?;
+    }
     Ok(())
 }
+
+/// Exits with the code for how the match ended, if it ended early, so the launcher can tell the user why.
+///
+/// By the time the game returns, it has been torn down, so the replay of the round in progress has already been written out as far as it got.
+fn exit_if_match_ended(r: anyhow::Result<()>) -> anyhow::Result<()> {
+    if let Err(e) = &r {
+        if let Some(match_ended) = e.downcast_ref::<tango_core::battle::MatchEnded>() {
+            std::process::exit(match_ended.exit_code() as i32);
+        }
+    }
+    r
+}
@@ -1,10 +1,20 @@
-use crate::{ipc, protocol, signaling};
+use rand::RngCore;
+use sha3::Digest;
+use subtle::ConstantTimeEq;
+
+use crate::{ipc, protocol, signaling, transport};
 
 #[derive(Debug)]
 pub enum Error {
     ExpectedHello,
     ProtocolVersionTooOld,
     ProtocolVersionTooNew,
+    MatchTypeMismatch,
+    IncompatibleGames,
+    GameMismatch,
+    PatchMismatch,
+    IdenticalCommitment,
+    InvalidCommitment,
     Other(anyhow::Error),
 }
 
@@ -32,6 +42,12 @@ impl std::fmt::Display for Error {
             Error::ExpectedHello => write!(f, "expected hello"),
             Error::ProtocolVersionTooOld => write!(f, "protocol version too old"),
             Error::ProtocolVersionTooNew => write!(f, "protocol version too new"),
+            Error::MatchTypeMismatch => write!(f, "match type mismatch"),
+            Error::IncompatibleGames => write!(f, "incompatible games"),
+            Error::GameMismatch => write!(f, "game mismatch"),
+            Error::PatchMismatch => write!(f, "patch mismatch"),
+            Error::IdenticalCommitment => write!(f, "identical commitment"),
+            Error::InvalidCommitment => write!(f, "invalid commitment"),
             Error::Other(e) => write!(f, "other error: {}", e),
         }
     }
@@ -46,7 +62,8 @@ pub async fn negotiate(
     ice_servers: &[String],
 ) -> Result<
     (
-        datachannel_wrapper::DataChannel,
+        Box<dyn transport::Sender>,
+        Box<dyn transport::Receiver>,
         datachannel_wrapper::PeerConnection,
     ),
     Error,
@@ -57,33 +74,7 @@ pub async fn negotiate(
         ice_servers
     );
 
-    let (mut peer_conn, mut event_rx) =
-        datachannel_wrapper::PeerConnection::new(datachannel_wrapper::RtcConfig::new(ice_servers))?;
-
-    let dc = peer_conn.create_data_channel(
-        "tango",
-        datachannel_wrapper::DataChannelInit::default()
-            .reliability(datachannel_wrapper::Reliability {
-                unordered: false,
-                unreliable: false,
-                max_packet_life_time: 0,
-                max_retransmits: 0,
-            })
-            .negotiated()
-            .manual_stream()
-            .stream(0),
-    )?;
-
-    loop {
-        if let Some(datachannel_wrapper::PeerConnectionEvent::GatheringStateChange(
-            datachannel_wrapper::GatheringState::Complete,
-        )) = event_rx.recv().await
-        {
-            break;
-        }
-    }
-
-    log::info!("candidates gathered");
+    let pending = PendingConnection::new(ice_servers).await?;
 
     ipc_sender
         .send(ipc::protos::FromCoreMessage {
@@ -95,54 +86,161 @@ pub async fn negotiate(
         })
         .await?;
 
-    signaling::connect(signaling_connect_addr, &mut peer_conn, event_rx, session_id).await?;
+    let (mut tx, mut rx, peer_conn) = pending.connect(signaling_connect_addr, session_id).await?;
 
-    let (mut dc_rx, mut dc_tx) = dc.split();
+    ipc_sender
+        .send(ipc::protos::FromCoreMessage {
+            which: Some(ipc::protos::from_core_message::Which::StateEv(
+                ipc::protos::from_core_message::StateEvent {
+                    state: ipc::protos::from_core_message::state_event::State::Connecting.into(),
+                },
+            )),
+        })
+        .await?;
 
-    log::debug!(
-        "local sdp (type = {:?}): {}",
-        peer_conn.local_description().expect("local sdp").sdp_type,
-        peer_conn.local_description().expect("local sdp").sdp
-    );
-    log::debug!(
-        "remote sdp (type = {:?}): {}",
-        peer_conn.remote_description().expect("remote sdp").sdp_type,
-        peer_conn.remote_description().expect("remote sdp").sdp
-    );
+    handshake(&mut *tx, &mut *rx).await?;
 
     ipc_sender
         .send(ipc::protos::FromCoreMessage {
             which: Some(ipc::protos::from_core_message::Which::StateEv(
                 ipc::protos::from_core_message::StateEvent {
-                    state: ipc::protos::from_core_message::state_event::State::Connecting.into(),
+                    state: ipc::protos::from_core_message::state_event::State::Starting.into(),
                 },
             )),
         })
         .await?;
 
-    dc_tx
-        .send(
-            protocol::Packet::Hello(protocol::Hello {
-                protocol_version: protocol::VERSION,
-            })
-            .serialize()
-            .expect("serialize")
-            .as_slice(),
-        )
+    Ok((tx, rx, peer_conn))
+}
+
+/// Connects to the opponent again through the same session, after the connection the match was started on was lost.
+///
+/// This goes through signaling and the handshake again, but nothing the launcher needs to hear about.
+pub async fn reconnect(
+    session_id: &str,
+    signaling_connect_addr: &str,
+    ice_servers: &[String],
+) -> Result<
+    (
+        Box<dyn transport::Sender>,
+        Box<dyn transport::Receiver>,
+        datachannel_wrapper::PeerConnection,
+    ),
+    Error,
+> {
+    log::info!("reconnecting, session_id = {}", session_id);
+    let (mut tx, mut rx, peer_conn) = PendingConnection::new(ice_servers)
+        .await?
+        .connect(signaling_connect_addr, session_id)
         .await?;
+    handshake(&mut *tx, &mut *rx).await?;
+    Ok((tx, rx, peer_conn))
+}
+
+/// A peer connection with our offer ready, waiting to be matched up with the opponent through signaling.
+struct PendingConnection {
+    peer_conn: datachannel_wrapper::PeerConnection,
+    event_rx: tokio::sync::mpsc::Receiver<datachannel_wrapper::PeerConnectionEvent>,
+    dc: datachannel_wrapper::DataChannel,
+}
+
+impl PendingConnection {
+    async fn new(ice_servers: &[String]) -> Result<Self, Error> {
+        let (mut peer_conn, mut event_rx) = datachannel_wrapper::PeerConnection::new(
+            datachannel_wrapper::RtcConfig::new(ice_servers),
+        )?;
 
-    let hello = match protocol::Packet::deserialize(
-        match dc_rx.receive().await {
-            Some(d) => d,
-            None => {
-                return Err(Error::ExpectedHello);
+        let dc = peer_conn.create_data_channel(
+            "tango",
+            datachannel_wrapper::DataChannelInit::default()
+                .reliability(datachannel_wrapper::Reliability {
+                    unordered: false,
+                    unreliable: false,
+                    max_packet_life_time: 0,
+                    max_retransmits: 0,
+                })
+                .negotiated()
+                .manual_stream()
+                .stream(0),
+        )?;
+
+        // Candidates are trickled through the signaling server as they are gathered, so we only need to wait for our offer to be ready.
+        loop {
+            match event_rx.recv().await {
+                Some(datachannel_wrapper::PeerConnectionEvent::SessionDescription(_)) => {
+                    break;
+                }
+                Some(_) => {}
+                None => {
+                    return Err(anyhow::anyhow!("peer connection closed").into());
+                }
             }
         }
-        .as_slice(),
-    )
-    .map_err(|_| Error::ExpectedHello)?
-    {
-        protocol::Packet::Hello(hello) => hello,
+
+        log::info!("local description ready");
+
+        Ok(Self {
+            peer_conn,
+            event_rx,
+            dc,
+        })
+    }
+
+    async fn connect(
+        mut self,
+        signaling_connect_addr: &str,
+        session_id: &str,
+    ) -> Result<
+        (
+            Box<dyn transport::Sender>,
+            Box<dyn transport::Receiver>,
+            datachannel_wrapper::PeerConnection,
+        ),
+        Error,
+    > {
+        signaling::connect(
+            signaling_connect_addr,
+            &mut self.peer_conn,
+            self.event_rx,
+            session_id,
+        )
+        .await?;
+
+        let (dc_rx, dc_tx) = self.dc.split();
+
+        log::debug!(
+            "local sdp (type = {:?}): {}",
+            self.peer_conn
+                .local_description()
+                .expect("local sdp")
+                .sdp_type,
+            self.peer_conn.local_description().expect("local sdp").sdp
+        );
+        log::debug!(
+            "remote sdp (type = {:?}): {}",
+            self.peer_conn
+                .remote_description()
+                .expect("remote sdp")
+                .sdp_type,
+            self.peer_conn.remote_description().expect("remote sdp").sdp
+        );
+
+        Ok((Box::new(dc_tx), Box::new(dc_rx), self.peer_conn))
+    }
+}
+
+/// Exchanges hellos with the opponent over an established transport and checks that our protocol versions agree.
+pub async fn handshake(
+    tx: &mut dyn transport::Sender,
+    rx: &mut dyn transport::Receiver,
+) -> Result<(), Error> {
+    tx.send(&protocol::Packet::Hello(protocol::Hello {
+        protocol_version: protocol::VERSION,
+    }))
+    .await?;
+
+    let hello = match rx.receive().await.map_err(|_| Error::ExpectedHello)? {
+        Some(protocol::Packet::Hello(hello)) => hello,
         _ => {
             return Err(Error::ExpectedHello);
         }
@@ -156,15 +254,89 @@ pub async fn negotiate(
         return Err(Error::ProtocolVersionTooNew);
     }
 
-    ipc_sender
-        .send(ipc::protos::FromCoreMessage {
-            which: Some(ipc::protos::from_core_message::Which::StateEv(
-                ipc::protos::from_core_message::StateEvent {
-                    state: ipc::protos::from_core_message::state_event::State::Starting.into(),
-                },
-            )),
-        })
-        .await?;
+    Ok(())
+}
+
+/// Checks the opponent's hola against our own, and against the game the shadow will be running on their behalf.
+///
+/// The shadow has to be running exactly what the opponent is running, otherwise the match will desync as soon as it starts.
+pub fn check_hola(
+    local_hola: &protocol::Hola,
+    remote_hola: &protocol::Hola,
+    shadow_game_info: &protocol::GameInfo,
+    shadow_patch_hash: Option<[u8; 32]>,
+) -> Result<(), Error> {
+    if local_hola.match_type != remote_hola.match_type {
+        return Err(Error::MatchTypeMismatch);
+    }
+
+    // Games that nobody knows the compatibility of can't be played against anything.
+    match (
+        &local_hola.netplay_compatibility,
+        &remote_hola.netplay_compatibility,
+    ) {
+        (Some(local), Some(remote)) if local == remote => {}
+        _ => {
+            return Err(Error::IncompatibleGames);
+        }
+    }
+
+    if *shadow_game_info != remote_hola.game_info {
+        return Err(Error::GameMismatch);
+    }
+
+    if shadow_patch_hash != remote_hola.patch_hash {
+        return Err(Error::PatchMismatch);
+    }
+
+    Ok(())
+}
+
+/// Our half of the RNG seed.
+///
+/// Each side commits to a random nonce by sending its hash, and only reveals the nonce once it has seen the other side's commitment. Neither side can then pick its nonce based on the other's, so neither can bias the seed.
+pub struct RngCommitment {
+    nonce: [u8; 16],
+}
+
+impl RngCommitment {
+    pub fn generate() -> Self {
+        let mut nonce = [0u8; 16];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+        Self { nonce }
+    }
+
+    pub fn nonce(&self) -> [u8; 16] {
+        self.nonce
+    }
+
+    pub fn commitment(&self) -> [u8; 32] {
+        commit(&self.nonce)
+    }
+
+    /// Checks the opponent's revealed nonce against their commitment and derives the shared seed from both nonces.
+    pub fn reveal(
+        &self,
+        remote_commitment: &[u8; 32],
+        remote_nonce: &[u8; 16],
+    ) -> Result<[u8; 16], Error> {
+        // If the opponent just echoed our commitment back, they would be able to echo our nonce back too and zero out the seed.
+        if bool::from(self.commitment().ct_eq(remote_commitment)) {
+            return Err(Error::IdenticalCommitment);
+        }
+
+        if !bool::from(commit(remote_nonce).ct_eq(remote_commitment)) {
+            return Err(Error::InvalidCommitment);
+        }
+
+        let mut seed = [0u8; 16];
+        for (i, b) in seed.iter_mut().enumerate() {
+            *b = self.nonce[i] ^ remote_nonce[i];
+        }
+        Ok(seed)
+    }
+}
 
-    Ok((dc_rx.unsplit(dc_tx), peer_conn))
+fn commit(nonce: &[u8; 16]) -> [u8; 32] {
+    sha3::Sha3_256::digest(nonce).into()
 }
@@ -0,0 +1,82 @@
+use prost::Message;
+use sha3::Digest;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    IPS,
+    UPS,
+    BPS,
+}
+
+impl Format {
+    pub fn detect(patch: &[u8]) -> Option<Self> {
+        if patch.starts_with(b"PATCH") {
+            Some(Format::IPS)
+        } else if patch.starts_with(b"UPS1") {
+            Some(Format::UPS)
+        } else if patch.starts_with(b"BPS1") {
+            Some(Format::BPS)
+        } else {
+            None
+        }
+    }
+}
+
+/// A ROM patch that has been read into memory, ready to be applied to every core that runs the patched game.
+#[derive(Clone)]
+pub struct Patch {
+    format: Format,
+    data: Vec<u8>,
+}
+
+impl Patch {
+    pub fn new(data: Vec<u8>) -> anyhow::Result<Self> {
+        let format = Format::detect(&data)
+            .ok_or_else(|| anyhow::anyhow!("patch is not an IPS, UPS or BPS patch"))?;
+        Ok(Self { format, data })
+    }
+
+    pub fn read(path: &std::path::Path) -> anyhow::Result<Self> {
+        Self::new(std::fs::read(path)?)
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Identifies the patch to the opponent, so both sides can check they are playing the same game.
+    pub fn hash(&self) -> [u8; 32] {
+        sha3::Sha3_256::digest(&self.data).into()
+    }
+
+    /// Applies the patch to the ROM that is loaded into the core. This must be called after the ROM is loaded and before the core is reset.
+    pub fn apply(&self, mut core: mgba::core::CoreMutRef) -> anyhow::Result<()> {
+        core.load_patch(mgba::vfile::VFile::open_memory(&self.data))
+    }
+}
+
+/// Fills in the hashes of the patches each side is playing with in the replay metadata the launcher gave us.
+pub fn record_in_replay_metadata(
+    replay_metadata: &[u8],
+    local_patch_hash: Option<[u8; 32]>,
+    remote_patch_hash: Option<[u8; 32]>,
+) -> anyhow::Result<Vec<u8>> {
+    let mut metadata = tango_protos::replay::ReplayMetadata::decode(replay_metadata)?;
+    for (side, hash) in [
+        (&mut metadata.local_side, local_patch_hash),
+        (&mut metadata.remote_side, remote_patch_hash),
+    ] {
+        let hash = if let Some(hash) = hash {
+            hash
+        } else {
+            continue;
+        };
+        side.get_or_insert_with(Default::default)
+            .game_info
+            .get_or_insert_with(Default::default)
+            .patch
+            .get_or_insert_with(Default::default)
+            .hash = hash.to_vec();
+    }
+    Ok(metadata.encode_to_vec())
+}
@@ -1,6 +1,6 @@
 use bincode::Options;
 
-pub const VERSION: u8 = 0x20;
+pub const VERSION: u8 = 0x2a;
 
 lazy_static! {
     static ref BINCODE_OPTIONS: bincode::config::WithOtherLimit<
@@ -22,6 +22,14 @@ pub enum Packet {
     Ping(Ping),
     Pong(Pong),
     Input(Input),
+    StateChecksum(StateChecksum),
+    DelayProposal(DelayProposal),
+    Reveal(Reveal),
+    Signature(Signature),
+    Ack(Ack),
+    Resume(Resume),
+    Goodbye(Goodbye),
+    ResumeProof(ResumeProof),
 }
 
 impl Packet {
@@ -32,6 +40,20 @@ impl Packet {
     pub fn deserialize(d: &[u8]) -> bincode::Result<Self> {
         BINCODE_OPTIONS.deserialize(d)
     }
+
+    /// Whether the packet has to reach the opponent even if the connection drops, in which case it is sent again after reconnecting.
+    ///
+    /// Everything else only matters at the time it was sent. Both sides count the packets this is true for, so they can tell each other where to resume from.
+    pub fn must_deliver(&self) -> bool {
+        !matches!(
+            self,
+            Packet::Ping(_)
+                | Packet::Pong(_)
+                | Packet::Ack(_)
+                | Packet::Resume(_)
+                | Packet::ResumeProof(_)
+        )
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
@@ -40,7 +62,27 @@ pub struct Hello {
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
-pub struct Hola {}
+pub struct Hola {
+    /// The input delay this side would like to play at, if it wants the input delay to be picked automatically.
+    pub recommended_input_delay: Option<u32>,
+    /// The hash of the patch this side has applied to its ROM, if any.
+    pub patch_hash: Option<[u8; 32]>,
+    pub game_info: GameInfo,
+    /// Which games this side's game can play against, if it is known at all.
+    pub netplay_compatibility: Option<String>,
+    pub match_type: (u8, u8),
+    /// The hash of the nonce this side will contribute to the RNG seed, which is only revealed once both sides have committed.
+    pub rng_commitment: [u8; 32],
+    /// The key this side will sign its replays with.
+    pub public_key: [u8; 32],
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GameInfo {
+    pub title: [u8; 16],
+    pub revision: u8,
+    pub crc32: u32,
+}
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct Smuggle {
@@ -64,3 +106,64 @@ pub struct Input {
     pub tick_diff: i8,
     pub joyflags: u16,
 }
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct StateChecksum {
+    pub round_number: u8,
+    pub tick: u32,
+    pub checksum: [u8; 32],
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct DelayProposal {
+    pub round_number: u8,
+    pub input_delay: u32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Reveal {
+    pub nonce: [u8; 16],
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Signature {
+    pub round_number: u8,
+    /// The sender's signature over the round's replay. This is always 64 bytes, but serde can't handle arrays that large.
+    pub signature: Vec<u8>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Ack {
+    /// How many packets that must be delivered the sender has received so far.
+    pub received: u64,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Resume {
+    /// How many packets that must be delivered the sender had received before the connection dropped: everything after that has to be sent again.
+    pub received: u64,
+    /// Random bytes for the receiver to sign, to prove they're who the sender was playing before the connection dropped.
+    pub challenge: [u8; 32],
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct ResumeProof {
+    /// The sender's signature over the challenge in the receiver's `Resume`. This is always 64 bytes, but serde can't handle arrays that large.
+    pub signature: Vec<u8>,
+}
+
+/// Why a player left the match.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GoodbyeReason {
+    Forfeit,
+    UserQuit,
+    Desync,
+    VersionError,
+    Crash,
+}
+
+/// Sent right before leaving the match on purpose, so the opponent can tell it apart from the connection dropping.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Goodbye {
+    pub reason: GoodbyeReason,
+}
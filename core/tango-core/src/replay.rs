@@ -1,19 +1,233 @@
 use crate::input;
+use crate::protocol;
+use crate::signing;
 use byteorder::ReadBytesExt;
 use byteorder::WriteBytesExt;
+use prost::Message;
 use std::io::Read;
 use std::io::Write;
 pub trait WriteSeek: std::io::Write + std::io::Seek {}
 impl<T: std::io::Write + std::io::Seek> WriteSeek for T {}
 
+pub trait ReadSeek: std::io::Read + std::io::Seek {}
+impl<T: std::io::Read + std::io::Seek> ReadSeek for T {}
+
 pub struct Writer {
-    encoder: Option<zstd::stream::write::Encoder<'static, Box<dyn WriteSeek + Send>>>,
+    writer: Option<Box<dyn WriteSeek + Send>>,
+    local_player_index: u8,
     num_inputs: u32,
+    chunk: Option<PendingChunk>,
+    chunks: Vec<ChunkInfo>,
+    inputs_since_keyframe: u32,
+    chain: signing::HashChain,
 }
 
 const HEADER: &[u8] = b"TOOT";
-const VERSION: u8 = 0x10;
 
+/// The original replay format: a header followed by a single zstd stream containing everything.
+const VERSION_LEGACY: u8 = 0x10;
+
+/// The chunked replay format: a header followed by length-prefixed sections, terminated by an index.
+pub const VERSION: u8 = 0x11;
+
+const NUM_INPUTS_OFFSET: u64 = (HEADER.len() + 1) as u64;
+const INDEX_OFFSET_OFFSET: u64 = NUM_INPUTS_OFFSET + 4;
+
+/// Number of input pairs that are compressed together into one chunk.
+const CHUNK_SIZE: u32 = 5 * 60;
+
+/// Number of input pairs between keyframes, if the writer is being fed keyframes.
+const KEYFRAME_INTERVAL: u32 = 10 * 60;
+
+mod section {
+    pub const STATE: u8 = 0x01;
+    pub const CHUNK: u8 = 0x02;
+    pub const INDEX: u8 = 0x03;
+    pub const STATS: u8 = 0x04;
+    pub const SIGNATURE: u8 = 0x05;
+    pub const GOODBYE: u8 = 0x06;
+}
+
+/// Describes where a chunk of input pairs lives in a replay file.
+#[derive(Clone, Debug)]
+pub struct ChunkInfo {
+    /// Offset of the chunk's section from the start of the file.
+    pub offset: u64,
+
+    /// Index of the first input pair in the chunk.
+    pub first_input: u32,
+
+    /// Tick of the first input pair in the chunk.
+    pub first_tick: u32,
+
+    /// Number of input pairs in the chunk.
+    pub num_inputs: u32,
+
+    /// If the chunk starts with a keyframe, i.e. a committed state at first_tick.
+    pub has_keyframe: bool,
+}
+
+const CHUNK_INFO_SIZE: usize = 8 + 4 + 4 + 4 + 1;
+
+pub struct Chunk {
+    pub keyframe: Option<mgba::state::State>,
+    pub input_pairs: Vec<input::Pair<input::Input, input::Input>>,
+}
+
+/// A state that a replayer can be started from, along with the chunk its input pairs start at.
+pub struct Keyframe {
+    pub tick: u32,
+    pub state: mgba::state::State,
+    pub chunk_index: usize,
+}
+
+struct PendingChunk {
+    first_input: u32,
+    first_tick: u32,
+    num_inputs: u32,
+    keyframe: Option<Vec<u8>>,
+    buf: Vec<u8>,
+}
+
+/// A sample of how much the emulator was sped up or slowed down to stay in step with the opponent.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct TpsAdjustment {
+    pub tick: u32,
+    pub adjustment: f32,
+}
+
+/// A summary of how the connection behaved over a round, stored as JSON in its own section.
+///
+/// Durations are in nanoseconds. Fields may be added over time, so anything missing is defaulted.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct Stats {
+    pub input_delay: u32,
+    pub remote_input_delay: u32,
+    pub num_rtt_samples: u32,
+    pub rtt_p50: Option<u64>,
+    pub rtt_p90: Option<u64>,
+    pub rtt_p99: Option<u64>,
+    pub jitter: Option<u64>,
+
+    /// Number of times a committed remote input turned out not to be what was predicted, so frames already emulated with the prediction had to be emulated again.
+    pub num_rollbacks: u32,
+
+    /// How many frames those rollbacks emulated again, in total and at most in any one of them.
+    pub frames_resimulated: u64,
+    pub max_rollback_depth: u32,
+
+    /// The most frames that were ever emulated ahead of the opponent's inputs.
+    pub max_prediction_depth: u32,
+    pub tps_adjustments: Vec<TpsAdjustment>,
+}
+
+fn decode_stats(payload: &[u8]) -> std::io::Result<Stats> {
+    serde_json::from_slice(payload).map_err(|e| invalid_data(&e.to_string()))
+}
+
+/// A player leaving the match partway through the round, stored as JSON in its own section.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Goodbye {
+    /// The player who left: 0 for P1, 1 for P2.
+    pub player_index: u8,
+    pub reason: protocol::GoodbyeReason,
+
+    /// How far into the round they left.
+    pub tick: u32,
+}
+
+fn decode_goodbye(payload: &[u8]) -> std::io::Result<Goodbye> {
+    serde_json::from_slice(payload).map_err(|e| invalid_data(&e.to_string()))
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct PatchInfo {
+    pub name: String,
+    pub version: String,
+    /// The hash of the patch as it was applied, if the core recorded one.
+    pub hash: Option<String>,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct GameInfo {
+    pub rom: String,
+    pub patch: Option<PatchInfo>,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct SideInfo {
+    pub nickname: String,
+    pub game: Option<GameInfo>,
+    pub reveal_setup: bool,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct RepairInfo {
+    pub num_inputs: u32,
+    pub truncated_at: u64,
+}
+
+/// Everything about a replay that can be known without emulating it, with its metadata decoded.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct Info {
+    pub ts: u64,
+    pub link_code: String,
+    pub local_side: Option<SideInfo>,
+    pub remote_side: Option<SideInfo>,
+    pub local_player_index: u8,
+    pub is_complete: bool,
+    pub num_inputs: u32,
+    /// Set if this replay was salvaged from one that was cut off.
+    pub repair: Option<RepairInfo>,
+    /// Set if a player left the match during this round, rather than the round being played out or the connection dropping.
+    pub goodbye: Option<Goodbye>,
+}
+
+impl Info {
+    fn new(
+        metadata: &[u8],
+        local_player_index: u8,
+        is_complete: bool,
+        num_inputs: u32,
+        goodbye: Option<Goodbye>,
+    ) -> std::io::Result<Self> {
+        let metadata = tango_protos::replay::ReplayMetadata::decode(metadata)
+            .map_err(|e| invalid_data(&e.to_string()))?;
+        let side_info = |side: tango_protos::replay::replay_metadata::Side| SideInfo {
+            nickname: side.nickname,
+            game: side.game_info.map(|game_info| GameInfo {
+                rom: game_info.rom,
+                patch: game_info.patch.map(|patch| PatchInfo {
+                    name: patch.name,
+                    version: patch.version,
+                    hash: if patch.hash.is_empty() {
+                        None
+                    } else {
+                        Some(hex::encode(patch.hash))
+                    },
+                }),
+            }),
+            reveal_setup: side.reveal_setup,
+        };
+        Ok(Self {
+            ts: metadata.ts,
+            link_code: metadata.link_code,
+            local_side: metadata.local_side.map(side_info),
+            remote_side: metadata.remote_side.map(side_info),
+            local_player_index,
+            is_complete,
+            num_inputs,
+            repair: metadata.repair.map(|repair| RepairInfo {
+                num_inputs: repair.num_inputs,
+                truncated_at: repair.truncated_at,
+            }),
+            goodbye,
+        })
+    }
+}
+
+#[derive(Clone)]
 pub struct Replay {
     pub is_complete: bool,
     pub metadata: Vec<u8>,
@@ -21,6 +235,174 @@ pub struct Replay {
     pub local_state: Option<mgba::state::State>,
     pub remote_state: Option<mgba::state::State>,
     pub input_pairs: Vec<input::Pair<input::Input, input::Input>>,
+    pub stats: Option<Stats>,
+    pub goodbye: Option<Goodbye>,
+}
+
+fn invalid_data(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+fn read_input_pair(
+    mut r: impl std::io::Read,
+    local_player_index: u8,
+    input_raw_size: usize,
+) -> Option<input::Pair<input::Input, input::Input>> {
+    let local_tick = r.read_u32::<byteorder::LittleEndian>().ok()?;
+    let remote_tick = r.read_u32::<byteorder::LittleEndian>().ok()?;
+
+    let mut p1_input = input::Input {
+        local_tick,
+        remote_tick,
+        joyflags: r.read_u16::<byteorder::LittleEndian>().ok()?,
+        packet: vec![0u8; input_raw_size],
+    };
+    r.read_exact(&mut p1_input.packet).ok()?;
+
+    let mut p2_input = input::Input {
+        local_tick,
+        remote_tick: local_tick,
+        joyflags: r.read_u16::<byteorder::LittleEndian>().ok()?,
+        packet: vec![0u8; input_raw_size],
+    };
+    r.read_exact(&mut p2_input.packet).ok()?;
+
+    let (local, remote) = if local_player_index == 0 {
+        (p1_input, p2_input)
+    } else {
+        (p2_input, p1_input)
+    };
+
+    Some(input::Pair { local, remote })
+}
+
+fn write_input_pair(
+    mut w: impl std::io::Write,
+    local_player_index: u8,
+    ip: &input::Pair<input::Input, input::Input>,
+) -> std::io::Result<()> {
+    w.write_u32::<byteorder::LittleEndian>(ip.local.local_tick)?;
+    w.write_u32::<byteorder::LittleEndian>(ip.local.remote_tick)?;
+
+    let (p1, p2) = if local_player_index == 0 {
+        (&ip.local, &ip.remote)
+    } else {
+        (&ip.remote, &ip.local)
+    };
+
+    w.write_u16::<byteorder::LittleEndian>(p1.joyflags)?;
+    w.write_all(&p1.packet)?;
+    w.write_u16::<byteorder::LittleEndian>(p2.joyflags)?;
+    w.write_all(&p2.packet)?;
+    Ok(())
+}
+
+fn read_section(mut r: impl std::io::Read) -> std::io::Result<Option<(u8, Vec<u8>)>> {
+    let kind = match r.read_u8() {
+        Ok(kind) => kind,
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+            return Ok(None);
+        }
+        Err(e) => {
+            return Err(e);
+        }
+    };
+    let mut payload = vec![0u8; r.read_u32::<byteorder::LittleEndian>()? as usize];
+    r.read_exact(&mut payload)?;
+    Ok(Some((kind, payload)))
+}
+
+fn decode_state(payload: &[u8]) -> std::io::Result<Option<mgba::state::State>> {
+    if payload.is_empty() {
+        return Ok(None);
+    }
+    let raw = zstd::stream::decode_all(payload)?;
+    Ok(Some(mgba::state::State::from_slice(&raw)))
+}
+
+fn decode_chunk(
+    payload: &[u8],
+    local_player_index: u8,
+    input_raw_size: usize,
+) -> std::io::Result<(ChunkInfo, Chunk)> {
+    let mut r = payload;
+    let first_tick = r.read_u32::<byteorder::LittleEndian>()?;
+    let num_inputs = r.read_u32::<byteorder::LittleEndian>()?;
+    let has_keyframe = r.read_u8()? != 0;
+
+    let mut zr = zstd::stream::read::Decoder::new(r)?;
+
+    let keyframe = if has_keyframe {
+        let mut state = vec![0u8; zr.read_u32::<byteorder::LittleEndian>()? as usize];
+        zr.read_exact(&mut state)?;
+        Some(mgba::state::State::from_slice(&state))
+    } else {
+        None
+    };
+
+    let mut input_pairs = Vec::with_capacity(num_inputs as usize);
+    for _ in 0..num_inputs {
+        input_pairs.push(
+            read_input_pair(&mut zr, local_player_index, input_raw_size)
+                .ok_or_else(|| invalid_data("truncated chunk"))?,
+        );
+    }
+
+    Ok((
+        ChunkInfo {
+            offset: 0,
+            first_input: 0,
+            first_tick,
+            num_inputs,
+            has_keyframe,
+        },
+        Chunk {
+            keyframe,
+            input_pairs,
+        },
+    ))
+}
+
+struct V2Header {
+    num_inputs: u32,
+    index_offset: u64,
+    metadata: Vec<u8>,
+    local_player_index: u8,
+    input_raw_size: u8,
+}
+
+fn read_header(mut r: impl std::io::Read) -> std::io::Result<u8> {
+    let mut header = [0u8; 4];
+    r.read_exact(&mut header)?;
+    if &header != HEADER {
+        return Err(invalid_data("invalid header"));
+    }
+
+    let version = r.read_u8()?;
+    if version != VERSION_LEGACY && version != VERSION {
+        return Err(invalid_data("invalid version"));
+    }
+    Ok(version)
+}
+
+fn read_v2_header(mut r: impl std::io::Read) -> std::io::Result<V2Header> {
+    let num_inputs = r.read_u32::<byteorder::LittleEndian>()?;
+    let index_offset = r.read_u64::<byteorder::LittleEndian>()?;
+
+    let metadata_len = r.read_u32::<byteorder::LittleEndian>()?;
+    let mut metadata = vec![0u8; metadata_len as usize];
+    r.read_exact(&mut metadata[..])?;
+
+    let local_player_index = r.read_u8()?;
+    let input_raw_size = r.read_u8()?;
+
+    Ok(V2Header {
+        num_inputs,
+        index_offset,
+        metadata,
+        local_player_index,
+        input_raw_size,
+    })
 }
 
 impl Replay {
@@ -35,23 +417,25 @@ impl Replay {
         Some(self)
     }
 
-    pub fn decode(mut r: impl std::io::Read) -> std::io::Result<Self> {
-        let mut header = [0u8; 4];
-        r.read_exact(&mut header)?;
-        if &header != HEADER {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "invalid header",
-            ));
-        }
+    pub fn info(&self) -> std::io::Result<Info> {
+        Info::new(
+            &self.metadata,
+            self.local_player_index,
+            self.is_complete,
+            self.input_pairs.len() as u32,
+            self.goodbye.clone(),
+        )
+    }
 
-        if r.read_u8()? != VERSION {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "invalid version",
-            ));
+    pub fn decode(mut r: impl std::io::Read) -> std::io::Result<Self> {
+        match read_header(&mut r)? {
+            VERSION_LEGACY => Self::decode_legacy(r),
+            VERSION => Self::decode_v2(r),
+            _ => unreachable!(),
         }
+    }
 
+    fn decode_legacy(mut r: impl std::io::Read) -> std::io::Result<Self> {
         let num_inputs = r.read_u32::<byteorder::LittleEndian>()?;
 
         let metadata_len = r.read_u32::<byteorder::LittleEndian>()?;
@@ -82,62 +466,662 @@ impl Replay {
 
         let mut input_pairs = vec![];
 
+        while let Some(ip) = read_input_pair(&mut zr, local_player_index, input_raw_size) {
+            input_pairs.push(ip);
+        }
+
+        Ok(Self {
+            is_complete: num_inputs > 0 && num_inputs as usize == input_pairs.len(),
+            metadata,
+            local_player_index,
+            local_state,
+            remote_state,
+            input_pairs,
+            stats: None,
+            goodbye: None,
+        })
+    }
+
+    fn decode_v2(mut r: impl std::io::Read) -> std::io::Result<Self> {
+        let header = read_v2_header(&mut r)?;
+
+        let mut states = vec![];
+        let mut input_pairs = vec![];
+        let mut stats = None;
+        let mut goodbye = None;
+
+        // Everything past the header is read leniently: if the replay was cut off, we keep everything up until the point it was cut off at.
         loop {
-            let local_tick = if let Ok(v) = zr.read_u32::<byteorder::LittleEndian>() {
-                v
-            } else {
-                break;
-            };
-            let remote_tick = if let Ok(v) = zr.read_u32::<byteorder::LittleEndian>() {
-                v
-            } else {
-                break;
+            let (kind, payload) = match read_section(&mut r) {
+                Ok(Some(section)) => section,
+                Ok(None) | Err(_) => {
+                    break;
+                }
             };
 
-            let mut p1_input = input::Input {
-                local_tick,
-                remote_tick,
-                joyflags: if let Ok(v) = zr.read_u16::<byteorder::LittleEndian>() {
-                    v
-                } else {
+            match kind {
+                section::STATE => {
+                    let state = match decode_state(&payload) {
+                        Ok(state) => state,
+                        Err(_) => {
+                            break;
+                        }
+                    };
+                    states.push(state);
+                }
+                section::CHUNK => {
+                    let (_, chunk) = match decode_chunk(
+                        &payload,
+                        header.local_player_index,
+                        header.input_raw_size as usize,
+                    ) {
+                        Ok(chunk) => chunk,
+                        Err(_) => {
+                            break;
+                        }
+                    };
+                    input_pairs.extend(chunk.input_pairs);
+                }
+                section::STATS => {
+                    stats = decode_stats(&payload).ok();
+                }
+                section::GOODBYE => {
+                    goodbye = decode_goodbye(&payload).ok();
+                }
+                section::INDEX => {
                     break;
+                }
+                _ => {
+                    // Unknown sections are skipped for forward compatibility.
+                }
+            }
+        }
+
+        let mut states = states.into_iter();
+        let local_state = states.next().flatten();
+        let remote_state = states.next().flatten();
+
+        Ok(Self {
+            is_complete: header.num_inputs > 0 && header.num_inputs as usize == input_pairs.len(),
+            metadata: header.metadata,
+            local_player_index: header.local_player_index,
+            local_state,
+            remote_state,
+            input_pairs,
+            stats,
+            goodbye,
+        })
+    }
+}
+
+/// Everything that could be read back out of a replay that was cut off.
+pub struct Salvage {
+    pub metadata: Vec<u8>,
+    pub local_player_index: u8,
+    pub input_raw_size: u8,
+    pub local_state: Option<mgba::state::State>,
+    pub remote_state: Option<mgba::state::State>,
+
+    /// The chunks that could be read, along with the tick each starts at. The last one may be missing input pairs.
+    pub chunks: Vec<(u32, Chunk)>,
+    pub stats: Option<Stats>,
+    pub goodbye: Option<Goodbye>,
+
+    /// Where the readable data in the file ended.
+    pub truncated_at: u64,
+
+    /// If the replay had in fact been finished properly, in which case there is nothing to repair.
+    pub was_complete: bool,
+}
+
+/// Reads as many input pairs as possible out of a chunk, even if its compressed data is cut off partway.
+///
+/// Returns the chunk and whether all of its input pairs could be read.
+fn salvage_chunk(
+    payload: &[u8],
+    local_player_index: u8,
+    input_raw_size: usize,
+) -> Option<(u32, Chunk, bool)> {
+    let mut r = payload;
+    let first_tick = r.read_u32::<byteorder::LittleEndian>().ok()?;
+    let num_inputs = r.read_u32::<byteorder::LittleEndian>().ok()?;
+    let has_keyframe = r.read_u8().ok()? != 0;
+
+    let mut zr = zstd::stream::read::Decoder::new(r).ok()?;
+
+    // Input pairs after a keyframe can only be replayed from it, so they're no use without it.
+    let keyframe = if has_keyframe {
+        let mut state = vec![0u8; zr.read_u32::<byteorder::LittleEndian>().ok()? as usize];
+        zr.read_exact(&mut state).ok()?;
+        Some(mgba::state::State::from_slice(&state))
+    } else {
+        None
+    };
+
+    let mut input_pairs = vec![];
+    while input_pairs.len() < num_inputs as usize {
+        match read_input_pair(&mut zr, local_player_index, input_raw_size) {
+            Some(ip) => input_pairs.push(ip),
+            None => break,
+        }
+    }
+
+    let is_complete = input_pairs.len() == num_inputs as usize;
+    Some((
+        first_tick,
+        Chunk {
+            keyframe,
+            input_pairs,
+        },
+        is_complete,
+    ))
+}
+
+/// Reads everything that can be read out of a replay, stopping at the first thing that's been cut off or is otherwise unreadable.
+pub fn salvage(buf: &[u8]) -> std::io::Result<Salvage> {
+    let mut r = buf;
+    if read_header(&mut r)? == VERSION_LEGACY {
+        // Legacy replays are one long compressed stream, which decoding already reads as far as it can.
+        let replay = Replay::decode(buf)?;
+        let input_raw_size = replay
+            .input_pairs
+            .first()
+            .map(|ip| ip.local.packet.len() as u8)
+            .unwrap_or(0);
+        return Ok(Salvage {
+            was_complete: replay.is_complete,
+            metadata: replay.metadata,
+            local_player_index: replay.local_player_index,
+            input_raw_size,
+            local_state: replay.local_state,
+            remote_state: replay.remote_state,
+            chunks: vec![(
+                replay
+                    .input_pairs
+                    .first()
+                    .map(|ip| ip.local.local_tick)
+                    .unwrap_or(0),
+                Chunk {
+                    keyframe: None,
+                    input_pairs: replay.input_pairs,
                 },
-                packet: vec![0u8; input_raw_size],
-            };
-            if zr.read_exact(&mut p1_input.packet).is_err() {
+            )],
+            stats: None,
+            goodbye: None,
+            truncated_at: buf.len() as u64,
+        });
+    }
+
+    let header = read_v2_header(&mut r)?;
+
+    let mut states = vec![];
+    let mut chunks = vec![];
+    let mut stats = None;
+    let mut goodbye = None;
+    let mut saw_index = false;
+    let truncated_at = loop {
+        let offset = (buf.len() - r.len()) as u64;
+
+        let (kind, len) = match (r.read_u8(), r.read_u32::<byteorder::LittleEndian>()) {
+            (Ok(kind), Ok(len)) => (kind, len as usize),
+            _ => {
+                break offset;
+            }
+        };
+        let payload = &r[..len.min(r.len())];
+        r = &r[payload.len()..];
+        let is_cut_off = payload.len() < len;
+
+        match kind {
+            section::STATE => match decode_state(payload) {
+                Ok(state) if !is_cut_off => states.push(state),
+                _ => {
+                    break offset;
+                }
+            },
+            section::CHUNK => {
+                let (first_tick, chunk, is_complete) = match salvage_chunk(
+                    payload,
+                    header.local_player_index,
+                    header.input_raw_size as usize,
+                ) {
+                    Some(chunk) => chunk,
+                    None => {
+                        break offset;
+                    }
+                };
+                if !chunk.input_pairs.is_empty() {
+                    chunks.push((first_tick, chunk));
+                }
+                if !is_complete {
+                    break offset;
+                }
+            }
+            section::STATS => {
+                stats = decode_stats(payload).ok();
+            }
+            section::GOODBYE => {
+                goodbye = decode_goodbye(payload).ok();
+            }
+            section::INDEX => {
+                saw_index = true;
+                break offset;
+            }
+            _ => {}
+        }
+
+        if is_cut_off {
+            break offset;
+        }
+    };
+
+    let num_inputs = chunks
+        .iter()
+        .map(|(_, chunk)| chunk.input_pairs.len())
+        .sum::<usize>();
+
+    let mut states = states.into_iter();
+    Ok(Salvage {
+        was_complete: saw_index
+            && header.num_inputs > 0
+            && header.num_inputs as usize == num_inputs,
+        metadata: header.metadata,
+        local_player_index: header.local_player_index,
+        input_raw_size: header.input_raw_size,
+        local_state: states.next().flatten(),
+        remote_state: states.next().flatten(),
+        chunks,
+        stats,
+        goodbye,
+        truncated_at,
+    })
+}
+
+impl Salvage {
+    pub fn num_inputs(&self) -> u32 {
+        self.chunks
+            .iter()
+            .map(|(_, chunk)| chunk.input_pairs.len() as u32)
+            .sum()
+    }
+
+    /// Writes out everything that was salvaged as a new, complete replay, noting in its metadata that it was repaired.
+    pub fn write_repaired(&self, w: Box<dyn WriteSeek + Send>) -> std::io::Result<()> {
+        let (local_state, remote_state) = match (&self.local_state, &self.remote_state) {
+            (Some(local_state), Some(remote_state)) => (local_state, remote_state),
+            _ => {
+                return Err(invalid_data("replay was cut off before the round started"));
+            }
+        };
+
+        let mut metadata = tango_protos::replay::ReplayMetadata::decode(&self.metadata[..])
+            .map_err(|e| invalid_data(&e.to_string()))?;
+        metadata.repair = Some(tango_protos::replay::replay_metadata::Repair {
+            num_inputs: self.num_inputs(),
+            truncated_at: self.truncated_at,
+        });
+
+        let mut writer = Writer::new(
+            w,
+            &metadata.encode_to_vec(),
+            self.local_player_index,
+            self.input_raw_size,
+        )?;
+        writer.write_state(local_state)?;
+        writer.write_state(remote_state)?;
+        for (first_tick, chunk) in self.chunks.iter() {
+            if let Some(keyframe) = chunk.keyframe.as_ref() {
+                writer.write_keyframe(*first_tick, keyframe)?;
+            }
+            for ip in chunk.input_pairs.iter() {
+                writer.write_input(self.local_player_index, ip)?;
+            }
+        }
+        if let Some(stats) = self.stats.as_ref() {
+            writer.write_stats(stats)?;
+        }
+        if let Some(goodbye) = self.goodbye.as_ref() {
+            writer.write_goodbye(goodbye)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+enum ReaderSource {
+    Legacy(Replay),
+    V2 {
+        header: V2Header,
+        state_offsets: Vec<u64>,
+    },
+}
+
+/// Provides random access to a replay without decoding all of it.
+///
+/// Legacy replays do not support random access, so they are fully decoded up front and presented as a single chunk.
+pub struct ReplayReader {
+    r: Box<dyn ReadSeek + Send>,
+    source: ReaderSource,
+    chunks: Vec<ChunkInfo>,
+}
+
+impl ReplayReader {
+    pub fn new(mut r: Box<dyn ReadSeek + Send>) -> std::io::Result<Self> {
+        let version = read_header(&mut r)?;
+        if version == VERSION_LEGACY {
+            r.seek(std::io::SeekFrom::Start(0))?;
+            let replay = Replay::decode(&mut r)?;
+            let chunks = vec![ChunkInfo {
+                offset: 0,
+                first_input: 0,
+                first_tick: replay
+                    .input_pairs
+                    .first()
+                    .map(|ip| ip.local.local_tick)
+                    .unwrap_or(0),
+                num_inputs: replay.input_pairs.len() as u32,
+                has_keyframe: false,
+            }];
+            return Ok(Self {
+                r,
+                source: ReaderSource::Legacy(replay),
+                chunks,
+            });
+        }
+
+        let header = read_v2_header(&mut r)?;
+
+        // Find where all the sections are. If there's an index, we can avoid scanning the chunks.
+        let mut state_offsets = vec![];
+        let mut chunks = vec![];
+        let mut num_inputs = 0;
+        loop {
+            let offset = r.stream_position()?;
+            if header.index_offset != 0 && offset == header.index_offset {
                 break;
             }
 
-            let mut p2_input = input::Input {
-                local_tick,
-                remote_tick: local_tick,
-                joyflags: if let Ok(v) = zr.read_u16::<byteorder::LittleEndian>() {
-                    v
-                } else {
+            let kind = match r.read_u8() {
+                Ok(kind) => kind,
+                Err(_) => {
                     break;
-                },
-                packet: vec![0u8; input_raw_size],
+                }
             };
-            if zr.read_exact(&mut p2_input.packet).is_err() {
-                break;
+            let len = match r.read_u32::<byteorder::LittleEndian>() {
+                Ok(len) => len,
+                Err(_) => {
+                    break;
+                }
+            };
+
+            match kind {
+                section::STATE => {
+                    state_offsets.push(offset);
+                    if state_offsets.len() == 2 && header.index_offset != 0 {
+                        break;
+                    }
+                }
+                section::CHUNK => {
+                    let mut prefix = [0u8; 9];
+                    if r.read_exact(&mut prefix).is_err() {
+                        break;
+                    }
+                    let mut raw = &prefix[..];
+                    let first_tick = raw.read_u32::<byteorder::LittleEndian>()?;
+                    let chunk_num_inputs = raw.read_u32::<byteorder::LittleEndian>()?;
+                    let has_keyframe = raw.read_u8()? != 0;
+                    chunks.push(ChunkInfo {
+                        offset,
+                        first_input: num_inputs,
+                        first_tick,
+                        num_inputs: chunk_num_inputs,
+                        has_keyframe,
+                    });
+                    num_inputs += chunk_num_inputs;
+                    r.seek(std::io::SeekFrom::Current(len as i64 - prefix.len() as i64))?;
+                    continue;
+                }
+                section::INDEX => {
+                    break;
+                }
+                _ => {}
             }
 
-            let (local, remote) = if local_player_index == 0 {
-                (p1_input, p2_input)
-            } else {
-                (p2_input, p1_input)
-            };
+            r.seek(std::io::SeekFrom::Current(len as i64))?;
+        }
 
-            input_pairs.push(input::Pair { local, remote });
+        if header.index_offset != 0 {
+            r.seek(std::io::SeekFrom::Start(header.index_offset))?;
+            let (kind, payload) = read_section(&mut r)?.ok_or_else(|| invalid_data("no index"))?;
+            if kind != section::INDEX {
+                return Err(invalid_data("invalid index"));
+            }
+            chunks = payload
+                .chunks_exact(CHUNK_INFO_SIZE)
+                .map(|mut raw| {
+                    Ok(ChunkInfo {
+                        offset: raw.read_u64::<byteorder::LittleEndian>()?,
+                        first_input: raw.read_u32::<byteorder::LittleEndian>()?,
+                        first_tick: raw.read_u32::<byteorder::LittleEndian>()?,
+                        num_inputs: raw.read_u32::<byteorder::LittleEndian>()?,
+                        has_keyframe: raw.read_u8()? != 0,
+                    })
+                })
+                .collect::<std::io::Result<Vec<_>>>()?;
         }
 
         Ok(Self {
-            is_complete: num_inputs > 0 && num_inputs as usize == input_pairs.len(),
-            metadata,
-            local_player_index,
-            local_state,
-            remote_state,
-            input_pairs,
+            r,
+            source: ReaderSource::V2 {
+                header,
+                state_offsets,
+            },
+            chunks,
+        })
+    }
+
+    pub fn metadata(&self) -> &[u8] {
+        match &self.source {
+            ReaderSource::Legacy(replay) => &replay.metadata,
+            ReaderSource::V2 { header, .. } => &header.metadata,
+        }
+    }
+
+    pub fn local_player_index(&self) -> u8 {
+        match &self.source {
+            ReaderSource::Legacy(replay) => replay.local_player_index,
+            ReaderSource::V2 { header, .. } => header.local_player_index,
+        }
+    }
+
+    pub fn num_inputs(&self) -> u32 {
+        self.chunks.iter().map(|c| c.num_inputs).sum()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        match &self.source {
+            ReaderSource::Legacy(replay) => replay.is_complete,
+            ReaderSource::V2 { header, .. } => {
+                header.num_inputs > 0 && header.num_inputs == self.num_inputs()
+            }
+        }
+    }
+
+    pub fn chunks(&self) -> &[ChunkInfo] {
+        &self.chunks
+    }
+
+    pub fn info(&mut self) -> std::io::Result<Info> {
+        let goodbye = self.goodbye()?;
+        Info::new(
+            self.metadata(),
+            self.local_player_index(),
+            self.is_complete(),
+            self.num_inputs(),
+            goodbye,
+        )
+    }
+
+    fn read_state(&mut self, i: usize) -> std::io::Result<Option<mgba::state::State>> {
+        let offset = match &self.source {
+            ReaderSource::Legacy(replay) => {
+                return Ok(if i == 0 {
+                    replay.local_state.clone()
+                } else {
+                    replay.remote_state.clone()
+                });
+            }
+            ReaderSource::V2 { state_offsets, .. } => match state_offsets.get(i) {
+                Some(offset) => *offset,
+                None => {
+                    return Ok(None);
+                }
+            },
+        };
+        self.r.seek(std::io::SeekFrom::Start(offset))?;
+        let (_, payload) = read_section(&mut self.r)?.ok_or_else(|| invalid_data("no state"))?;
+        decode_state(&payload)
+    }
+
+    /// Reads the round's connection statistics, if the replay was finished with them.
+    pub fn stats(&mut self) -> std::io::Result<Option<Stats>> {
+        if let ReaderSource::Legacy(replay) = &self.source {
+            return Ok(replay.stats.clone());
+        }
+        self.read_trailing_section(section::STATS)?
+            .map(|payload| decode_stats(&payload))
+            .transpose()
+    }
+
+    /// Reads who left the match during the round and why, if anyone did.
+    pub fn goodbye(&mut self) -> std::io::Result<Option<Goodbye>> {
+        if let ReaderSource::Legacy(replay) = &self.source {
+            return Ok(replay.goodbye.clone());
+        }
+        self.read_trailing_section(section::GOODBYE)?
+            .map(|payload| decode_goodbye(&payload))
+            .transpose()
+    }
+
+    /// Finds a section of the given kind among those written after the last chunk, before the index.
+    fn read_trailing_section(&mut self, kind: u8) -> std::io::Result<Option<Vec<u8>>> {
+        let state_offsets = match &self.source {
+            ReaderSource::Legacy(_) => {
+                return Ok(None);
+            }
+            ReaderSource::V2 { state_offsets, .. } => state_offsets,
+        };
+
+        // These are written after the last chunk, so start looking from there.
+        let offset = match self
+            .chunks
+            .last()
+            .map(|c| c.offset)
+            .or_else(|| state_offsets.first().cloned())
+        {
+            Some(offset) => offset,
+            None => {
+                return Ok(None);
+            }
+        };
+        self.r.seek(std::io::SeekFrom::Start(offset))?;
+        while let Some((section_kind, payload)) = read_section(&mut self.r)? {
+            if section_kind == kind {
+                return Ok(Some(payload));
+            }
+            if section_kind == section::INDEX {
+                break;
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads both players' signatures, if the replay was finished and signed.
+    pub fn signature(&mut self) -> std::io::Result<Option<signing::Footer>> {
+        let index_offset = match &self.source {
+            ReaderSource::Legacy(_) => {
+                return Ok(None);
+            }
+            ReaderSource::V2 { header, .. } => header.index_offset,
+        };
+        if index_offset == 0 {
+            return Ok(None);
+        }
+
+        self.r.seek(std::io::SeekFrom::Start(index_offset))?;
+        while let Some((kind, payload)) = read_section(&mut self.r)? {
+            if kind == section::SIGNATURE {
+                return Ok(Some(signing::Footer::decode(&payload[..])?));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn local_state(&mut self) -> std::io::Result<Option<mgba::state::State>> {
+        self.read_state(0)
+    }
+
+    pub fn remote_state(&mut self) -> std::io::Result<Option<mgba::state::State>> {
+        self.read_state(1)
+    }
+
+    pub fn read_chunk(&mut self, i: usize) -> std::io::Result<Chunk> {
+        let (local_player_index, input_raw_size) = match &self.source {
+            ReaderSource::Legacy(replay) => {
+                return Ok(Chunk {
+                    keyframe: None,
+                    input_pairs: replay.input_pairs.clone(),
+                });
+            }
+            ReaderSource::V2 { header, .. } => {
+                (header.local_player_index, header.input_raw_size as usize)
+            }
+        };
+        let info = self
+            .chunks
+            .get(i)
+            .ok_or_else(|| invalid_data("no such chunk"))?;
+        self.r.seek(std::io::SeekFrom::Start(info.offset))?;
+        let (_, payload) = read_section(&mut self.r)?.ok_or_else(|| invalid_data("no chunk"))?;
+        let (_, chunk) = decode_chunk(&payload, local_player_index, input_raw_size)?;
+        Ok(chunk)
+    }
+
+    /// Reads all input pairs starting from the given chunk until the end of the replay.
+    pub fn input_pairs_from(
+        &mut self,
+        chunk_index: usize,
+    ) -> std::io::Result<Vec<input::Pair<input::Input, input::Input>>> {
+        let mut input_pairs = vec![];
+        for i in chunk_index..self.chunks.len() {
+            input_pairs.extend(self.read_chunk(i)?.input_pairs);
+        }
+        Ok(input_pairs)
+    }
+
+    /// Finds the closest keyframe at or before the given tick.
+    ///
+    /// If there are no keyframes before the tick, the local state is used as the keyframe instead.
+    pub fn seek(&mut self, tick: u32) -> std::io::Result<Keyframe> {
+        if let Some(chunk_index) = self
+            .chunks
+            .iter()
+            .rposition(|c| c.has_keyframe && c.first_tick <= tick)
+        {
+            let keyframe = self.read_chunk(chunk_index)?.keyframe.expect("keyframe");
+            return Ok(Keyframe {
+                tick: self.chunks[chunk_index].first_tick,
+                state: keyframe,
+                chunk_index,
+            });
+        }
+
+        Ok(Keyframe {
+            tick: self.chunks.first().map(|c| c.first_tick).unwrap_or(0),
+            state: self
+                .local_state()?
+                .ok_or_else(|| invalid_data("no local state"))?,
+            chunk_index: 0,
         })
     }
 }
@@ -152,25 +1136,86 @@ impl Writer {
         writer.write_all(HEADER)?;
         writer.write_u8(VERSION)?;
         writer.write_u32::<byteorder::LittleEndian>(0)?;
+        writer.write_u64::<byteorder::LittleEndian>(0)?;
         writer.write_u32::<byteorder::LittleEndian>(metadata.len() as u32)?;
         writer.write_all(metadata)?;
-        let mut encoder = zstd::Encoder::new(writer, 3)?;
-        encoder.write_u8(local_player_index)?;
-        encoder.write_u8(raw_input_size)?;
-        encoder.flush()?;
+        writer.write_u8(local_player_index)?;
+        writer.write_u8(raw_input_size)?;
+        writer.flush()?;
         Ok(Writer {
-            encoder: Some(encoder),
+            writer: Some(writer),
+            local_player_index,
             num_inputs: 0,
+            chunk: None,
+            chunks: vec![],
+            inputs_since_keyframe: 0,
+            chain: signing::HashChain::new(),
         })
     }
 
+    fn write_section(&mut self, kind: u8, payload: &[u8]) -> std::io::Result<u64> {
+        let writer = self.writer.as_mut().unwrap();
+        let offset = writer.stream_position()?;
+        writer.write_u8(kind)?;
+        writer.write_u32::<byteorder::LittleEndian>(payload.len() as u32)?;
+        writer.write_all(payload)?;
+        writer.flush()?;
+        Ok(offset)
+    }
+
     pub fn write_state(&mut self, state: &mgba::state::State) -> std::io::Result<()> {
-        self.encoder
-            .as_mut()
-            .unwrap()
-            .write_u32::<byteorder::LittleEndian>(state.as_slice().len() as u32)?;
-        self.encoder.as_mut().unwrap().write_all(state.as_slice())?;
-        self.encoder.as_mut().unwrap().flush()?;
+        let payload = zstd::stream::encode_all(state.as_slice(), 3)?;
+        self.write_section(section::STATE, &payload)?;
+        Ok(())
+    }
+
+    fn flush_chunk(&mut self) -> std::io::Result<()> {
+        let chunk = if let Some(chunk) = self.chunk.take() {
+            chunk
+        } else {
+            return Ok(());
+        };
+
+        let mut raw = vec![];
+        if let Some(keyframe) = chunk.keyframe.as_ref() {
+            raw.write_u32::<byteorder::LittleEndian>(keyframe.len() as u32)?;
+            raw.write_all(keyframe)?;
+        }
+        raw.write_all(&chunk.buf)?;
+
+        let mut payload = vec![];
+        payload.write_u32::<byteorder::LittleEndian>(chunk.first_tick)?;
+        payload.write_u32::<byteorder::LittleEndian>(chunk.num_inputs)?;
+        payload.write_u8(chunk.keyframe.is_some() as u8)?;
+        payload.write_all(&zstd::stream::encode_all(&raw[..], 3)?)?;
+
+        let offset = self.write_section(section::CHUNK, &payload)?;
+        self.chunks.push(ChunkInfo {
+            offset,
+            first_input: chunk.first_input,
+            first_tick: chunk.first_tick,
+            num_inputs: chunk.num_inputs,
+            has_keyframe: chunk.keyframe.is_some(),
+        });
+        Ok(())
+    }
+
+    /// Returns if enough input pairs have been written since the last keyframe that another one should be written.
+    pub fn wants_keyframe(&self) -> bool {
+        self.inputs_since_keyframe >= KEYFRAME_INTERVAL
+    }
+
+    /// Writes a committed state that the next input pair (at the given tick) can be replayed from.
+    pub fn write_keyframe(&mut self, tick: u32, state: &mgba::state::State) -> std::io::Result<()> {
+        self.flush_chunk()?;
+        self.chunk = Some(PendingChunk {
+            first_input: self.num_inputs,
+            first_tick: tick,
+            num_inputs: 0,
+            keyframe: Some(state.as_slice().to_vec()),
+            buf: vec![],
+        });
+        self.inputs_since_keyframe = 0;
         Ok(())
     }
 
@@ -179,49 +1224,101 @@ impl Writer {
         local_player_index: u8,
         ip: &input::Pair<input::Input, input::Input>,
     ) -> std::io::Result<()> {
-        self.encoder
-            .as_mut()
-            .unwrap()
-            .write_u32::<byteorder::LittleEndian>(ip.local.local_tick)?;
-        self.encoder
-            .as_mut()
-            .unwrap()
-            .write_u32::<byteorder::LittleEndian>(ip.local.remote_tick)?;
-
-        let (p1, p2) = if local_player_index == 0 {
-            (&ip.local, &ip.remote)
-        } else {
-            (&ip.remote, &ip.local)
-        };
-
-        self.encoder
-            .as_mut()
-            .unwrap()
-            .write_u16::<byteorder::LittleEndian>(p1.joyflags)?;
-        self.encoder.as_mut().unwrap().write_all(&p1.packet)?;
-        self.encoder
-            .as_mut()
-            .unwrap()
-            .write_u16::<byteorder::LittleEndian>(p2.joyflags)?;
-        self.encoder.as_mut().unwrap().write_all(&p2.packet)?;
+        let num_inputs = self.num_inputs;
+        let chunk = self.chunk.get_or_insert_with(|| PendingChunk {
+            first_input: num_inputs,
+            first_tick: ip.local.local_tick,
+            num_inputs: 0,
+            keyframe: None,
+            buf: vec![],
+        });
+        write_input_pair(&mut chunk.buf, local_player_index, ip)?;
+        chunk.num_inputs += 1;
+        self.chain.push(local_player_index, ip);
 
         self.num_inputs += 1;
+        self.inputs_since_keyframe += 1;
+
+        if chunk.num_inputs >= CHUNK_SIZE {
+            self.flush_chunk()?;
+        }
+        Ok(())
+    }
+
+    pub fn local_player_index(&self) -> u8 {
+        self.local_player_index
+    }
+
+    pub fn num_inputs(&self) -> u32 {
+        self.num_inputs
+    }
+
+    /// The hash chain over every input pair written so far.
+    pub fn chain_hash(&self) -> [u8; 32] {
+        self.chain.hash()
+    }
+
+    /// Writes the round's connection statistics. This should be done once, right before finishing.
+    pub fn write_stats(&mut self, stats: &Stats) -> std::io::Result<()> {
+        self.flush_chunk()?;
+        let payload = serde_json::to_vec(stats).map_err(|e| invalid_data(&e.to_string()))?;
+        self.write_section(section::STATS, &payload)?;
+        Ok(())
+    }
+
+    /// Writes down that a player left the match partway through the round. This should be done once, right before finishing.
+    pub fn write_goodbye(&mut self, goodbye: &Goodbye) -> std::io::Result<()> {
+        self.flush_chunk()?;
+        let payload = serde_json::to_vec(goodbye).map_err(|e| invalid_data(&e.to_string()))?;
+        self.write_section(section::GOODBYE, &payload)?;
         Ok(())
     }
 
     pub fn finish(mut self) -> std::io::Result<Box<dyn WriteSeek + Send>> {
-        let mut w = self.encoder.take().unwrap().finish()?;
-        w.seek(std::io::SeekFrom::Start((HEADER.len() + 1) as u64))?;
+        self.flush_chunk()?;
+
+        let mut index = Vec::with_capacity(self.chunks.len() * CHUNK_INFO_SIZE);
+        for chunk in self.chunks.iter() {
+            index.write_u64::<byteorder::LittleEndian>(chunk.offset)?;
+            index.write_u32::<byteorder::LittleEndian>(chunk.first_input)?;
+            index.write_u32::<byteorder::LittleEndian>(chunk.first_tick)?;
+            index.write_u32::<byteorder::LittleEndian>(chunk.num_inputs)?;
+            index.write_u8(chunk.has_keyframe as u8)?;
+        }
+        let index_offset = self.write_section(section::INDEX, &index)?;
+
+        let mut w = self.writer.take().unwrap();
+        w.seek(std::io::SeekFrom::Start(NUM_INPUTS_OFFSET))?;
         w.write_u32::<byteorder::LittleEndian>(self.num_inputs)?;
+        w.seek(std::io::SeekFrom::Start(INDEX_OFFSET_OFFSET))?;
+        w.write_u64::<byteorder::LittleEndian>(index_offset)?;
+        w.flush()?;
         Ok(w)
     }
 }
 
+/// Appends both players' signatures to a finished replay.
+///
+/// The signatures only exist once both sides have finished the round, so they go after the index rather than before it.
+pub fn append_signature(
+    w: &mut (dyn WriteSeek + Send),
+    footer: &signing::Footer,
+) -> std::io::Result<()> {
+    let mut payload = vec![];
+    footer.encode(&mut payload)?;
+    w.seek(std::io::SeekFrom::End(0))?;
+    w.write_u8(section::SIGNATURE)?;
+    w.write_u32::<byteorder::LittleEndian>(payload.len() as u32)?;
+    w.write_all(&payload)?;
+    w.flush()?;
+    Ok(())
+}
+
 impl Drop for Writer {
     fn drop(&mut self) {
-        if let Some(encoder) = self.encoder.take() {
+        if self.writer.is_some() {
             log::info!("writer was not finished before drop, this replay will be incomplete!");
-            encoder.finish().expect("finish");
+            self.flush_chunk().expect("flush chunk");
         }
     }
 }
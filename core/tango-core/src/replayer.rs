@@ -1,20 +1,27 @@
 use crate::battle;
 use crate::hooks;
 use crate::input;
+use crate::patch;
 
 pub struct InnerState {
     current_tick: u32,
     local_player_index: u8,
     input_pairs: std::collections::VecDeque<input::Pair<input::PartialInput, input::PartialInput>>,
     output_pairs: Vec<input::Pair<input::Input, input::Input>>,
-    apply_shadow_input: Box<
-        dyn FnMut(input::Pair<input::Input, input::PartialInput>) -> anyhow::Result<Vec<u8>>
-            + Sync
-            + Send,
+    apply_shadow_input: Option<
+        Box<
+            dyn FnMut(input::Pair<input::Input, input::PartialInput>) -> anyhow::Result<Vec<u8>>
+                + Sync
+                + Send,
+        >,
     >,
+    committed_remote_packets: std::collections::VecDeque<Vec<u8>>,
     local_packet: Option<input::Packet>,
     commit_tick: u32,
     committed_state: Option<battle::CommittedState>,
+    /// If set, checksums are taken of every state due one after this tick, up to and including the commit tick.
+    checksum_after: Option<u32>,
+    state_checksums: Vec<(u32, [u8; 32])>,
     dirty_tick: u32,
     dirty_state: Option<battle::CommittedState>,
     round_result: Option<RoundResult>,
@@ -32,6 +39,11 @@ impl InnerState {
         self.commit_tick
     }
 
+    /// Moves the tick at which the next committed state will be saved, e.g. to capture a state at every tick.
+    pub fn set_commit_tick(&mut self, tick: u32) {
+        self.commit_tick = tick;
+    }
+
     pub fn set_round_result(&mut self, result: BattleResult) {
         self.round_result = Some(RoundResult {
             tick: self.current_tick,
@@ -58,6 +70,25 @@ impl InnerState {
         self.committed_state.take()
     }
 
+    /// Whether the state at the current tick is committed and due a checksum to be sent to the opponent.
+    ///
+    /// The commit tick can move by more than one tick at a time, so this catches every due tick it moved past, not just the commit tick itself.
+    pub fn wants_state_checksum(&self) -> bool {
+        match self.checksum_after {
+            Some(checksum_after) => {
+                self.current_tick > checksum_after
+                    && self.current_tick <= self.commit_tick
+                    && self.current_tick % battle::STATE_CHECKSUM_INTERVAL == 0
+            }
+            None => false,
+        }
+    }
+
+    pub fn add_state_checksum(&mut self, state: &mgba::state::State) {
+        self.state_checksums
+            .push((self.current_tick, battle::wram_checksum(state)));
+    }
+
     pub fn dirty_tick(&self) -> u32 {
         self.dirty_tick
     }
@@ -93,7 +124,16 @@ impl InnerState {
         &mut self,
         input: input::Pair<input::Input, input::PartialInput>,
     ) -> anyhow::Result<Vec<u8>> {
-        let remote_packet = (self.apply_shadow_input)(input.clone())?;
+        let remote_packet = match self.apply_shadow_input.as_mut() {
+            Some(apply_shadow_input) => apply_shadow_input(input.clone())?,
+            None => {
+                if let Some(remote_packet) = self.committed_remote_packets.pop_front() {
+                    remote_packet
+                } else {
+                    anyhow::bail!("no more committed inputs");
+                }
+            }
+        };
         self.output_pairs.push(input::Pair {
             local: input.local,
             remote: input.remote.with_packet(remote_packet.clone()),
@@ -142,6 +182,49 @@ impl InnerState {
         self.round_result
     }
 
+    /// Appends an already committed input pair, e.g. one that was received after the replayer was started.
+    pub fn push_input_pair(&mut self, ip: input::Pair<input::Input, input::Input>) {
+        self.input_pairs.push_back(input::Pair {
+            local: input::PartialInput {
+                local_tick: ip.local.local_tick,
+                remote_tick: ip.local.remote_tick,
+                joyflags: ip.local.joyflags,
+            },
+            remote: input::PartialInput {
+                local_tick: ip.remote.local_tick,
+                remote_tick: ip.remote.remote_tick,
+                joyflags: ip.remote.joyflags,
+            },
+        });
+        self.committed_remote_packets.push_back(ip.remote.packet);
+    }
+
+    /// Puts the replayer back at the tick a committed state was saved at, so playback can continue from there once the state is loaded into the core.
+    ///
+    /// `input_pairs` must start at that tick.
+    pub fn rewind(
+        &mut self,
+        committed_state: &battle::CommittedState,
+        input_pairs: Vec<input::Pair<input::Input, input::Input>>,
+    ) {
+        self.current_tick = committed_state.tick;
+        self.local_packet = Some(input::Packet {
+            tick: committed_state.tick,
+            packet: committed_state.packet.clone(),
+        });
+        self.input_pairs.clear();
+        self.committed_remote_packets.clear();
+        self.output_pairs.clear();
+        self.committed_state = None;
+        self.dirty_state = None;
+        self.round_result = None;
+        self.phase = RoundPhase::InProgress;
+        self.error = None;
+        for ip in input_pairs {
+            self.push_input_pair(ip);
+        }
+    }
+
     pub fn input_pairs_left(&self) -> usize {
         self.input_pairs.len()
     }
@@ -158,6 +241,8 @@ impl InnerState {
 pub struct FastforwardResult {
     pub committed_state: battle::CommittedState,
     pub dirty_state: battle::CommittedState,
+    /// Checksums of the committed states at every tick a checksum was due, in tick order.
+    pub state_checksums: Vec<(u32, [u8; 32])>,
     pub round_result: Option<RoundResult>,
     pub output_pairs: Vec<input::Pair<input::Input, input::Input>>,
 }
@@ -170,6 +255,17 @@ pub enum BattleResult {
     Win = 1,
 }
 
+impl BattleResult {
+    pub fn from_i8(v: i8) -> Option<Self> {
+        match v {
+            -1 => Some(BattleResult::Draw),
+            0 => Some(BattleResult::Loss),
+            1 => Some(BattleResult::Win),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq)]
 enum RoundPhase {
     InProgress,
@@ -183,6 +279,87 @@ pub struct RoundResult {
     pub result: BattleResult,
 }
 
+/// A replay being played back frame by frame on a fresh core, starting from its local state.
+pub struct Playback {
+    core: mgba::core::Core,
+    state: State,
+}
+
+impl Playback {
+    pub fn new(
+        rom: &[u8],
+        patch: Option<&patch::Patch>,
+        replay: &crate::replay::Replay,
+    ) -> anyhow::Result<Self> {
+        let mut core = mgba::core::Core::new_gba("tango_core")?;
+        let vf = mgba::vfile::VFile::open_memory(rom);
+        core.as_mut().load_rom(vf)?;
+        if let Some(patch) = patch {
+            patch.apply(core.as_mut())?;
+        }
+        core.as_mut().reset();
+
+        let state = State::new(
+            replay.local_player_index,
+            replay.input_pairs.clone(),
+            0,
+            Box::new(|| {}),
+        );
+        let hooks = hooks::get(core.as_mut()).ok_or_else(|| anyhow::anyhow!("unsupported game"))?;
+        hooks.patch(core.as_mut());
+        {
+            let mut traps = hooks.common_traps();
+            traps.extend(hooks.replayer_traps(state.clone()));
+            core.set_traps(traps);
+        }
+        core.as_mut().load_state(
+            replay
+                .local_state
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("replay has no local state"))?,
+        )?;
+
+        Ok(Self { core, state })
+    }
+
+    /// Runs one frame, returning false without running anything once the inputs have run out or the round has ended.
+    pub fn step(&mut self) -> anyhow::Result<bool> {
+        {
+            let state = self.state.lock_inner();
+            if state.input_pairs_left() == 0 || state.is_round_ended() {
+                return Ok(false);
+            }
+        }
+
+        self.core.as_mut().run_frame();
+
+        if let Some(err) = self.state.lock_inner().take_error() {
+            return Err(err);
+        }
+        Ok(true)
+    }
+
+    pub fn core_mut(&mut self) -> mgba::core::CoreMutRef {
+        self.core.as_mut()
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+}
+
+/// Plays a replay through to the end on a fresh core and returns how the round ended, if it did.
+pub fn evaluate(
+    rom: &[u8],
+    patch: Option<&patch::Patch>,
+    replay: &crate::replay::Replay,
+) -> anyhow::Result<Option<RoundResult>> {
+    let mut playback = Playback::new(rom, patch, replay)?;
+    while playback.step()? {}
+    let round_result = playback.state().lock_inner().round_result();
+    Ok(round_result)
+}
+
 pub struct Fastforwarder {
     core: mgba::core::Core,
     state: State,
@@ -204,49 +381,35 @@ impl State {
             tick: ip.local.local_tick,
             packet: ip.local.packet.clone(),
         });
+        // A replay need not start at the beginning of the round, e.g. when seeking from a keyframe, so the game's tick counter starts at the first input's tick rather than 0. Replays of a whole round start at tick 0 either way, and the fastforwarder sets its own current tick.
+        let current_tick = input_pairs
+            .first()
+            .map(|ip| ip.local.local_tick)
+            .unwrap_or(0);
+        let mut inner_state = InnerState {
+            current_tick,
+            local_player_index,
+            input_pairs: std::collections::VecDeque::new(),
+            apply_shadow_input: None,
+            committed_remote_packets: std::collections::VecDeque::new(),
+            output_pairs: vec![],
+            local_packet,
+            commit_tick,
+            committed_state: None,
+            checksum_after: None,
+            state_checksums: vec![],
+            dirty_tick: 0,
+            dirty_state: None,
+            round_result: None,
+            phase: RoundPhase::InProgress,
+            error: None,
+            on_round_ended,
+        };
+        for ip in input_pairs {
+            inner_state.push_input_pair(ip);
+        }
         State(std::sync::Arc::new(parking_lot::Mutex::new(Some(
-            InnerState {
-                current_tick: 0,
-                local_player_index,
-                input_pairs: input_pairs
-                    .iter()
-                    .map(|ip| input::Pair {
-                        local: input::PartialInput {
-                            local_tick: ip.local.local_tick,
-                            remote_tick: ip.local.remote_tick,
-                            joyflags: ip.local.joyflags,
-                        },
-                        remote: input::PartialInput {
-                            local_tick: ip.remote.local_tick,
-                            remote_tick: ip.remote.remote_tick,
-                            joyflags: ip.remote.joyflags,
-                        },
-                    })
-                    .collect(),
-                apply_shadow_input: Box::new({
-                    let mut iq = input_pairs
-                        .into_iter()
-                        .collect::<std::collections::VecDeque<_>>();
-                    move |_| {
-                        let ip = if let Some(ip) = iq.pop_front() {
-                            ip
-                        } else {
-                            anyhow::bail!("no more committed inputs");
-                        };
-                        Ok(ip.remote.packet)
-                    }
-                }),
-                output_pairs: vec![],
-                local_packet,
-                commit_tick,
-                committed_state: None,
-                dirty_tick: 0,
-                dirty_state: None,
-                round_result: None,
-                phase: RoundPhase::InProgress,
-                error: None,
-                on_round_ended,
-            },
+            inner_state,
         ))))
     }
 
@@ -258,6 +421,7 @@ impl State {
 impl Fastforwarder {
     pub fn new(
         rom: &[u8],
+        patch: Option<&patch::Patch>,
         hooks: &'static Box<dyn hooks::Hooks + Send + Sync>,
         local_player_index: u8,
         opponent_nickname: &Option<String>,
@@ -265,6 +429,9 @@ impl Fastforwarder {
         let mut core = mgba::core::Core::new_gba("tango")?;
         let rom_vf = mgba::vfile::VFile::open_memory(rom);
         core.as_mut().load_rom(rom_vf)?;
+        if let Some(patch) = patch {
+            patch.apply(core.as_mut())?;
+        }
         hooks.patch(core.as_mut());
 
         let state = State(std::sync::Arc::new(parking_lot::Mutex::new(None)));
@@ -307,13 +474,16 @@ impl Fastforwarder {
             local_player_index: self.local_player_index,
             input_pairs: input_pairs.into_iter().collect(),
             output_pairs: vec![],
-            apply_shadow_input,
+            apply_shadow_input: Some(apply_shadow_input),
+            committed_remote_packets: std::collections::VecDeque::new(),
             local_packet: Some(input::Packet {
                 tick: current_tick,
                 packet: last_local_packet.to_vec(),
             }),
             commit_tick,
             committed_state: None,
+            checksum_after: Some(current_tick),
+            state_checksums: vec![],
             dirty_tick,
             dirty_state: None,
             round_result: None,
@@ -331,6 +501,7 @@ impl Fastforwarder {
                     return Ok(FastforwardResult {
                         committed_state: state.committed_state.expect("committed state"),
                         dirty_state: state.dirty_state.expect("dirty state"),
+                        state_checksums: state.state_checksums,
                         round_result: state.round_result,
                         output_pairs: state.output_pairs,
                     });
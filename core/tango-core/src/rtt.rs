@@ -0,0 +1,77 @@
+use crate::game;
+
+/// Input delay is never recommended outside of this range.
+pub const MIN_INPUT_DELAY: u32 = 2;
+pub const MAX_INPUT_DELAY: u32 = 10;
+
+/// Which percentile of round trip times to size the input delay for, such that the occasional spike does not blow up the delay but a consistently jittery connection does.
+const RECOMMENDATION_PERCENTILE: f32 = 0.9;
+
+/// Keeps a window of the most recent round trip times to the opponent.
+pub struct Tracker {
+    window_size: usize,
+    samples: std::collections::VecDeque<std::time::Duration>,
+}
+
+impl Tracker {
+    pub fn new(window_size: usize) -> Self {
+        Self {
+            window_size,
+            samples: std::collections::VecDeque::with_capacity(window_size),
+        }
+    }
+
+    pub fn add_sample(&mut self, rtt: std::time::Duration) {
+        if self.samples.len() >= self.window_size {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(rtt);
+    }
+
+    pub fn num_samples(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Returns the round trip time at the given percentile (0.0 to 1.0), using the nearest rank.
+    pub fn percentile(&self, p: f32) -> Option<std::time::Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let mut samples = self.samples.iter().cloned().collect::<Vec<_>>();
+        samples.sort();
+        let rank = ((p.clamp(0.0, 1.0) * samples.len() as f32).ceil() as usize).max(1);
+        Some(samples[rank - 1])
+    }
+
+    pub fn median(&self) -> Option<std::time::Duration> {
+        self.percentile(0.5)
+    }
+
+    /// Returns the mean difference between consecutive round trip times.
+    pub fn jitter(&self) -> Option<std::time::Duration> {
+        let diffs = self
+            .samples
+            .iter()
+            .zip(self.samples.iter().skip(1))
+            .map(|(x, y)| if y > x { *y - *x } else { *x - *y })
+            .collect::<Vec<std::time::Duration>>();
+        if diffs.is_empty() {
+            return None;
+        }
+        Some(diffs.iter().sum::<std::time::Duration>() / diffs.len() as u32)
+    }
+
+    /// Recommends an input delay, in frames, that covers the one-way latency to the opponent for most inputs.
+    pub fn recommended_input_delay(&self) -> Option<u32> {
+        let rtt = self.percentile(RECOMMENDATION_PERCENTILE)?;
+        let frames = (rtt.as_secs_f32() / 2.0 * game::EXPECTED_FPS).ceil() as u32;
+        Some(frames.clamp(MIN_INPUT_DELAY, MAX_INPUT_DELAY))
+    }
+}
+
+/// Agrees on a shared input delay from both sides' recommendations.
+///
+/// Both sides must come to the same result, so this is only ever decided by the two recommendations: if either side did not make one, the delays stay as they were.
+pub fn agree_input_delay(local: Option<u32>, remote: Option<u32>) -> Option<u32> {
+    Some(local?.max(remote?))
+}
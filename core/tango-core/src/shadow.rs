@@ -1,4 +1,4 @@
-use crate::{battle, hooks, input};
+use crate::{battle, hooks, input, patch};
 
 pub struct Round {
     current_tick: u32,
@@ -100,6 +100,7 @@ pub struct Shadow {
     core: mgba::core::Core,
     state: State,
     hooks: &'static Box<dyn hooks::Hooks + Send + Sync>,
+    wram_checksums: Vec<(u32, [u8; 32])>,
 }
 
 #[derive(Clone)]
@@ -179,6 +180,7 @@ impl State {
 impl Shadow {
     pub fn new(
         rom: &[u8],
+        patch: Option<&patch::Patch>,
         save_path: &std::path::Path,
         match_type: (u8, u8),
         is_offerer: bool,
@@ -188,6 +190,9 @@ impl Shadow {
         let mut core = mgba::core::Core::new_gba("tango")?;
         let rom_vf = mgba::vfile::VFile::open_memory(rom);
         core.as_mut().load_rom(rom_vf)?;
+        if let Some(patch) = patch {
+            patch.apply(core.as_mut())?;
+        }
 
         log::info!(
             "loaded shadow game: {} rev {}",
@@ -208,7 +213,12 @@ impl Shadow {
         core.set_traps(traps);
         core.as_mut().reset();
 
-        Ok(Shadow { core, hooks, state })
+        Ok(Shadow {
+            core,
+            hooks,
+            state,
+            wram_checksums: vec![],
+        })
     }
 
     pub fn advance_until_first_committed_state(&mut self) -> anyhow::Result<mgba::state::State> {
@@ -266,6 +276,11 @@ impl Shadow {
         }
     }
 
+    /// Takes the checksums of the remote's WRAM at every checksummed tick the shadow has reached since the last call.
+    pub fn take_wram_checksums(&mut self) -> Vec<(u32, [u8; 32])> {
+        std::mem::take(&mut self.wram_checksums)
+    }
+
     pub fn apply_input(
         &mut self,
         ip: input::Pair<input::Input, input::PartialInput>,
@@ -296,6 +311,13 @@ impl Shadow {
                 .as_mut()
                 .load_state(&applied_state.state)
                 .expect("load state");
+            // The applied state is saved on entering main_read_joyflags with the game's own tick counter at this tick, which is exactly where the opponent's replayer saves its committed state for the same tick. The only difference is the joyflags register, which isn't part of WRAM.
+            if applied_state.tick % battle::STATE_CHECKSUM_INTERVAL == 0 {
+                self.wram_checksums.push((
+                    applied_state.tick,
+                    battle::wram_checksum(&applied_state.state),
+                ));
+            }
             let mut round_state = self.state.lock_round_state();
             let round = round_state.round.as_mut().expect("round");
             round.current_tick = applied_state.tick;
@@ -0,0 +1,80 @@
+/// The height of the strip above each screen that the player's nickname is drawn in.
+pub const LABEL_HEIGHT: usize = 10;
+
+const SCREEN_WIDTH: usize = mgba::gba::SCREEN_WIDTH as usize;
+const SCREEN_HEIGHT: usize = mgba::gba::SCREEN_HEIGHT as usize;
+
+/// Lays two players' screens out next to each other, each with their nickname above it.
+pub struct SideBySide {
+    buf: Vec<u8>,
+}
+
+impl SideBySide {
+    pub fn new(left_nickname: &str, right_nickname: &str) -> anyhow::Result<Self> {
+        let mut sbs = Self {
+            buf: vec![0u8; Self::width() as usize * Self::height() as usize * 4],
+        };
+        for i in (0..sbs.buf.len()).step_by(4) {
+            sbs.buf[i + 3] = 0xff;
+        }
+
+        let ttf = sdl2::ttf::init().map_err(|e| anyhow::anyhow!("{}", e))?;
+        let font = ttf
+            .load_font_from_rwops(
+                sdl2::rwops::RWops::from_bytes(include_bytes!("fonts/04B_03__.TTF"))
+                    .map_err(|e| anyhow::anyhow!("{}", e))?,
+                8,
+            )
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+
+        for (i, nickname) in [left_nickname, right_nickname].into_iter().enumerate() {
+            // SDL_ttf refuses to render empty text.
+            if nickname.is_empty() {
+                continue;
+            }
+
+            let surface = font
+                .render(nickname)
+                .shaded(
+                    sdl2::pixels::Color::RGBA(255, 255, 255, 255),
+                    sdl2::pixels::Color::RGBA(0, 0, 0, 255),
+                )
+                .map_err(|e| anyhow::anyhow!("{}", e))?
+                .convert_format(sdl2::pixels::PixelFormatEnum::ABGR8888)
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+
+            let width = (surface.width() as usize).min(SCREEN_WIDTH - 2);
+            let height = (surface.height() as usize).min(LABEL_HEIGHT - 1);
+            let pitch = surface.pitch() as usize;
+            surface.with_lock(|pixels| {
+                for y in 0..height {
+                    let dst = ((1 + y) * Self::width() as usize + i * SCREEN_WIDTH + 1) * 4;
+                    sbs.buf[dst..dst + width * 4]
+                        .copy_from_slice(&pixels[y * pitch..y * pitch + width * 4]);
+                }
+            });
+        }
+
+        Ok(sbs)
+    }
+
+    pub fn width() -> u32 {
+        (SCREEN_WIDTH * 2) as u32
+    }
+
+    pub fn height() -> u32 {
+        (LABEL_HEIGHT + SCREEN_HEIGHT) as u32
+    }
+
+    /// Draws both screens, as RGBA, and returns the combined RGBA frame.
+    pub fn composite(&mut self, left: &[u8], right: &[u8]) -> &[u8] {
+        for (i, screen) in [left, right].into_iter().enumerate() {
+            for y in 0..SCREEN_HEIGHT {
+                let dst = ((LABEL_HEIGHT + y) * Self::width() as usize + i * SCREEN_WIDTH) * 4;
+                self.buf[dst..dst + SCREEN_WIDTH * 4]
+                    .copy_from_slice(&screen[y * SCREEN_WIDTH * 4..(y + 1) * SCREEN_WIDTH * 4]);
+            }
+        }
+        &self.buf
+    }
+}
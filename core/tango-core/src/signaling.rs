@@ -39,37 +39,75 @@ where
         .await?;
     log::info!("negotiation start sent");
 
+    // If we turn out to be the polite side, we roll back our offer: any candidates we gathered for it are useless to the other side, so we drop them until our answer is ready.
+    let mut discard_local_candidates = false;
+
+    // Remote candidates can only be applied once we have a remote description.
+    let mut pending_remote_candidates = vec![];
+
+    let mut signaling_open = true;
+
     loop {
         tokio::select! {
             signal_msg = event_rx.recv() => {
-                let _cand = if let Some(datachannel_wrapper::PeerConnectionEvent::IceCandidate(cand)) = signal_msg {
-                    cand
+                let signal_msg = if let Some(signal_msg) = signal_msg {
+                    signal_msg
                 } else {
-                    anyhow::bail!("ice candidate not received")
+                    anyhow::bail!("peer connection closed");
                 };
 
-                // TODO: Support trickle ICE.
-                // stream
-                //     .send(tokio_tungstenite::tungstenite::Message::Binary(
-                //         tango_protos::signaling::Packet {
-                //             which: Some(
-                //                 tango_protos::signaling::packet::Which::IceCandidate(
-                //                     tango_protos::signaling::packet::IceCandidate {
-                //                         candidate: cand.candidate,
-                //                         mid: cand.mid,
-                //                     },
-                //                 ),
-                //             ),
-                //         }
-                //         .encode_to_vec(),
-                // ))
-                //     .await?;
+                match signal_msg {
+                    datachannel_wrapper::PeerConnectionEvent::SessionDescription(_) => {
+                        discard_local_candidates = false;
+                    }
+                    datachannel_wrapper::PeerConnectionEvent::IceCandidate(cand) => {
+                        if discard_local_candidates || !signaling_open {
+                            continue;
+                        }
+                        stream
+                            .send(tokio_tungstenite::tungstenite::Message::Binary(
+                                tango_protos::signaling::Packet {
+                                    which: Some(
+                                        tango_protos::signaling::packet::Which::IceCandidate(
+                                            tango_protos::signaling::packet::IceCandidate {
+                                                candidate: cand.candidate,
+                                                mid: cand.mid,
+                                            },
+                                        ),
+                                    ),
+                                }
+                                .encode_to_vec(),
+                            ))
+                            .await?;
+                    }
+                    datachannel_wrapper::PeerConnectionEvent::ConnectionStateChange(c) => match c {
+                        datachannel_wrapper::ConnectionState::Connected => {
+                            break;
+                        }
+                        datachannel_wrapper::ConnectionState::Disconnected => {
+                            anyhow::bail!("peer connection unexpectedly disconnected");
+                        }
+                        datachannel_wrapper::ConnectionState::Failed => {
+                            anyhow::bail!("peer connection failed");
+                        }
+                        datachannel_wrapper::ConnectionState::Closed => {
+                            anyhow::bail!("peer connection unexpectedly closed");
+                        }
+                        _ => {}
+                    },
+                    _ => {}
+                }
             }
-            ws_msg = stream.try_next() => {
+            ws_msg = stream.try_next(), if signaling_open => {
                 let raw = if let Some(raw) = ws_msg? {
                     raw
                 } else {
-                    anyhow::bail!("stream ended early");
+                    if peer_conn.remote_description().is_none() {
+                        anyhow::bail!("stream ended early");
+                    }
+                    // The other side may hang up on signaling as soon as it's connected: we just need to wait for our side of the connection to come up too.
+                    signaling_open = false;
+                    continue;
                 };
 
                 let packet = if let tokio_tungstenite::tungstenite::Message::Binary(d) = raw {
@@ -85,6 +123,7 @@ where
                     Some(tango_protos::signaling::packet::Which::Offer(offer)) => {
                         log::info!("received an offer, this is the polite side. rolling back our local description and switching to answer");
 
+                        discard_local_candidates = true;
                         peer_conn.set_local_description(datachannel_wrapper::SdpType::Rollback)?;
                         peer_conn.set_remote_description(datachannel_wrapper::SessionDescription {
                             sdp_type: datachannel_wrapper::SdpType::Offer,
@@ -103,7 +142,6 @@ where
                             ))
                             .await?;
                         log::info!("sent answer to impolite side");
-                        break;
                     }
                     Some(tango_protos::signaling::packet::Which::Answer(answer)) => {
                         log::info!("received an answer, this is the impolite side");
@@ -112,43 +150,33 @@ where
                             sdp_type: datachannel_wrapper::SdpType::Answer,
                             sdp: datachannel_wrapper::sdp::parse_sdp(&answer.sdp, false)?,
                         })?;
-                        break;
                     }
-                    Some(tango_protos::signaling::packet::Which::IceCandidate(_ice_candidate)) => {
-                        anyhow::bail!("ice candidates not supported");
+                    Some(tango_protos::signaling::packet::Which::Error(error)) => {
+                        anyhow::bail!("signaling server error: {:?}", error.reason());
+                    }
+                    Some(tango_protos::signaling::packet::Which::IceCandidate(ice_candidate)) => {
+                        pending_remote_candidates.push(datachannel_wrapper::IceCandidate {
+                            candidate: ice_candidate.candidate,
+                            mid: ice_candidate.mid,
+                        });
                     }
                     p => {
                         anyhow::bail!("unexpected packet: {:?}", p);
                     }
                 }
+
+                if peer_conn.remote_description().is_some() {
+                    for cand in pending_remote_candidates.drain(..) {
+                        log::debug!("adding remote candidate: {}", cand.candidate);
+                        peer_conn.add_remote_candidate(cand)?;
+                    }
+                }
             }
         };
     }
 
-    stream.close(None).await?;
-
-    loop {
-        match event_rx.recv().await {
-            Some(signal) => match signal {
-                datachannel_wrapper::PeerConnectionEvent::ConnectionStateChange(c) => match c {
-                    datachannel_wrapper::ConnectionState::Connected => {
-                        break;
-                    }
-                    datachannel_wrapper::ConnectionState::Disconnected => {
-                        anyhow::bail!("peer connection unexpectedly disconnected");
-                    }
-                    datachannel_wrapper::ConnectionState::Failed => {
-                        anyhow::bail!("peer connection failed");
-                    }
-                    datachannel_wrapper::ConnectionState::Closed => {
-                        anyhow::bail!("peer connection unexpectedly closed");
-                    }
-                    _ => {}
-                },
-                _ => {}
-            },
-            None => unreachable!(),
-        }
+    if signaling_open {
+        stream.close(None).await?;
     }
 
     Ok(())
@@ -0,0 +1,328 @@
+use crate::{battle, input, replay};
+use byteorder::ReadBytesExt;
+use byteorder::WriteBytesExt;
+use prost::Message;
+use rand::RngCore;
+use sha3::Digest;
+
+/// Prefixed to everything that gets signed, so a replay signature can't be passed off as a signature over anything else.
+const DOMAIN: &[u8] = b"tango replay signature v2";
+
+/// Prefixed to resume challenges before they're signed, for the same reason as `DOMAIN`.
+const RESUME_DOMAIN: &[u8] = b"tango resume challenge v1";
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidPublicKey,
+    InvalidSignature(u8),
+    ChainMismatch,
+    NumInputsMismatch { expected: u32, actual: u32 },
+    StateMismatch,
+    InvalidMetadata,
+    MetadataMismatch,
+    InvalidResumeSignature,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidPublicKey => write!(f, "invalid public key"),
+            Error::InvalidSignature(player_index) => {
+                write!(f, "invalid signature from p{}", player_index + 1)
+            }
+            Error::ChainMismatch => write!(f, "input pairs do not match the signed hash chain"),
+            Error::NumInputsMismatch { expected, actual } => write!(
+                f,
+                "expected {} signed input pairs, found {}",
+                expected, actual
+            ),
+            Error::StateMismatch => write!(f, "initial states do not match the signed hashes"),
+            Error::InvalidMetadata => write!(f, "invalid metadata"),
+            Error::MetadataMismatch => write!(f, "metadata does not match the signed hash"),
+            Error::InvalidResumeSignature => {
+                write!(f, "resume challenge was not signed by the opponent")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Loads the keypair at the given path, or generates one and saves it there if there isn't one yet.
+///
+/// Without a path, a throwaway keypair is generated: replays will still be signed, but the signatures can't be tied to anyone.
+pub fn load_or_generate_keypair(
+    path: Option<&std::path::Path>,
+) -> anyhow::Result<ed25519_dalek::Keypair> {
+    let secret = match path {
+        Some(path) if path.exists() => ed25519_dalek::SecretKey::from_bytes(&std::fs::read(path)?)
+            .map_err(|e| anyhow::anyhow!("invalid signing key {}: {}", path.display(), e))?,
+        path => {
+            let mut raw = [0u8; ed25519_dalek::SECRET_KEY_LENGTH];
+            rand::rngs::OsRng.fill_bytes(&mut raw);
+            let secret = ed25519_dalek::SecretKey::from_bytes(&raw).expect("secret key");
+            if let Some(path) = path {
+                log::info!("generated new signing key: {}", path.display());
+                let mut options = std::fs::OpenOptions::new();
+                options.write(true).create_new(true);
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::OpenOptionsExt;
+                    options.mode(0o600);
+                }
+                std::io::Write::write_all(&mut options.open(path)?, secret.as_bytes())?;
+            }
+            secret
+        }
+    };
+    let public = ed25519_dalek::PublicKey::from(&secret);
+    Ok(ed25519_dalek::Keypair { secret, public })
+}
+
+/// A running hash over committed input pairs.
+///
+/// Only what both sides agree on goes into the hash, so both peers arrive at the same hash for the same round.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HashChain([u8; 32]);
+
+impl HashChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, local_player_index: u8, ip: &input::Pair<input::Input, input::Input>) {
+        let (p1, p2) = if local_player_index == 0 {
+            (&ip.local, &ip.remote)
+        } else {
+            (&ip.remote, &ip.local)
+        };
+
+        let mut hasher = sha3::Sha3_256::new();
+        hasher.update(self.0);
+        hasher.update(ip.local.local_tick.to_le_bytes());
+        hasher.update(p1.joyflags.to_le_bytes());
+        hasher.update(&p1.packet);
+        hasher.update(p2.joyflags.to_le_bytes());
+        hasher.update(&p2.packet);
+        self.0 = hasher.finalize().into();
+    }
+
+    pub fn hash(&self) -> [u8; 32] {
+        self.0
+    }
+}
+
+/// Hashes the state a round starts from.
+///
+/// Only WRAM goes into the hash, as with the checksums compared during the round: our shadow's copy of the opponent's state agrees with theirs in WRAM, but not in every register.
+pub fn hash_state(state: &mgba::state::State) -> [u8; 32] {
+    battle::wram_checksum(state)
+}
+
+/// Hashes the parts of a replay's metadata that both players' copies agree on, with the sides in P1, P2 order.
+///
+/// The timestamp is left out since each player stamps their own copy, as is any note of a repair.
+pub fn hash_metadata(
+    local_player_index: u8,
+    metadata: &[u8],
+) -> Result<[u8; 32], prost::DecodeError> {
+    let metadata = tango_protos::replay::ReplayMetadata::decode(metadata)?;
+    let (p1_side, p2_side) = if local_player_index == 0 {
+        (metadata.local_side, metadata.remote_side)
+    } else {
+        (metadata.remote_side, metadata.local_side)
+    };
+
+    let mut hasher = sha3::Sha3_256::new();
+    for field in [
+        metadata.link_code.into_bytes(),
+        p1_side.map(|side| side.encode_to_vec()).unwrap_or_default(),
+        p2_side.map(|side| side.encode_to_vec()).unwrap_or_default(),
+    ] {
+        hasher.update((field.len() as u32).to_le_bytes());
+        hasher.update(&field);
+    }
+    Ok(hasher.finalize().into())
+}
+
+/// What both players sign at the end of a round.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Statement {
+    pub round_number: u8,
+    pub num_inputs: u32,
+    pub chain_hash: [u8; 32],
+
+    /// The hashes of P1's and P2's initial states, in that order, as given by `hash_state`.
+    pub state_hashes: [[u8; 32]; 2],
+
+    /// As given by `hash_metadata`.
+    pub metadata_hash: [u8; 32],
+
+    /// The public keys of P1 and P2, in that order.
+    pub public_keys: [[u8; 32]; 2],
+}
+
+impl Statement {
+    fn message(&self) -> Vec<u8> {
+        let mut buf = DOMAIN.to_vec();
+        buf.push(self.round_number);
+        buf.extend(self.num_inputs.to_le_bytes());
+        buf.extend(self.chain_hash);
+        buf.extend(self.state_hashes[0]);
+        buf.extend(self.state_hashes[1]);
+        buf.extend(self.metadata_hash);
+        buf.extend(self.public_keys[0]);
+        buf.extend(self.public_keys[1]);
+        buf
+    }
+
+    pub fn sign(&self, keypair: &ed25519_dalek::Keypair) -> [u8; 64] {
+        use ed25519_dalek::Signer;
+        keypair.sign(&self.message()).to_bytes()
+    }
+
+    /// Checks that the given player's key signed this statement.
+    pub fn verify(&self, player_index: u8, signature: &[u8; 64]) -> Result<(), Error> {
+        use ed25519_dalek::Verifier;
+        let public_key =
+            ed25519_dalek::PublicKey::from_bytes(&self.public_keys[player_index as usize])
+                .map_err(|_| Error::InvalidPublicKey)?;
+        let signature = ed25519_dalek::Signature::from_bytes(signature)
+            .map_err(|_| Error::InvalidSignature(player_index))?;
+        public_key
+            .verify(&self.message(), &signature)
+            .map_err(|_| Error::InvalidSignature(player_index))
+    }
+}
+
+/// Signs the challenge the opponent sent when resuming a match.
+pub fn sign_resume_challenge(keypair: &ed25519_dalek::Keypair, challenge: &[u8; 32]) -> [u8; 64] {
+    use ed25519_dalek::Signer;
+    let mut message = RESUME_DOMAIN.to_vec();
+    message.extend(challenge);
+    keypair.sign(&message).to_bytes()
+}
+
+/// Checks that the challenge we sent when resuming a match was signed with the opponent's key.
+pub fn verify_resume_challenge(
+    public_key: &[u8; 32],
+    challenge: &[u8; 32],
+    signature: &[u8],
+) -> Result<(), Error> {
+    use ed25519_dalek::Verifier;
+    let public_key =
+        ed25519_dalek::PublicKey::from_bytes(public_key).map_err(|_| Error::InvalidPublicKey)?;
+    let signature = ed25519_dalek::Signature::from_bytes(signature)
+        .map_err(|_| Error::InvalidResumeSignature)?;
+    let mut message = RESUME_DOMAIN.to_vec();
+    message.extend(challenge);
+    public_key
+        .verify(&message, &signature)
+        .map_err(|_| Error::InvalidResumeSignature)
+}
+
+/// The statement about a round along with both players' signatures over it, as written at the end of a replay.
+#[derive(Clone, Debug)]
+pub struct Footer {
+    pub statement: Statement,
+
+    /// The signatures of P1 and P2, in that order.
+    pub signatures: [[u8; 64]; 2],
+}
+
+impl Footer {
+    /// Checks both signatures, then checks that the replay's metadata, initial states and input pairs are exactly the ones that were signed.
+    pub fn verify(&self, replay: &replay::Replay) -> Result<(), Error> {
+        self.statement.verify(0, &self.signatures[0])?;
+        self.statement.verify(1, &self.signatures[1])?;
+
+        if hash_metadata(replay.local_player_index, &replay.metadata)
+            .map_err(|_| Error::InvalidMetadata)?
+            != self.statement.metadata_hash
+        {
+            return Err(Error::MetadataMismatch);
+        }
+
+        let (local_state, remote_state) = match (&replay.local_state, &replay.remote_state) {
+            (Some(local_state), Some(remote_state)) => (local_state, remote_state),
+            _ => {
+                return Err(Error::StateMismatch);
+            }
+        };
+        let mut state_hashes = [hash_state(local_state), hash_state(remote_state)];
+        if replay.local_player_index != 0 {
+            state_hashes.reverse();
+        }
+        if state_hashes != self.statement.state_hashes {
+            return Err(Error::StateMismatch);
+        }
+
+        let input_pairs = &replay.input_pairs;
+        if input_pairs.len() != self.statement.num_inputs as usize {
+            return Err(Error::NumInputsMismatch {
+                expected: self.statement.num_inputs,
+                actual: input_pairs.len() as u32,
+            });
+        }
+
+        let mut chain = HashChain::new();
+        for ip in input_pairs {
+            chain.push(replay.local_player_index, ip);
+        }
+        if chain.hash() != self.statement.chain_hash {
+            return Err(Error::ChainMismatch);
+        }
+
+        Ok(())
+    }
+
+    pub fn encode(&self, mut w: impl std::io::Write) -> std::io::Result<()> {
+        w.write_u8(self.statement.round_number)?;
+        w.write_u32::<byteorder::LittleEndian>(self.statement.num_inputs)?;
+        w.write_all(&self.statement.chain_hash)?;
+        for state_hash in self.statement.state_hashes.iter() {
+            w.write_all(state_hash)?;
+        }
+        w.write_all(&self.statement.metadata_hash)?;
+        for (public_key, signature) in self
+            .statement
+            .public_keys
+            .iter()
+            .zip(self.signatures.iter())
+        {
+            w.write_all(public_key)?;
+            w.write_all(signature)?;
+        }
+        Ok(())
+    }
+
+    pub fn decode(mut r: impl std::io::Read) -> std::io::Result<Self> {
+        let round_number = r.read_u8()?;
+        let num_inputs = r.read_u32::<byteorder::LittleEndian>()?;
+        let mut chain_hash = [0u8; 32];
+        r.read_exact(&mut chain_hash)?;
+        let mut state_hashes = [[0u8; 32]; 2];
+        for state_hash in state_hashes.iter_mut() {
+            r.read_exact(state_hash)?;
+        }
+        let mut metadata_hash = [0u8; 32];
+        r.read_exact(&mut metadata_hash)?;
+        let mut public_keys = [[0u8; 32]; 2];
+        let mut signatures = [[0u8; 64]; 2];
+        for (public_key, signature) in public_keys.iter_mut().zip(signatures.iter_mut()) {
+            r.read_exact(public_key)?;
+            r.read_exact(signature)?;
+        }
+        Ok(Self {
+            statement: Statement {
+                round_number,
+                num_inputs,
+                chain_hash,
+                state_hashes,
+                metadata_hash,
+                public_keys,
+            },
+            signatures,
+        })
+    }
+}
@@ -0,0 +1,256 @@
+use bincode::Options;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+
+use crate::input;
+
+pub const VERSION: u8 = 0x01;
+
+const MAX_FRAME_SIZE: u32 = 4 * 1024 * 1024;
+
+/// How many packets a spectator may fall behind by before it's disconnected. Inputs are published every tick, so this is about 10 seconds' worth.
+const MAX_SUBSCRIBER_LAG: usize = 10 * 60;
+
+lazy_static! {
+    static ref BINCODE_OPTIONS: bincode::config::WithOtherLimit<
+        bincode::config::WithOtherIntEncoding<
+            bincode::config::DefaultOptions,
+            bincode::config::VarintEncoding,
+        >,
+        bincode::config::Bounded,
+    > = bincode::DefaultOptions::new()
+        .with_varint_encoding()
+        .with_limit(MAX_FRAME_SIZE as u64);
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub enum Packet {
+    Hello(Hello),
+    RoundStart(RoundStart),
+    Input(Input),
+    RoundEnd(RoundEnd),
+}
+
+impl Packet {
+    pub fn serialize(&self) -> bincode::Result<Vec<u8>> {
+        BINCODE_OPTIONS.serialize(self)
+    }
+
+    pub fn deserialize(d: &[u8]) -> bincode::Result<Self> {
+        BINCODE_OPTIONS.deserialize(d)
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Hello {
+    pub protocol_version: u8,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct RoundStart {
+    pub round_number: u8,
+    pub local_player_index: u8,
+    pub metadata: Vec<u8>,
+    pub local_state: Vec<u8>,
+    pub remote_state: Vec<u8>,
+}
+
+impl std::fmt::Debug for RoundStart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RoundStart")
+            .field("round_number", &self.round_number)
+            .field("local_player_index", &self.local_player_index)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Input {
+    pub round_number: u8,
+    pub local_tick: u32,
+    pub remote_tick: u32,
+    pub local_joyflags: u16,
+    pub local_packet: Vec<u8>,
+    pub remote_joyflags: u16,
+    pub remote_packet: Vec<u8>,
+}
+
+impl Input {
+    pub fn new(round_number: u8, ip: &input::Pair<input::Input, input::Input>) -> Self {
+        Input {
+            round_number,
+            local_tick: ip.local.local_tick,
+            remote_tick: ip.local.remote_tick,
+            local_joyflags: ip.local.joyflags,
+            local_packet: ip.local.packet.clone(),
+            remote_joyflags: ip.remote.joyflags,
+            remote_packet: ip.remote.packet.clone(),
+        }
+    }
+
+    pub fn into_pair(self) -> input::Pair<input::Input, input::Input> {
+        input::Pair {
+            local: input::Input {
+                local_tick: self.local_tick,
+                remote_tick: self.remote_tick,
+                joyflags: self.local_joyflags,
+                packet: self.local_packet,
+            },
+            remote: input::Input {
+                local_tick: self.local_tick,
+                remote_tick: self.local_tick,
+                joyflags: self.remote_joyflags,
+                packet: self.remote_packet,
+            },
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct RoundEnd {
+    pub round_number: u8,
+}
+
+async fn write_frame(
+    w: &mut (impl tokio::io::AsyncWrite + Unpin),
+    buf: &[u8],
+) -> std::io::Result<()> {
+    w.write_u32_le(buf.len() as u32).await?;
+    w.write_all(buf).await?;
+    Ok(())
+}
+
+struct BroadcasterInner {
+    // The backlog contains everything since the start of the current round, such that spectators that join late can catch up.
+    backlog: Vec<std::sync::Arc<Vec<u8>>>,
+    subscribers: Vec<tokio::sync::mpsc::Sender<std::sync::Arc<Vec<u8>>>>,
+}
+
+/// Fans out committed match data to all connected spectators.
+pub struct Broadcaster {
+    inner: parking_lot::Mutex<BroadcasterInner>,
+}
+
+impl Broadcaster {
+    pub fn new() -> Self {
+        Self {
+            inner: parking_lot::Mutex::new(BroadcasterInner {
+                backlog: vec![],
+                subscribers: vec![],
+            }),
+        }
+    }
+
+    pub fn publish(&self, packet: Packet) {
+        let buf = std::sync::Arc::new(packet.serialize().expect("serialize spectator packet"));
+        let mut inner = self.inner.lock();
+        if let Packet::RoundStart(_) = packet {
+            inner.backlog.clear();
+        }
+        inner.backlog.push(buf.clone());
+        inner
+            .subscribers
+            .retain(|subscriber| match subscriber.try_send(buf.clone()) {
+                Ok(()) => true,
+                Err(tokio::sync::mpsc::error::TrySendError::Full(_)) => {
+                    // Dropping the sender ends the spectator's connection once it has caught up on what's already queued.
+                    log::warn!("spectator fell too far behind, disconnecting");
+                    false
+                }
+                Err(tokio::sync::mpsc::error::TrySendError::Closed(_)) => false,
+            });
+    }
+
+    fn subscribe(
+        &self,
+    ) -> (
+        Vec<std::sync::Arc<Vec<u8>>>,
+        tokio::sync::mpsc::Receiver<std::sync::Arc<Vec<u8>>>,
+    ) {
+        let (tx, rx) = tokio::sync::mpsc::channel(MAX_SUBSCRIBER_LAG);
+        let mut inner = self.inner.lock();
+        inner.subscribers.push(tx);
+        (inner.backlog.clone(), rx)
+    }
+
+    async fn serve_one(&self, mut stream: tokio::net::TcpStream) -> anyhow::Result<()> {
+        stream.set_nodelay(true)?;
+        let (backlog, mut rx) = self.subscribe();
+        write_frame(
+            &mut stream,
+            &Packet::Hello(Hello {
+                protocol_version: VERSION,
+            })
+            .serialize()?,
+        )
+        .await?;
+        for buf in backlog {
+            write_frame(&mut stream, &buf).await?;
+        }
+        while let Some(buf) = rx.recv().await {
+            write_frame(&mut stream, &buf).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn serve(
+        self: std::sync::Arc<Self>,
+        listener: tokio::net::TcpListener,
+    ) -> anyhow::Result<()> {
+        log::info!("spectator server listening on {}", listener.local_addr()?);
+        loop {
+            let (stream, addr) = listener.accept().await?;
+            log::info!("spectator connected: {}", addr);
+            let broadcaster = self.clone();
+            tokio::task::spawn(async move {
+                if let Err(e) = broadcaster.serve_one(stream).await {
+                    log::info!("spectator {} disconnected: {}", addr, e);
+                }
+            });
+        }
+    }
+}
+
+pub struct Receiver {
+    stream: tokio::net::TcpStream,
+}
+
+impl Receiver {
+    pub async fn connect(addr: &str) -> anyhow::Result<Self> {
+        let stream = tokio::net::TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        let mut receiver = Self { stream };
+        match receiver.receive().await? {
+            Some(Packet::Hello(hello)) => {
+                if hello.protocol_version != VERSION {
+                    anyhow::bail!(
+                        "spectator protocol version mismatch: {} != {}",
+                        hello.protocol_version,
+                        VERSION
+                    );
+                }
+            }
+            Some(p) => anyhow::bail!("expected hello, got {:?}", p),
+            None => anyhow::bail!("spectator connection closed before hello"),
+        }
+        Ok(receiver)
+    }
+
+    pub async fn receive(&mut self) -> anyhow::Result<Option<Packet>> {
+        let len = match self.stream.read_u32_le().await {
+            Ok(len) => len,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Ok(None);
+            }
+            Err(e) => {
+                return Err(e.into());
+            }
+        };
+        if len > MAX_FRAME_SIZE {
+            anyhow::bail!("spectator frame too large: {} bytes", len);
+        }
+        let mut buf = vec![0u8; len as usize];
+        self.stream.read_exact(&mut buf).await?;
+        Ok(Some(Packet::deserialize(&buf)?))
+    }
+}
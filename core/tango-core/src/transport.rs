@@ -1,19 +1,140 @@
 use crate::protocol;
 
+pub mod loopback;
+
+/// The sending half of a connection to the opponent.
+#[async_trait::async_trait]
+pub trait Sender: Send {
+    async fn send(&mut self, packet: &protocol::Packet) -> anyhow::Result<()>;
+}
+
+/// The receiving half of a connection to the opponent.
+///
+/// Returns None once the connection is closed.
+#[async_trait::async_trait]
+pub trait Receiver: Send {
+    async fn receive(&mut self) -> anyhow::Result<Option<protocol::Packet>>;
+}
+
+#[async_trait::async_trait]
+impl Sender for datachannel_wrapper::DataChannelSender {
+    async fn send(&mut self, packet: &protocol::Packet) -> anyhow::Result<()> {
+        datachannel_wrapper::DataChannelSender::send(self, packet.serialize()?.as_slice()).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Receiver for datachannel_wrapper::DataChannelReceiver {
+    async fn receive(&mut self) -> anyhow::Result<Option<protocol::Packet>> {
+        Ok(
+            match datachannel_wrapper::DataChannelReceiver::receive(self).await {
+                Some(buf) => Some(protocol::Packet::deserialize(buf.as_slice())?),
+                None => None,
+            },
+        )
+    }
+}
+
+/// Connects to the opponent again after the connection dropped in the middle of a match.
+#[async_trait::async_trait]
+pub trait Reconnector: Send + Sync {
+    async fn reconnect(
+        &self,
+    ) -> anyhow::Result<(
+        Box<dyn Sender>,
+        Box<dyn Receiver>,
+        Option<datachannel_wrapper::PeerConnection>,
+    )>;
+}
+
 pub struct Transport {
-    dc_tx: datachannel_wrapper::DataChannelSender,
+    /// Unset while we're disconnected from the opponent.
+    tx: Option<Box<dyn Sender>>,
     rendezvous_rx: Option<tokio::sync::oneshot::Receiver<()>>,
+
+    /// Whether the connection can be resumed if it drops. If not, nothing needs to be kept around for resending.
+    resumable: bool,
+
+    /// Packets that must be delivered which the opponent hasn't acknowledged yet, in the order they were sent.
+    unacked: std::collections::VecDeque<protocol::Packet>,
+
+    /// How many packets were acknowledged before the first one in `unacked`.
+    num_acked: u64,
+
+    /// Whether we've already told the opponent we're leaving.
+    said_goodbye: bool,
 }
 
 impl Transport {
     pub fn new(
-        dc_tx: datachannel_wrapper::DataChannelSender,
+        tx: Box<dyn Sender>,
         rendezvous_rx: tokio::sync::oneshot::Receiver<()>,
+        resumable: bool,
     ) -> Transport {
         Transport {
-            dc_tx,
+            tx: Some(tx),
             rendezvous_rx: Some(rendezvous_rx),
+            resumable,
+            unacked: std::collections::VecDeque::new(),
+            num_acked: 0,
+            said_goodbye: false,
+        }
+    }
+
+    async fn send(&mut self, packet: protocol::Packet) -> anyhow::Result<()> {
+        if !self.resumable {
+            let tx = self
+                .tx
+                .as_mut()
+                .ok_or_else(|| anyhow::anyhow!("not connected"))?;
+            return tx.send(&packet).await;
+        }
+
+        // If we can resume, failing to send isn't an error: the packet will be sent again once we've reconnected, if it needs to be.
+        if let Some(tx) = self.tx.as_mut() {
+            if let Err(e) = tx.send(&packet).await {
+                log::warn!(
+                    "failed to send packet, will resend after reconnecting: {}",
+                    e
+                );
+                self.tx = None;
+            }
         }
+        if packet.must_deliver() {
+            self.unacked.push_back(packet);
+        }
+        Ok(())
+    }
+
+    /// Forgets about packets the opponent has told us they've received.
+    pub fn ack(&mut self, received: u64) {
+        while self.num_acked < received && self.unacked.pop_front().is_some() {
+            self.num_acked += 1;
+        }
+    }
+
+    /// Stops sending anything until the connection is resumed.
+    pub fn disconnect(&mut self) {
+        self.tx = None;
+    }
+
+    /// Switches over to a new connection, first sending again everything after the first `received` packets that must be delivered.
+    pub async fn resume(&mut self, mut tx: Box<dyn Sender>, received: u64) -> anyhow::Result<()> {
+        if received < self.num_acked {
+            anyhow::bail!(
+                "opponent wants to resume from packet {}, but we only have packets from {}",
+                received,
+                self.num_acked
+            );
+        }
+        self.ack(received);
+        log::info!("resending {} packets", self.unacked.len());
+        for packet in self.unacked.iter() {
+            tx.send(packet).await?;
+        }
+        self.tx = Some(tx);
+        Ok(())
     }
 
     pub async fn send_input(
@@ -23,21 +144,79 @@ impl Transport {
         tick_diff: i8,
         joyflags: u16,
     ) -> anyhow::Result<()> {
-        self.dc_tx
-            .send(
-                protocol::Packet::Input(protocol::Input {
-                    round_number,
-                    local_tick,
-                    tick_diff,
-                    joyflags,
-                })
-                .serialize()?
-                .as_slice(),
-            )
-            .await?;
+        self.send(protocol::Packet::Input(protocol::Input {
+            round_number,
+            local_tick,
+            tick_diff,
+            joyflags,
+        }))
+        .await?;
         if let Some(rendezvous_rx) = self.rendezvous_rx.take() {
             rendezvous_rx.await?;
         }
         Ok(())
     }
+
+    pub async fn send_state_checksum(
+        &mut self,
+        round_number: u8,
+        tick: u32,
+        checksum: [u8; 32],
+    ) -> anyhow::Result<()> {
+        self.send(protocol::Packet::StateChecksum(protocol::StateChecksum {
+            round_number,
+            tick,
+            checksum,
+        }))
+        .await
+    }
+
+    pub async fn send_ping(&mut self, ts: u64) -> anyhow::Result<()> {
+        self.send(protocol::Packet::Ping(protocol::Ping { ts }))
+            .await
+    }
+
+    pub async fn send_pong(&mut self, ts: u64) -> anyhow::Result<()> {
+        self.send(protocol::Packet::Pong(protocol::Pong { ts }))
+            .await
+    }
+
+    pub async fn send_ack(&mut self, received: u64) -> anyhow::Result<()> {
+        self.send(protocol::Packet::Ack(protocol::Ack { received }))
+            .await
+    }
+
+    /// Tells the opponent we're leaving. Only the first reason given is sent: anything after it is just fallout from the first.
+    pub async fn send_goodbye(&mut self, reason: protocol::GoodbyeReason) -> anyhow::Result<()> {
+        if self.said_goodbye {
+            return Ok(());
+        }
+        self.said_goodbye = true;
+        self.send(protocol::Packet::Goodbye(protocol::Goodbye { reason }))
+            .await
+    }
+
+    pub async fn send_delay_proposal(
+        &mut self,
+        round_number: u8,
+        input_delay: u32,
+    ) -> anyhow::Result<()> {
+        self.send(protocol::Packet::DelayProposal(protocol::DelayProposal {
+            round_number,
+            input_delay,
+        }))
+        .await
+    }
+
+    pub async fn send_signature(
+        &mut self,
+        round_number: u8,
+        signature: [u8; 64],
+    ) -> anyhow::Result<()> {
+        self.send(protocol::Packet::Signature(protocol::Signature {
+            round_number,
+            signature: signature.to_vec(),
+        }))
+        .await
+    }
 }
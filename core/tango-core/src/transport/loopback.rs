@@ -0,0 +1,173 @@
+use rand::Rng;
+use rand::SeedableRng;
+
+use crate::protocol;
+
+/// Network conditions to simulate on a loopback connection.
+#[derive(Clone, Debug)]
+pub struct Conditions {
+    /// Base one-way delay applied to every packet.
+    pub latency: std::time::Duration,
+
+    /// Maximum additional random delay applied to each packet.
+    pub jitter: std::time::Duration,
+
+    /// If packets may overtake each other when their jitter allows it. Otherwise, packets are always delivered in the order they were sent.
+    pub reorder: bool,
+
+    /// Seed for the jitter RNG, so that runs are reproducible.
+    pub seed: u64,
+}
+
+impl Default for Conditions {
+    fn default() -> Self {
+        Self {
+            latency: std::time::Duration::ZERO,
+            jitter: std::time::Duration::ZERO,
+            reorder: false,
+            seed: 0,
+        }
+    }
+}
+
+struct InFlight {
+    deliver_at: tokio::time::Instant,
+    seq: u64,
+    buf: Vec<u8>,
+}
+
+impl PartialEq for InFlight {
+    fn eq(&self, other: &Self) -> bool {
+        self.deliver_at == other.deliver_at && self.seq == other.seq
+    }
+}
+
+impl Eq for InFlight {}
+
+impl PartialOrd for InFlight {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for InFlight {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // Reversed, such that the BinaryHeap pops the earliest packet first.
+        other
+            .deliver_at
+            .cmp(&self.deliver_at)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+pub struct Sender {
+    tx: tokio::sync::mpsc::UnboundedSender<InFlight>,
+    conditions: Conditions,
+    rng: rand_pcg::Mcg128Xsl64,
+    seq: u64,
+    last_deliver_at: tokio::time::Instant,
+}
+
+pub struct Receiver {
+    rx: tokio::sync::mpsc::UnboundedReceiver<InFlight>,
+    in_flight: std::collections::BinaryHeap<InFlight>,
+}
+
+fn channel(conditions: Conditions) -> (Sender, Receiver) {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    (
+        Sender {
+            tx,
+            rng: rand_pcg::Mcg128Xsl64::seed_from_u64(conditions.seed),
+            conditions,
+            seq: 0,
+            last_deliver_at: tokio::time::Instant::now(),
+        },
+        Receiver {
+            rx,
+            in_flight: std::collections::BinaryHeap::new(),
+        },
+    )
+}
+
+/// Creates two connected endpoints, where packets sent by one are received by the other under the given conditions.
+///
+/// Packets are serialized and deserialized just like over a real connection.
+pub fn pair(conditions: Conditions) -> ((Sender, Receiver), (Sender, Receiver)) {
+    let mut reverse_conditions = conditions.clone();
+    reverse_conditions.seed = conditions.seed.wrapping_add(1);
+    let (a_tx, b_rx) = channel(conditions);
+    let (b_tx, a_rx) = channel(reverse_conditions);
+    ((a_tx, a_rx), (b_tx, b_rx))
+}
+
+#[async_trait::async_trait]
+impl super::Sender for Sender {
+    async fn send(&mut self, packet: &protocol::Packet) -> anyhow::Result<()> {
+        let jitter = if self.conditions.jitter.is_zero() {
+            std::time::Duration::ZERO
+        } else {
+            self.rng
+                .gen_range(std::time::Duration::ZERO..=self.conditions.jitter)
+        };
+        let mut deliver_at = tokio::time::Instant::now() + self.conditions.latency + jitter;
+        if !self.conditions.reorder && deliver_at < self.last_deliver_at {
+            deliver_at = self.last_deliver_at;
+        }
+        self.last_deliver_at = deliver_at;
+
+        let seq = self.seq;
+        self.seq += 1;
+
+        self.tx
+            .send(InFlight {
+                deliver_at,
+                seq,
+                buf: packet.serialize()?,
+            })
+            .map_err(|_| anyhow::anyhow!("loopback closed"))?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl super::Receiver for Receiver {
+    async fn receive(&mut self) -> anyhow::Result<Option<protocol::Packet>> {
+        let mut closed = false;
+        loop {
+            if let Some(next) = self.in_flight.peek() {
+                if next.deliver_at <= tokio::time::Instant::now() {
+                    let next = self.in_flight.pop().unwrap();
+                    return Ok(Some(protocol::Packet::deserialize(&next.buf)?));
+                }
+            } else if closed {
+                return Ok(None);
+            }
+
+            let deliver_at = self.in_flight.peek().map(|next| next.deliver_at);
+            if closed {
+                tokio::time::sleep_until(deliver_at.unwrap()).await;
+                continue;
+            }
+
+            tokio::select! {
+                in_flight = self.rx.recv() => {
+                    match in_flight {
+                        Some(in_flight) => {
+                            self.in_flight.push(in_flight);
+                        }
+                        None => {
+                            closed = true;
+                        }
+                    }
+                }
+                _ = async {
+                    match deliver_at {
+                        Some(deliver_at) => tokio::time::sleep_until(deliver_at).await,
+                        None => std::future::pending().await,
+                    }
+                } => {}
+            }
+        }
+    }
+}
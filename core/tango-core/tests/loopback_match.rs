@@ -0,0 +1,225 @@
+//! Plays a match between two headless cores in the same process, connected by a loopback transport with simulated latency and jitter.
+//!
+//! This needs a ROM and a save file that can get into a link battle, neither of which can be checked in, so it is ignored by default:
+//!
+//! ```text
+//! TANGO_TEST_ROM=bn6.gba TANGO_TEST_SAVE=bn6.sav cargo test -p tango-core --test loopback_match -- --ignored
+//! ```
+//!
+//! `TANGO_TEST_MATCH_TYPE` picks the match type (0 by default), and `TANGO_TEST_INPUT_SCRIPT` can point at an input script for both players to follow instead of the default ones.
+
+/// Long enough to get through the link battle menus and well into the first round, but not to the end of the match.
+const NUM_FRAMES: u64 = 30 * 60;
+
+const INPUT_DELAY: u32 = 2;
+
+/// Both mash through the menus, then keep moving and firing, but differently so that mixing up the players' inputs would show.
+const DEFAULT_INPUT_SCRIPTS: [&str; 2] = [
+    "
+60 -
+5 A
+25 -
+10 UP+B
+10 DOWN+A
+10 LEFT
+10 RIGHT+B
+",
+    "
+45 -
+5 A
+20 -
+15 DOWN+B
+5 A
+15 UP
+5 L
+",
+];
+
+const CONDITIONS: tango_core::transport::loopback::Conditions =
+    tango_core::transport::loopback::Conditions {
+        latency: std::time::Duration::from_millis(40),
+        jitter: std::time::Duration::from_millis(15),
+        reorder: false,
+        seed: 0x7a6e60,
+    };
+
+fn env_path(name: &str) -> std::path::PathBuf {
+    std::env::var_os(name)
+        .unwrap_or_else(|| panic!("{} must be set", name))
+        .into()
+}
+
+fn run_side(
+    rom_path: std::path::PathBuf,
+    save_path: std::path::PathBuf,
+    input_script: tango_core::game::headless::InputScript,
+    match_init: tango_core::battle::MatchInit,
+) -> anyhow::Result<()> {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    let g = tango_core::game::headless::HeadlessGame::new(
+        rt,
+        std::sync::Arc::new(parking_lot::Mutex::new(
+            tango_core::ipc::Sender::new_from_writer(tokio::io::sink()),
+        )),
+        tango_core::ipc::Receiver::new_from_reader(tokio::io::empty()),
+        input_script,
+        rom_path,
+        None,
+        save_path,
+        Some(match_init),
+    )?;
+    g.run_for(Some(NUM_FRAMES))
+}
+
+/// Reads every input pair that made it into a replay, whether or not the round was finished.
+fn read_input_pairs(
+    path: &std::path::Path,
+) -> (
+    u8,
+    Vec<tango_core::input::Pair<tango_core::input::Input, tango_core::input::Input>>,
+) {
+    let salvage = tango_core::replay::salvage(&std::fs::read(path).expect("read replay"))
+        .expect("salvage replay");
+    (
+        salvage.local_player_index,
+        salvage
+            .chunks
+            .into_iter()
+            .flat_map(|(_, chunk)| chunk.input_pairs)
+            .collect(),
+    )
+}
+
+fn replay_paths(dir: &std::path::Path, prefix: &str) -> Vec<std::path::PathBuf> {
+    let mut paths = std::fs::read_dir(dir)
+        .expect("read replays dir")
+        .map(|entry| entry.expect("read replays dir").path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map(|name| name.starts_with(prefix))
+                .unwrap_or(false)
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+#[test]
+#[ignore = "needs a ROM and save: set TANGO_TEST_ROM and TANGO_TEST_SAVE"]
+fn match_over_loopback_with_latency_and_jitter() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let rom_path = env_path("TANGO_TEST_ROM");
+    let save_path = env_path("TANGO_TEST_SAVE");
+    let match_type = std::env::var("TANGO_TEST_MATCH_TYPE")
+        .map(|match_type| match_type.parse::<u8>().expect("match type"))
+        .unwrap_or(0);
+    let input_scripts = match std::env::var_os("TANGO_TEST_INPUT_SCRIPT") {
+        Some(path) => {
+            let input_script = std::fs::read_to_string(path).expect("read input script");
+            [input_script.clone(), input_script]
+        }
+        None => DEFAULT_INPUT_SCRIPTS.map(|input_script| input_script.to_owned()),
+    };
+
+    let replays_dir = tempfile::tempdir().expect("replays dir");
+    let keypairs = [
+        std::sync::Arc::new(tango_core::signing::load_or_generate_keypair(None).unwrap()),
+        std::sync::Arc::new(tango_core::signing::load_or_generate_keypair(None).unwrap()),
+    ];
+    // Both sides would normally agree on this during the handshake.
+    let rng_seed = vec![0x42u8; 16];
+
+    let ((a_tx, a_rx), (b_tx, b_rx)) = tango_core::transport::loopback::pair(CONDITIONS);
+    let ends: [(
+        Box<dyn tango_core::transport::Sender>,
+        Box<dyn tango_core::transport::Receiver>,
+    ); 2] = [
+        (Box::new(a_tx), Box::new(a_rx)),
+        (Box::new(b_tx), Box::new(b_rx)),
+    ];
+
+    let threads = ends
+        .into_iter()
+        .enumerate()
+        .map(|(i, (tx, rx))| {
+            let match_init = tango_core::battle::MatchInit {
+                tx,
+                rx,
+                peer_conn: None,
+                reconnector: None,
+                is_offerer: i == 0,
+                rtt_tracker: tango_core::rtt::Tracker::new(60),
+                settings: tango_core::battle::Settings {
+                    replays_path: replays_dir.path().join(format!("side{}", i)),
+                    shadow_save_path: save_path.clone(),
+                    shadow_rom_path: rom_path.clone(),
+                    shadow_patch: None,
+                    replay_metadata: vec![],
+                    match_type: (match_type, 0),
+                    input_delay: INPUT_DELAY,
+                    shadow_input_delay: INPUT_DELAY,
+                    auto_input_delay: false,
+                    rng_seed: rng_seed.clone(),
+                    opponent_nickname: None,
+                    max_queue_length: 1200,
+                    spectator_listen_addr: None,
+                    signing_keypair: keypairs[i].clone(),
+                    remote_public_key: keypairs[1 - i].public.to_bytes(),
+                },
+            };
+            let rom_path = rom_path.clone();
+            let save_path = save_path.clone();
+            let input_script = tango_core::game::headless::InputScript::parse(&input_scripts[i])
+                .expect("parse input script");
+            std::thread::spawn(move || run_side(rom_path, save_path, input_script, match_init))
+        })
+        .collect::<Vec<_>>();
+
+    for (i, thread) in threads.into_iter().enumerate() {
+        if let Err(e) = thread.join().expect("side panicked") {
+            panic!("side {} ended the match early: {}", i, e);
+        }
+    }
+
+    let a_paths = replay_paths(replays_dir.path(), "side0-");
+    let b_paths = replay_paths(replays_dir.path(), "side1-");
+    assert!(!a_paths.is_empty(), "no round was started");
+    assert_eq!(a_paths.len(), b_paths.len());
+
+    for (a_path, b_path) in a_paths.iter().zip(b_paths.iter()) {
+        let (a_local_player_index, a_input_pairs) = read_input_pairs(a_path);
+        let (b_local_player_index, b_input_pairs) = read_input_pairs(b_path);
+        assert_ne!(a_local_player_index, b_local_player_index);
+
+        // Either side may have stopped a little before the other, but everything both committed must agree.
+        let n = a_input_pairs.len().min(b_input_pairs.len());
+        assert!(n > 60, "only {} input pairs were committed", n);
+        for (a, b) in a_input_pairs[..n].iter().zip(b_input_pairs[..n].iter()) {
+            assert_eq!(a.local.local_tick, b.local.local_tick);
+            assert_eq!(
+                a.local.joyflags, b.remote.joyflags,
+                "at tick {}",
+                a.local.local_tick
+            );
+            assert_eq!(
+                a.remote.joyflags, b.local.joyflags,
+                "at tick {}",
+                a.local.local_tick
+            );
+            assert_eq!(
+                a.local.packet, b.remote.packet,
+                "at tick {}",
+                a.local.local_tick
+            );
+            assert_eq!(
+                a.remote.packet, b.local.packet,
+                "at tick {}",
+                a.local.local_tick
+            );
+        }
+    }
+}
@@ -0,0 +1,166 @@
+#![windows_subsystem = "windows"]
+
+use clap::Parser;
+
+#[derive(clap::Parser)]
+struct Cli {
+    #[clap(parse(from_os_str))]
+    path: std::path::PathBuf,
+
+    /// The ROM the save belongs to. If not given, the game is guessed from the save itself.
+    #[clap(long, parse(from_os_str))]
+    rom_path: Option<std::path::PathBuf>,
+}
+
+#[derive(serde::Serialize)]
+struct Rom {
+    name: String,
+    revision: u8,
+}
+
+#[derive(serde::Serialize)]
+struct Folder {
+    chips: Vec<Option<tango_save::Chip>>,
+    regular_chip_index: Option<usize>,
+    tag_chip_indexes: Option<[usize; 2]>,
+}
+
+#[derive(serde::Serialize)]
+struct Folders {
+    equipped_folder_index: usize,
+    folders: Vec<Folder>,
+}
+
+#[derive(serde::Serialize)]
+struct Navicust {
+    width: usize,
+    height: usize,
+    command_line: usize,
+    has_out_of_bounds: bool,
+    blocks: Vec<tango_save::NavicustBlock>,
+}
+
+#[derive(serde::Serialize)]
+struct LibraryEntry {
+    id: usize,
+    code_slot: usize,
+    count: u8,
+}
+
+#[derive(serde::Serialize)]
+struct Dump {
+    roms: Vec<Rom>,
+    family: &'static str,
+    checksum: u32,
+    folders: Option<Folders>,
+    navicust: Option<Navicust>,
+    modcards: Option<Vec<tango_save::Modcard>>,
+    library: Option<Vec<LibraryEntry>>,
+}
+
+fn main() -> Result<(), anyhow::Error> {
+    env_logger::Builder::from_default_env()
+        .filter(Some("savedump"), log::LevelFilter::Info)
+        .init();
+
+    let args = Cli::parse();
+
+    let dump = std::fs::read(&args.path)?;
+
+    let (family, candidates) = if let Some(rom_path) = args.rom_path.as_ref() {
+        let rom = std::fs::read(rom_path)?;
+        let (rom_name, rom_revision) = tango_save::rom_name_and_revision(&rom)
+            .ok_or_else(|| anyhow::anyhow!("rom is too short"))?;
+        let game = tango_save::get(rom_name, rom_revision)
+            .ok_or_else(|| anyhow::anyhow!("unsupported rom"))?;
+        let save = game.load_save(&dump)?;
+        (
+            game.family(),
+            vec![(
+                Rom {
+                    name: String::from_utf8_lossy(rom_name).to_string(),
+                    revision: rom_revision,
+                },
+                save,
+            )],
+        )
+    } else {
+        let candidates = tango_save::sniff(&dump);
+        let (rom_name, rom_revision, _) = candidates
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("save does not match any supported game"))?;
+        (
+            tango_save::get(rom_name, *rom_revision).unwrap().family(),
+            candidates
+                .into_iter()
+                .map(|(rom_name, rom_revision, save)| {
+                    (
+                        Rom {
+                            name: String::from_utf8_lossy(rom_name).to_string(),
+                            revision: rom_revision,
+                        },
+                        save,
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )
+    };
+
+    // Every ROM that a save loads for shares the same layout, so it doesn't matter which one we read it with.
+    let (roms, saves): (Vec<_>, Vec<_>) = candidates.into_iter().unzip();
+    let save = &saves[0];
+    log::info!("save matches {} rom(s), reading as {}", roms.len(), family);
+
+    serde_json::to_writer(
+        std::io::stdout(),
+        &Dump {
+            roms,
+            family,
+            checksum: save.checksum(),
+            folders: save.folder_view().map(|view| Folders {
+                equipped_folder_index: view.equipped_folder_index(),
+                folders: (0..view.count())
+                    .map(|i| Folder {
+                        chips: (0..tango_save::CHIPS_PER_FOLDER)
+                            .map(|j| view.chip(i, j))
+                            .collect(),
+                        regular_chip_index: view.regular_chip_index(i),
+                        tag_chip_indexes: view.tag_chip_indexes(i),
+                    })
+                    .collect(),
+            }),
+            navicust: save.navicust_view().map(|view| Navicust {
+                width: view.width(),
+                height: view.height(),
+                command_line: view.command_line(),
+                has_out_of_bounds: view.has_out_of_bounds(),
+                blocks: (0..tango_save::MAX_NAVICUST_BLOCKS)
+                    .filter_map(|i| view.block(i))
+                    .collect(),
+            }),
+            modcards: save
+                .modcards_view()
+                .map(|view| (0..view.count()).filter_map(|i| view.modcard(i)).collect()),
+            library: save.library_view().map(|view| {
+                (0..view.num_chips())
+                    .flat_map(|id| {
+                        (0..tango_save::NUM_CHIP_CODE_SLOTS).map(move |code_slot| (id, code_slot))
+                    })
+                    .filter_map(|(id, code_slot)| {
+                        let count = view.count(id, code_slot);
+                        if count == 0 {
+                            return None;
+                        }
+                        Some(LibraryEntry {
+                            id,
+                            code_slot,
+                            count,
+                        })
+                    })
+                    .collect()
+            }),
+        },
+    )?;
+
+    Ok(())
+}
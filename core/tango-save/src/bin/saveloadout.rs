@@ -0,0 +1,58 @@
+#![windows_subsystem = "windows"]
+
+use clap::Parser;
+
+#[derive(clap::Parser)]
+struct Cli {
+    /// The save to apply the loadout to.
+    #[clap(parse(from_os_str))]
+    path: std::path::PathBuf,
+
+    /// The loadout, as JSON.
+    #[clap(parse(from_os_str))]
+    loadout_path: std::path::PathBuf,
+
+    /// The ROM the save belongs to. Some parts of the save, such as the NaviCust tile map, are rebuilt from it.
+    #[clap(long, parse(from_os_str))]
+    rom_path: std::path::PathBuf,
+
+    /// Where to write the edited save. If not given, the save is edited in place.
+    #[clap(long, short('o'), parse(from_os_str))]
+    output_path: Option<std::path::PathBuf>,
+}
+
+fn main() -> Result<(), anyhow::Error> {
+    env_logger::Builder::from_default_env()
+        .filter(Some("saveloadout"), log::LevelFilter::Info)
+        .init();
+
+    let args = Cli::parse();
+
+    let rom = std::fs::read(&args.rom_path)?;
+    let (rom_name, rom_revision) = tango_save::rom_name_and_revision(&rom)
+        .ok_or_else(|| anyhow::anyhow!("rom is too short"))?;
+    let game = tango_save::get(rom_name, rom_revision)
+        .ok_or_else(|| anyhow::anyhow!("unsupported rom"))?;
+
+    let loadout: tango_save::loadout::Loadout =
+        serde_json::from_reader(std::fs::File::open(&args.loadout_path)?)?;
+
+    let mut dump = std::fs::read(&args.path)?;
+    let mut save = game.load_save(&dump)?;
+    let old_checksum = save.checksum();
+
+    tango_save::loadout::apply(save.as_mut(), &loadout)?;
+    save.rebuild(&rom)?;
+    save.write_sram_dump(&mut dump);
+
+    log::info!(
+        "applied loadout to {} save, checksum {:08x} -> {:08x}",
+        game.family(),
+        old_checksum,
+        save.checksum()
+    );
+
+    std::fs::write(args.output_path.as_ref().unwrap_or(&args.path), &dump)?;
+
+    Ok(())
+}
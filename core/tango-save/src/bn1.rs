@@ -0,0 +1,70 @@
+use byteorder::ByteOrder;
+
+const SRAM_SIZE: usize = 0x2308;
+const GAME_NAME_OFFSET: usize = 0x03fc;
+const CHECKSUM_OFFSET: usize = 0x03f0;
+
+/// BN1 saves can only be identified and have their checksums checked and fixed: nothing else in them is decoded.
+#[derive(Clone, Copy)]
+pub struct BN1 {
+    game_name: &'static [u8],
+}
+
+pub static MEGAMAN_BNAREE_00: BN1 = BN1 {
+    game_name: b"ROCKMAN EXE 20010727",
+};
+pub static ROCKMAN_EXEAREJ_00: BN1 = BN1 {
+    game_name: b"ROCKMAN EXE 20010120",
+};
+
+impl crate::Game for BN1 {
+    fn family(&self) -> &'static str {
+        "bn1"
+    }
+
+    fn load_save(&self, dump: &[u8]) -> Result<Box<dyn crate::Save + Send + Sync>, crate::Error> {
+        crate::check_size(dump, 0, SRAM_SIZE)?;
+        let buf = dump[..SRAM_SIZE].to_vec();
+
+        let game_name = crate::read_game_name(&buf, GAME_NAME_OFFSET);
+        if game_name != self.game_name {
+            return Err(crate::Error::UnknownGameName(
+                String::from_utf8_lossy(game_name).to_string(),
+            ));
+        }
+
+        let save = Save { buf };
+        crate::check_checksum(&save)?;
+        Ok(Box::new(save))
+    }
+}
+
+pub struct Save {
+    buf: Vec<u8>,
+}
+
+impl crate::Save for Save {
+    fn as_raw(&self) -> &[u8] {
+        &self.buf
+    }
+
+    fn checksum(&self) -> u32 {
+        byteorder::LittleEndian::read_u32(&self.buf[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4])
+    }
+
+    fn compute_checksum(&self) -> u32 {
+        crate::sum_bytes(&self.buf, CHECKSUM_OFFSET).wrapping_add(0x16)
+    }
+
+    fn rebuild_checksum(&mut self) {
+        let checksum = self.compute_checksum();
+        byteorder::LittleEndian::write_u32(
+            &mut self.buf[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4],
+            checksum,
+        );
+    }
+
+    fn write_sram_dump(&self, dump: &mut [u8]) {
+        dump[..SRAM_SIZE].copy_from_slice(&self.buf);
+    }
+}
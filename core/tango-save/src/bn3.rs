@@ -0,0 +1,88 @@
+use byteorder::ByteOrder;
+
+const SRAM_SIZE: usize = 0x57b0;
+const GAME_NAME_OFFSET: usize = 0x1e00;
+const CHECKSUM_OFFSET: usize = 0x1dd8;
+
+const GAME_NAMES: &[&[u8]] = &[b"ROCKMANEXE3 20021002", b"BBN3 v0.5.0 20021002"];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Variant {
+    White,
+    Blue,
+}
+
+/// Identifies BN3 saves, telling White and Blue apart by their checksums. The folder and library aren't decoded.
+#[derive(Clone, Copy)]
+pub struct BN3 {
+    variant: Variant,
+}
+
+pub static MEGA_EXE3_BLA3XE_00: BN3 = BN3 {
+    variant: Variant::Blue,
+};
+pub static MEGA_EXE3_WHA6BE_00: BN3 = BN3 {
+    variant: Variant::White,
+};
+pub static ROCK_EXE3_BKA3XJ_01: BN3 = BN3 {
+    variant: Variant::Blue,
+};
+pub static ROCKMAN_EXE3A6BJ_01: BN3 = BN3 {
+    variant: Variant::White,
+};
+
+impl crate::Game for BN3 {
+    fn family(&self) -> &'static str {
+        "bn3"
+    }
+
+    fn load_save(&self, dump: &[u8]) -> Result<Box<dyn crate::Save + Send + Sync>, crate::Error> {
+        crate::check_size(dump, 0, SRAM_SIZE)?;
+        let buf = dump[..SRAM_SIZE].to_vec();
+
+        let game_name = crate::read_game_name(&buf, GAME_NAME_OFFSET);
+        if !GAME_NAMES.contains(&game_name) {
+            return Err(crate::Error::UnknownGameName(
+                String::from_utf8_lossy(game_name).to_string(),
+            ));
+        }
+
+        let save = Save { buf, game: *self };
+        crate::check_checksum(&save)?;
+        Ok(Box::new(save))
+    }
+}
+
+pub struct Save {
+    buf: Vec<u8>,
+    game: BN3,
+}
+
+impl crate::Save for Save {
+    fn as_raw(&self) -> &[u8] {
+        &self.buf
+    }
+
+    fn checksum(&self) -> u32 {
+        byteorder::LittleEndian::read_u32(&self.buf[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4])
+    }
+
+    fn compute_checksum(&self) -> u32 {
+        crate::sum_bytes(&self.buf, CHECKSUM_OFFSET).wrapping_add(match self.game.variant {
+            Variant::White => 0x16,
+            Variant::Blue => 0x22,
+        })
+    }
+
+    fn rebuild_checksum(&mut self) {
+        let checksum = self.compute_checksum();
+        byteorder::LittleEndian::write_u32(
+            &mut self.buf[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4],
+            checksum,
+        );
+    }
+
+    fn write_sram_dump(&self, dump: &mut [u8]) {
+        dump[..SRAM_SIZE].copy_from_slice(&self.buf);
+    }
+}
@@ -0,0 +1,345 @@
+use byteorder::ByteOrder;
+
+const SRAM_SIZE: usize = 0x73d2;
+const MASK_OFFSET: usize = 0x1554;
+const START_OFFSET_OFFSET: usize = 0x1550;
+const GAME_NAME_OFFSET: usize = 0x2208;
+const CHECKSUM_OFFSET: usize = 0x21e8;
+
+const GAME_NAME: &[u8] = b"ROCKMANEXE4 20031022";
+
+const NUM_CHIPS: usize = 389;
+const NAVICUST_SIZE: usize = 5;
+const NUM_FOLDERS: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Region {
+    US,
+    JP,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Variant {
+    BlueMoon,
+    RedSun,
+}
+
+#[derive(Clone, Copy)]
+pub struct BN4 {
+    region: Region,
+    variant: Variant,
+    ncp_data_offset: Option<usize>,
+}
+
+pub static MEGAMANBN4BMB4BE_00: BN4 = BN4 {
+    region: Region::US,
+    variant: Variant::BlueMoon,
+    ncp_data_offset: Some(0x00045644),
+};
+pub static MEGAMANBN4RSB4WE_00: BN4 = BN4 {
+    region: Region::US,
+    variant: Variant::RedSun,
+    ncp_data_offset: Some(0x0004563c),
+};
+pub static ROCK_EXE4_BMB4BJ_00: BN4 = BN4 {
+    region: Region::JP,
+    variant: Variant::BlueMoon,
+    ncp_data_offset: Some(0x00045504),
+};
+pub static ROCK_EXE4_BMB4BJ_01: BN4 = BN4 {
+    region: Region::JP,
+    variant: Variant::BlueMoon,
+    ncp_data_offset: None,
+};
+pub static ROCK_EXE4_RSB4WJ_00: BN4 = BN4 {
+    region: Region::JP,
+    variant: Variant::RedSun,
+    ncp_data_offset: Some(0x000454fc),
+};
+pub static ROCK_EXE4_RSB4WJ_01: BN4 = BN4 {
+    region: Region::JP,
+    variant: Variant::RedSun,
+    ncp_data_offset: Some(0x00045538),
+};
+
+impl crate::Game for BN4 {
+    fn family(&self) -> &'static str {
+        "bn4"
+    }
+
+    fn load_save(&self, dump: &[u8]) -> Result<Box<dyn crate::Save + Send + Sync>, crate::Error> {
+        crate::check_size(dump, 0, SRAM_SIZE)?;
+        let mut buf = dump[..SRAM_SIZE].to_vec();
+        crate::mask(&mut buf, MASK_OFFSET);
+
+        // The save data floats around inside of SRAM: the game stores where it actually starts.
+        let shift =
+            byteorder::LittleEndian::read_u32(&buf[START_OFFSET_OFFSET..START_OFFSET_OFFSET + 4]);
+        if shift > 0x1fc || shift & 3 != 0 {
+            return Err(crate::Error::InvalidStartOffset(shift));
+        }
+        let shift = shift as usize;
+
+        let game_name = crate::read_game_name(&buf, shift + GAME_NAME_OFFSET);
+        if game_name != GAME_NAME {
+            return Err(crate::Error::UnknownGameName(
+                String::from_utf8_lossy(game_name).to_string(),
+            ));
+        }
+
+        let save = Save {
+            buf,
+            shift,
+            game: *self,
+            navicust_dirty: false,
+        };
+        crate::check_checksum(&save)?;
+        Ok(Box::new(save))
+    }
+}
+
+pub struct Save {
+    buf: Vec<u8>,
+    shift: usize,
+    game: BN4,
+    navicust_dirty: bool,
+}
+
+impl Save {
+    fn data(&self) -> &[u8] {
+        &self.buf[self.shift..]
+    }
+
+    fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.buf[self.shift..]
+    }
+
+    fn rebuild_navicust_tiles(&mut self, rom: &[u8]) -> Result<(), crate::Error> {
+        let ncp_data_offset = self
+            .game
+            .ncp_data_offset
+            .ok_or(crate::Error::MissingRomData("navicust program data"))?;
+        let view = NavicustView { save: self };
+        let blocks = (0..crate::MAX_NAVICUST_BLOCKS)
+            .map(|i| crate::NavicustView::block(&view, i))
+            .collect::<Vec<_>>();
+        let tiles = crate::build_navicust_tiles(rom, ncp_data_offset, NAVICUST_SIZE, &blocks)?;
+        self.data_mut()[0x4540..0x4540 + tiles.len()].copy_from_slice(&tiles);
+        self.navicust_dirty = false;
+        Ok(())
+    }
+}
+
+impl crate::Save for Save {
+    fn as_raw(&self) -> &[u8] {
+        &self.buf
+    }
+
+    fn checksum(&self) -> u32 {
+        byteorder::LittleEndian::read_u32(&self.data()[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4])
+    }
+
+    fn compute_checksum(&self) -> u32 {
+        // The first byte of SRAM is only counted by the US versions.
+        crate::sum_bytes(&self.buf, self.shift + CHECKSUM_OFFSET)
+            .wrapping_sub(if self.game.region == Region::JP {
+                self.buf[0] as u32
+            } else {
+                0
+            })
+            .wrapping_add(match self.game.variant {
+                Variant::BlueMoon => 0x22,
+                Variant::RedSun => 0x16,
+            })
+    }
+
+    fn rebuild_checksum(&mut self) {
+        let checksum = self.compute_checksum();
+        byteorder::LittleEndian::write_u32(
+            &mut self.data_mut()[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4],
+            checksum,
+        );
+    }
+
+    fn rebuild(&mut self, rom: &[u8]) -> Result<(), crate::Error> {
+        if self.navicust_dirty {
+            self.rebuild_navicust_tiles(rom)?;
+        }
+        self.rebuild_checksum();
+        Ok(())
+    }
+
+    fn write_sram_dump(&self, dump: &mut [u8]) {
+        let buf = &mut dump[..SRAM_SIZE];
+        buf.copy_from_slice(&self.buf);
+        crate::mask(buf, MASK_OFFSET);
+    }
+
+    fn folder_view(&self) -> Option<Box<dyn crate::FolderView + '_>> {
+        Some(Box::new(FolderView { save: self }))
+    }
+
+    fn folder_view_mut(&mut self) -> Option<Box<dyn crate::FolderViewMut + '_>> {
+        Some(Box::new(FolderViewMut { save: self }))
+    }
+
+    fn navicust_view(&self) -> Option<Box<dyn crate::NavicustView + '_>> {
+        Some(Box::new(NavicustView { save: self }))
+    }
+
+    fn navicust_view_mut(&mut self) -> Option<Box<dyn crate::NavicustViewMut + '_>> {
+        Some(Box::new(NavicustViewMut { save: self }))
+    }
+
+    fn library_view(&self) -> Option<Box<dyn crate::LibraryView + '_>> {
+        Some(Box::new(LibraryView { save: self }))
+    }
+}
+
+struct FolderView<'a> {
+    save: &'a Save,
+}
+
+impl<'a> crate::FolderView for FolderView<'a> {
+    fn count(&self) -> usize {
+        NUM_FOLDERS
+    }
+
+    fn equipped_folder_index(&self) -> usize {
+        self.save.data()[0x2132] as usize
+    }
+
+    fn regular_chip_index(&self, folder_index: usize) -> Option<usize> {
+        let i = self.save.data()[0x214d + folder_index];
+        if i == 0xff {
+            return None;
+        }
+        Some(i as usize)
+    }
+
+    fn chip(&self, folder_index: usize, chip_index: usize) -> Option<crate::Chip> {
+        let offset = 0x262c + folder_index * (crate::CHIPS_PER_FOLDER * 2) + chip_index * 2;
+        crate::Chip::from_raw(byteorder::LittleEndian::read_u16(
+            &self.save.data()[offset..offset + 2],
+        ))
+    }
+}
+
+struct FolderViewMut<'a> {
+    save: &'a mut Save,
+}
+
+impl<'a> crate::FolderViewMut for FolderViewMut<'a> {
+    fn set_equipped_folder_index(&mut self, folder_index: usize) -> Result<(), crate::Error> {
+        crate::check_range("folder index", folder_index, NUM_FOLDERS)?;
+        self.save.data_mut()[0x2132] = folder_index as u8;
+        Ok(())
+    }
+
+    fn set_regular_chip_index(
+        &mut self,
+        folder_index: usize,
+        chip_index: Option<usize>,
+    ) -> Result<(), crate::Error> {
+        crate::check_range("folder index", folder_index, NUM_FOLDERS)?;
+        if let Some(chip_index) = chip_index {
+            crate::check_range("chip index", chip_index, crate::CHIPS_PER_FOLDER)?;
+        }
+        self.save.data_mut()[0x214d + folder_index] = chip_index.map(|i| i as u8).unwrap_or(0xff);
+        Ok(())
+    }
+
+    fn set_chip(
+        &mut self,
+        folder_index: usize,
+        chip_index: usize,
+        chip: Option<crate::Chip>,
+    ) -> Result<(), crate::Error> {
+        crate::check_range("folder index", folder_index, NUM_FOLDERS)?;
+        crate::check_range("chip index", chip_index, crate::CHIPS_PER_FOLDER)?;
+        let raw = chip.map(|chip| chip.to_raw()).transpose()?.unwrap_or(0);
+        let offset = 0x262c + folder_index * (crate::CHIPS_PER_FOLDER * 2) + chip_index * 2;
+        byteorder::LittleEndian::write_u16(&mut self.save.data_mut()[offset..offset + 2], raw);
+        Ok(())
+    }
+}
+
+struct NavicustView<'a> {
+    save: &'a Save,
+}
+
+impl<'a> crate::NavicustView for NavicustView<'a> {
+    fn width(&self) -> usize {
+        5
+    }
+
+    fn height(&self) -> usize {
+        5
+    }
+
+    fn command_line(&self) -> usize {
+        2
+    }
+
+    fn has_out_of_bounds(&self) -> bool {
+        false
+    }
+
+    fn block(&self, i: usize) -> Option<crate::NavicustBlock> {
+        let buf = &self.save.data()[0x4564 + i * 8..][..8];
+        if buf[0] == 0 {
+            return None;
+        }
+
+        Some(crate::NavicustBlock {
+            id: (buf[0] >> 2) as usize,
+            variant: (buf[0] & 0x3) as usize,
+            col: buf[2],
+            row: buf[3],
+            rot: buf[4],
+            compressed: buf[5] != 0,
+        })
+    }
+}
+
+struct NavicustViewMut<'a> {
+    save: &'a mut Save,
+}
+
+impl<'a> crate::NavicustViewMut for NavicustViewMut<'a> {
+    fn set_block(
+        &mut self,
+        i: usize,
+        block: Option<crate::NavicustBlock>,
+    ) -> Result<(), crate::Error> {
+        crate::check_range("navicust block index", i, crate::MAX_NAVICUST_BLOCKS)?;
+        if let Some(block) = block.as_ref() {
+            crate::check_navicust_block(block, NAVICUST_SIZE)?;
+        }
+        let buf = &mut self.save.data_mut()[0x4564 + i * 8..][..8];
+        buf.fill(0);
+        if let Some(block) = block {
+            buf[0] = (block.id << 2 | block.variant) as u8;
+            buf[2] = block.col;
+            buf[3] = block.row;
+            buf[4] = block.rot;
+            buf[5] = block.compressed as u8;
+        }
+        self.save.navicust_dirty = true;
+        Ok(())
+    }
+}
+
+struct LibraryView<'a> {
+    save: &'a Save,
+}
+
+impl<'a> crate::LibraryView for LibraryView<'a> {
+    fn num_chips(&self) -> usize {
+        NUM_CHIPS
+    }
+
+    fn count(&self, id: usize, code_slot: usize) -> u8 {
+        self.save.data()[0x26e4 + id * 0xc + code_slot]
+    }
+}
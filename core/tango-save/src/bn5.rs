@@ -0,0 +1,299 @@
+use byteorder::ByteOrder;
+
+const SRAM_START_OFFSET: usize = 0x0100;
+const SRAM_SIZE: usize = 0x7c14;
+const MASK_OFFSET: usize = 0x1a34;
+const GAME_NAME_OFFSET: usize = 0x29e0;
+const CHECKSUM_OFFSET: usize = 0x29dc;
+
+const NUM_CHIPS: usize = 411;
+const NAVICUST_SIZE: usize = 5;
+const NUM_FOLDERS: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Variant {
+    Protoman,
+    Colonel,
+}
+
+#[derive(Clone, Copy)]
+pub struct BN5 {
+    game_name: &'static [u8],
+    variant: Variant,
+    ncp_data_offset: usize,
+}
+
+pub static MEGAMAN5_TP_BRBE_00: BN5 = BN5 {
+    game_name: b"REXE5TOB 20041006 US",
+    variant: Variant::Protoman,
+    ncp_data_offset: 0x0013d540,
+};
+pub static MEGAMAN5_TC_BRKE_00: BN5 = BN5 {
+    game_name: b"REXE5TOK 20041006 US",
+    variant: Variant::Colonel,
+    ncp_data_offset: 0x0013d628,
+};
+pub static ROCKEXE5_TOBBRBJ_00: BN5 = BN5 {
+    game_name: b"REXE5TOB 20041104 JP",
+    variant: Variant::Protoman,
+    ncp_data_offset: 0x0013d0cc,
+};
+pub static ROCKEXE5_TOCBRKJ_00: BN5 = BN5 {
+    game_name: b"REXE5TOK 20041104 JP",
+    variant: Variant::Colonel,
+    ncp_data_offset: 0x0013d1b4,
+};
+
+impl crate::Game for BN5 {
+    fn family(&self) -> &'static str {
+        "bn5"
+    }
+
+    fn load_save(&self, dump: &[u8]) -> Result<Box<dyn crate::Save + Send + Sync>, crate::Error> {
+        crate::check_size(dump, SRAM_START_OFFSET, SRAM_SIZE)?;
+        let mut buf = dump[SRAM_START_OFFSET..SRAM_START_OFFSET + SRAM_SIZE].to_vec();
+        crate::mask(&mut buf, MASK_OFFSET);
+
+        let game_name = crate::read_game_name(&buf, GAME_NAME_OFFSET);
+        if game_name != self.game_name {
+            return Err(crate::Error::UnknownGameName(
+                String::from_utf8_lossy(game_name).to_string(),
+            ));
+        }
+
+        let save = Save {
+            buf,
+            game: *self,
+            navicust_dirty: false,
+        };
+        crate::check_checksum(&save)?;
+        Ok(Box::new(save))
+    }
+}
+
+pub struct Save {
+    buf: Vec<u8>,
+    game: BN5,
+    navicust_dirty: bool,
+}
+
+impl Save {
+    fn rebuild_navicust_tiles(&mut self, rom: &[u8]) -> Result<(), crate::Error> {
+        let view = NavicustView { save: self };
+        let blocks = (0..crate::MAX_NAVICUST_BLOCKS)
+            .map(|i| crate::NavicustView::block(&view, i))
+            .collect::<Vec<_>>();
+        let tiles =
+            crate::build_navicust_tiles(rom, self.game.ncp_data_offset, NAVICUST_SIZE, &blocks)?;
+        self.buf[0x4d48..0x4d48 + tiles.len()].copy_from_slice(&tiles);
+        self.navicust_dirty = false;
+        Ok(())
+    }
+}
+
+impl crate::Save for Save {
+    fn as_raw(&self) -> &[u8] {
+        &self.buf
+    }
+
+    fn checksum(&self) -> u32 {
+        byteorder::LittleEndian::read_u32(&self.buf[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4])
+    }
+
+    fn compute_checksum(&self) -> u32 {
+        crate::sum_bytes(&self.buf, CHECKSUM_OFFSET).wrapping_add(match self.game.variant {
+            Variant::Protoman => 0x72,
+            Variant::Colonel => 0x18,
+        })
+    }
+
+    fn rebuild_checksum(&mut self) {
+        let checksum = self.compute_checksum();
+        byteorder::LittleEndian::write_u32(
+            &mut self.buf[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4],
+            checksum,
+        );
+    }
+
+    fn rebuild(&mut self, rom: &[u8]) -> Result<(), crate::Error> {
+        if self.navicust_dirty {
+            self.rebuild_navicust_tiles(rom)?;
+        }
+        self.rebuild_checksum();
+        Ok(())
+    }
+
+    fn write_sram_dump(&self, dump: &mut [u8]) {
+        let buf = &mut dump[SRAM_START_OFFSET..SRAM_START_OFFSET + SRAM_SIZE];
+        buf.copy_from_slice(&self.buf);
+        crate::mask(buf, MASK_OFFSET);
+    }
+
+    fn folder_view(&self) -> Option<Box<dyn crate::FolderView + '_>> {
+        Some(Box::new(FolderView { save: self }))
+    }
+
+    fn folder_view_mut(&mut self) -> Option<Box<dyn crate::FolderViewMut + '_>> {
+        Some(Box::new(FolderViewMut { save: self }))
+    }
+
+    fn navicust_view(&self) -> Option<Box<dyn crate::NavicustView + '_>> {
+        Some(Box::new(NavicustView { save: self }))
+    }
+
+    fn navicust_view_mut(&mut self) -> Option<Box<dyn crate::NavicustViewMut + '_>> {
+        Some(Box::new(NavicustViewMut { save: self }))
+    }
+
+    fn library_view(&self) -> Option<Box<dyn crate::LibraryView + '_>> {
+        Some(Box::new(LibraryView { save: self }))
+    }
+}
+
+struct FolderView<'a> {
+    save: &'a Save,
+}
+
+impl<'a> crate::FolderView for FolderView<'a> {
+    fn count(&self) -> usize {
+        NUM_FOLDERS
+    }
+
+    fn equipped_folder_index(&self) -> usize {
+        self.save.buf[0x52d5] as usize
+    }
+
+    fn regular_chip_index(&self, folder_index: usize) -> Option<usize> {
+        let i = self.save.buf[0x52d6 + folder_index];
+        if i == 0xff {
+            return None;
+        }
+        Some(i as usize)
+    }
+
+    fn chip(&self, folder_index: usize, chip_index: usize) -> Option<crate::Chip> {
+        let offset = 0x2df4 + folder_index * (crate::CHIPS_PER_FOLDER * 2) + chip_index * 2;
+        crate::Chip::from_raw(byteorder::LittleEndian::read_u16(
+            &self.save.buf[offset..offset + 2],
+        ))
+    }
+}
+
+struct FolderViewMut<'a> {
+    save: &'a mut Save,
+}
+
+impl<'a> crate::FolderViewMut for FolderViewMut<'a> {
+    fn set_equipped_folder_index(&mut self, folder_index: usize) -> Result<(), crate::Error> {
+        crate::check_range("folder index", folder_index, NUM_FOLDERS)?;
+        self.save.buf[0x52d5] = folder_index as u8;
+        Ok(())
+    }
+
+    fn set_regular_chip_index(
+        &mut self,
+        folder_index: usize,
+        chip_index: Option<usize>,
+    ) -> Result<(), crate::Error> {
+        crate::check_range("folder index", folder_index, NUM_FOLDERS)?;
+        if let Some(chip_index) = chip_index {
+            crate::check_range("chip index", chip_index, crate::CHIPS_PER_FOLDER)?;
+        }
+        self.save.buf[0x52d6 + folder_index] = chip_index.map(|i| i as u8).unwrap_or(0xff);
+        Ok(())
+    }
+
+    fn set_chip(
+        &mut self,
+        folder_index: usize,
+        chip_index: usize,
+        chip: Option<crate::Chip>,
+    ) -> Result<(), crate::Error> {
+        crate::check_range("folder index", folder_index, NUM_FOLDERS)?;
+        crate::check_range("chip index", chip_index, crate::CHIPS_PER_FOLDER)?;
+        let raw = chip.map(|chip| chip.to_raw()).transpose()?.unwrap_or(0);
+        let offset = 0x2df4 + folder_index * (crate::CHIPS_PER_FOLDER * 2) + chip_index * 2;
+        byteorder::LittleEndian::write_u16(&mut self.save.buf[offset..offset + 2], raw);
+        Ok(())
+    }
+}
+
+struct NavicustView<'a> {
+    save: &'a Save,
+}
+
+impl<'a> crate::NavicustView for NavicustView<'a> {
+    fn width(&self) -> usize {
+        5
+    }
+
+    fn height(&self) -> usize {
+        5
+    }
+
+    fn command_line(&self) -> usize {
+        2
+    }
+
+    fn has_out_of_bounds(&self) -> bool {
+        false
+    }
+
+    fn block(&self, i: usize) -> Option<crate::NavicustBlock> {
+        let buf = &self.save.buf[0x4d6c + i * 8..][..8];
+        if buf[0] == 0 {
+            return None;
+        }
+
+        Some(crate::NavicustBlock {
+            id: (buf[0] >> 2) as usize,
+            variant: (buf[0] & 0x3) as usize,
+            col: buf[2],
+            row: buf[3],
+            rot: buf[4],
+            compressed: buf[5] != 0,
+        })
+    }
+}
+
+struct NavicustViewMut<'a> {
+    save: &'a mut Save,
+}
+
+impl<'a> crate::NavicustViewMut for NavicustViewMut<'a> {
+    fn set_block(
+        &mut self,
+        i: usize,
+        block: Option<crate::NavicustBlock>,
+    ) -> Result<(), crate::Error> {
+        crate::check_range("navicust block index", i, crate::MAX_NAVICUST_BLOCKS)?;
+        if let Some(block) = block.as_ref() {
+            crate::check_navicust_block(block, NAVICUST_SIZE)?;
+        }
+        let buf = &mut self.save.buf[0x4d6c + i * 8..][..8];
+        buf.fill(0);
+        if let Some(block) = block {
+            buf[0] = (block.id << 2 | block.variant) as u8;
+            buf[2] = block.col;
+            buf[3] = block.row;
+            buf[4] = block.rot;
+            buf[5] = block.compressed as u8;
+        }
+        self.save.navicust_dirty = true;
+        Ok(())
+    }
+}
+
+struct LibraryView<'a> {
+    save: &'a Save,
+}
+
+impl<'a> crate::LibraryView for LibraryView<'a> {
+    fn num_chips(&self) -> usize {
+        NUM_CHIPS
+    }
+
+    fn count(&self, id: usize, code_slot: usize) -> u8 {
+        self.save.buf[0x2eac + id * 0xc + code_slot]
+    }
+}
@@ -0,0 +1,467 @@
+use byteorder::ByteOrder;
+
+const SRAM_START_OFFSET: usize = 0x0100;
+const SRAM_SIZE: usize = 0x6710;
+const MASK_OFFSET: usize = 0x1064;
+const GAME_NAME_OFFSET: usize = 0x1c70;
+const CHECKSUM_OFFSET: usize = 0x1c6c;
+
+const NUM_CHIPS: usize = 411;
+const NUM_MODCARDS: usize = 118;
+const NAVICUST_SIZE: usize = 7;
+
+const MODCARDS_OFFSET: usize = 0x6620;
+const MAX_INSTALLED_MODCARDS: usize = SRAM_SIZE - MODCARDS_OFFSET;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Region {
+    US,
+    JP,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Variant {
+    Falzar,
+    Gregar,
+}
+
+#[derive(Clone, Copy)]
+pub struct BN6 {
+    game_name: &'static [u8],
+    region: Region,
+    variant: Variant,
+    ncp_data_offset: usize,
+}
+
+pub static MEGAMAN6_FXXBR6E_00: BN6 = BN6 {
+    game_name: b"REXE6 F 20060110a US",
+    region: Region::US,
+    variant: Variant::Falzar,
+    ncp_data_offset: 0x0013944c,
+};
+pub static MEGAMAN6_GXXBR5E_00: BN6 = BN6 {
+    game_name: b"REXE6 G 20060110a US",
+    region: Region::US,
+    variant: Variant::Gregar,
+    ncp_data_offset: 0x0013b22c,
+};
+pub static ROCKEXE6_RXXBR6J_00: BN6 = BN6 {
+    game_name: b"REXE6 F 20050924a JP",
+    region: Region::JP,
+    variant: Variant::Falzar,
+    ncp_data_offset: 0x00144300,
+};
+pub static ROCKEXE6_GXXBR5J_00: BN6 = BN6 {
+    game_name: b"REXE6 G 20050924a JP",
+    region: Region::JP,
+    variant: Variant::Gregar,
+    ncp_data_offset: 0x001460cc,
+};
+
+impl crate::Game for BN6 {
+    fn family(&self) -> &'static str {
+        "bn6"
+    }
+
+    fn load_save(&self, dump: &[u8]) -> Result<Box<dyn crate::Save + Send + Sync>, crate::Error> {
+        crate::check_size(dump, SRAM_START_OFFSET, SRAM_SIZE)?;
+        let mut buf = dump[SRAM_START_OFFSET..SRAM_START_OFFSET + SRAM_SIZE].to_vec();
+        crate::mask(&mut buf, MASK_OFFSET);
+
+        let game_name = crate::read_game_name(&buf, GAME_NAME_OFFSET);
+        if game_name != self.game_name {
+            return Err(crate::Error::UnknownGameName(
+                String::from_utf8_lossy(game_name).to_string(),
+            ));
+        }
+
+        let save = Save {
+            buf,
+            game: *self,
+            navicust_dirty: false,
+            modcards_dirty: false,
+        };
+        crate::check_checksum(&save)?;
+        Ok(Box::new(save))
+    }
+}
+
+pub struct Save {
+    buf: Vec<u8>,
+    game: BN6,
+    navicust_dirty: bool,
+    modcards_dirty: bool,
+}
+
+impl Save {
+    fn current_navi(&self) -> usize {
+        self.buf[0x1b81] as usize
+    }
+
+    fn navi_stats_offset(&self) -> usize {
+        (if self.game.region == Region::JP {
+            0x478c
+        } else {
+            0x47cc
+        }) + 0x64 * if self.current_navi() == 0 { 0 } else { 1 }
+    }
+
+    fn navicust_offset(&self) -> usize {
+        if self.game.region == Region::JP {
+            0x4150
+        } else {
+            0x4190
+        }
+    }
+
+    fn navicust_tiles_offset(&self) -> usize {
+        if self.game.region == Region::JP {
+            0x410c
+        } else {
+            0x414c
+        }
+    }
+
+    fn folder_count(&self) -> usize {
+        self.buf[0x1c09] as usize
+    }
+
+    fn modcard_count(&self) -> usize {
+        self.buf[0x65f0] as usize
+    }
+
+    fn rebuild_navicust_tiles(&mut self, rom: &[u8]) -> Result<(), crate::Error> {
+        let view = NavicustView { save: self };
+        let blocks = (0..crate::MAX_NAVICUST_BLOCKS)
+            .map(|i| crate::NavicustView::block(&view, i))
+            .collect::<Vec<_>>();
+        let tiles =
+            crate::build_navicust_tiles(rom, self.game.ncp_data_offset, NAVICUST_SIZE, &blocks)?;
+        let offset = self.navicust_tiles_offset();
+        self.buf[offset..offset + tiles.len()].copy_from_slice(&tiles);
+        self.navicust_dirty = false;
+        Ok(())
+    }
+
+    /// Besides the list of installed modcards, the game keeps a masked flag per modcard of whether it's loaded.
+    fn rebuild_modcards_loaded(&mut self) {
+        let mut loaded = [false; NUM_MODCARDS];
+        for i in 0..self.modcard_count() {
+            if let Some(loaded) = loaded.get_mut((self.buf[MODCARDS_OFFSET + i] & 0x7f) as usize) {
+                *loaded = true;
+            }
+        }
+
+        for (id, loaded) in loaded.iter().enumerate().skip(1) {
+            self.buf[0x5047 + id] = self.buf[0x06bf + id]
+                ^ if *loaded {
+                    match self.game.variant {
+                        Variant::Falzar => 0x8d,
+                        Variant::Gregar => 0x43,
+                    }
+                } else {
+                    0xff
+                };
+        }
+        self.modcards_dirty = false;
+    }
+}
+
+impl crate::Save for Save {
+    fn as_raw(&self) -> &[u8] {
+        &self.buf
+    }
+
+    fn checksum(&self) -> u32 {
+        byteorder::LittleEndian::read_u32(&self.buf[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4])
+    }
+
+    fn compute_checksum(&self) -> u32 {
+        crate::sum_bytes(&self.buf, CHECKSUM_OFFSET).wrapping_add(match self.game.variant {
+            Variant::Falzar => 0x18,
+            Variant::Gregar => 0x72,
+        })
+    }
+
+    fn rebuild_checksum(&mut self) {
+        let checksum = self.compute_checksum();
+        byteorder::LittleEndian::write_u32(
+            &mut self.buf[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4],
+            checksum,
+        );
+    }
+
+    fn rebuild(&mut self, rom: &[u8]) -> Result<(), crate::Error> {
+        if self.navicust_dirty {
+            self.rebuild_navicust_tiles(rom)?;
+        }
+        if self.modcards_dirty {
+            self.rebuild_modcards_loaded();
+        }
+        self.rebuild_checksum();
+        Ok(())
+    }
+
+    fn write_sram_dump(&self, dump: &mut [u8]) {
+        let buf = &mut dump[SRAM_START_OFFSET..SRAM_START_OFFSET + SRAM_SIZE];
+        buf.copy_from_slice(&self.buf);
+        crate::mask(buf, MASK_OFFSET);
+    }
+
+    fn folder_view(&self) -> Option<Box<dyn crate::FolderView + '_>> {
+        Some(Box::new(FolderView { save: self }))
+    }
+
+    fn folder_view_mut(&mut self) -> Option<Box<dyn crate::FolderViewMut + '_>> {
+        Some(Box::new(FolderViewMut { save: self }))
+    }
+
+    fn navicust_view(&self) -> Option<Box<dyn crate::NavicustView + '_>> {
+        Some(Box::new(NavicustView { save: self }))
+    }
+
+    fn navicust_view_mut(&mut self) -> Option<Box<dyn crate::NavicustViewMut + '_>> {
+        Some(Box::new(NavicustViewMut { save: self }))
+    }
+
+    fn modcards_view(&self) -> Option<Box<dyn crate::ModcardsView + '_>> {
+        if self.game.region != Region::JP {
+            return None;
+        }
+        Some(Box::new(ModcardsView { save: self }))
+    }
+
+    fn modcards_view_mut(&mut self) -> Option<Box<dyn crate::ModcardsViewMut + '_>> {
+        if self.game.region != Region::JP {
+            return None;
+        }
+        Some(Box::new(ModcardsViewMut { save: self }))
+    }
+
+    fn library_view(&self) -> Option<Box<dyn crate::LibraryView + '_>> {
+        Some(Box::new(LibraryView { save: self }))
+    }
+}
+
+struct FolderView<'a> {
+    save: &'a Save,
+}
+
+impl<'a> crate::FolderView for FolderView<'a> {
+    fn count(&self) -> usize {
+        self.save.folder_count()
+    }
+
+    fn equipped_folder_index(&self) -> usize {
+        self.save.buf[self.save.navi_stats_offset() + 0x2d] as usize
+    }
+
+    fn regular_chip_index(&self, folder_index: usize) -> Option<usize> {
+        let i = self.save.buf[self.save.navi_stats_offset() + 0x2e + folder_index];
+        if i == 0xff {
+            return None;
+        }
+        Some(i as usize)
+    }
+
+    fn tag_chip_indexes(&self, folder_index: usize) -> Option<[usize; 2]> {
+        let offset = self.save.navi_stats_offset() + 0x56 + folder_index * 2;
+        let (i1, i2) = (self.save.buf[offset], self.save.buf[offset + 1]);
+        if i1 == 0xff || i2 == 0xff {
+            return None;
+        }
+        Some([i1 as usize, i2 as usize])
+    }
+
+    fn chip(&self, folder_index: usize, chip_index: usize) -> Option<crate::Chip> {
+        let offset = 0x2178 + folder_index * (crate::CHIPS_PER_FOLDER * 2) + chip_index * 2;
+        crate::Chip::from_raw(byteorder::LittleEndian::read_u16(
+            &self.save.buf[offset..offset + 2],
+        ))
+    }
+}
+
+struct FolderViewMut<'a> {
+    save: &'a mut Save,
+}
+
+impl<'a> crate::FolderViewMut for FolderViewMut<'a> {
+    fn set_equipped_folder_index(&mut self, folder_index: usize) -> Result<(), crate::Error> {
+        crate::check_range("folder index", folder_index, self.save.folder_count())?;
+        let offset = self.save.navi_stats_offset() + 0x2d;
+        self.save.buf[offset] = folder_index as u8;
+        Ok(())
+    }
+
+    fn set_regular_chip_index(
+        &mut self,
+        folder_index: usize,
+        chip_index: Option<usize>,
+    ) -> Result<(), crate::Error> {
+        crate::check_range("folder index", folder_index, self.save.folder_count())?;
+        if let Some(chip_index) = chip_index {
+            crate::check_range("chip index", chip_index, crate::CHIPS_PER_FOLDER)?;
+        }
+        let offset = self.save.navi_stats_offset() + 0x2e + folder_index;
+        self.save.buf[offset] = chip_index.map(|i| i as u8).unwrap_or(0xff);
+        Ok(())
+    }
+
+    fn set_tag_chip_indexes(
+        &mut self,
+        folder_index: usize,
+        chip_indexes: Option<[usize; 2]>,
+    ) -> Result<(), crate::Error> {
+        crate::check_range("folder index", folder_index, self.save.folder_count())?;
+        if let Some(chip_indexes) = chip_indexes {
+            for chip_index in chip_indexes {
+                crate::check_range("chip index", chip_index, crate::CHIPS_PER_FOLDER)?;
+            }
+        }
+        let offset = self.save.navi_stats_offset() + 0x56 + folder_index * 2;
+        let [i1, i2] = chip_indexes
+            .map(|[i1, i2]| [i1 as u8, i2 as u8])
+            .unwrap_or([0xff, 0xff]);
+        self.save.buf[offset] = i1;
+        self.save.buf[offset + 1] = i2;
+        Ok(())
+    }
+
+    fn set_chip(
+        &mut self,
+        folder_index: usize,
+        chip_index: usize,
+        chip: Option<crate::Chip>,
+    ) -> Result<(), crate::Error> {
+        crate::check_range("folder index", folder_index, self.save.folder_count())?;
+        crate::check_range("chip index", chip_index, crate::CHIPS_PER_FOLDER)?;
+        let raw = chip.map(|chip| chip.to_raw()).transpose()?.unwrap_or(0);
+        let offset = 0x2178 + folder_index * (crate::CHIPS_PER_FOLDER * 2) + chip_index * 2;
+        byteorder::LittleEndian::write_u16(&mut self.save.buf[offset..offset + 2], raw);
+        Ok(())
+    }
+}
+
+struct NavicustView<'a> {
+    save: &'a Save,
+}
+
+impl<'a> crate::NavicustView for NavicustView<'a> {
+    fn width(&self) -> usize {
+        NAVICUST_SIZE
+    }
+
+    fn height(&self) -> usize {
+        NAVICUST_SIZE
+    }
+
+    fn command_line(&self) -> usize {
+        3
+    }
+
+    fn has_out_of_bounds(&self) -> bool {
+        true
+    }
+
+    fn block(&self, i: usize) -> Option<crate::NavicustBlock> {
+        let buf = &self.save.buf[self.save.navicust_offset() + i * 8..][..8];
+        if buf[0] == 0 {
+            return None;
+        }
+
+        Some(crate::NavicustBlock {
+            id: (buf[0] >> 2) as usize,
+            variant: (buf[0] & 0x3) as usize,
+            col: buf[3],
+            row: buf[4],
+            rot: buf[5],
+            compressed: buf[6] != 0,
+        })
+    }
+}
+
+struct NavicustViewMut<'a> {
+    save: &'a mut Save,
+}
+
+impl<'a> crate::NavicustViewMut for NavicustViewMut<'a> {
+    fn set_block(
+        &mut self,
+        i: usize,
+        block: Option<crate::NavicustBlock>,
+    ) -> Result<(), crate::Error> {
+        crate::check_range("navicust block index", i, crate::MAX_NAVICUST_BLOCKS)?;
+        if let Some(block) = block.as_ref() {
+            crate::check_navicust_block(block, NAVICUST_SIZE)?;
+        }
+        let offset = self.save.navicust_offset() + i * 8;
+        let buf = &mut self.save.buf[offset..offset + 8];
+        buf.fill(0);
+        if let Some(block) = block {
+            buf[0] = (block.id << 2 | block.variant) as u8;
+            buf[3] = block.col;
+            buf[4] = block.row;
+            buf[5] = block.rot;
+            buf[6] = block.compressed as u8;
+        }
+        self.save.navicust_dirty = true;
+        Ok(())
+    }
+}
+
+struct ModcardsView<'a> {
+    save: &'a Save,
+}
+
+impl<'a> crate::ModcardsView for ModcardsView<'a> {
+    fn count(&self) -> usize {
+        self.save.modcard_count()
+    }
+
+    fn modcard(&self, i: usize) -> Option<crate::Modcard> {
+        if i >= self.count() {
+            return None;
+        }
+
+        let raw = self.save.buf[MODCARDS_OFFSET + i];
+        Some(crate::Modcard {
+            id: (raw & 0x7f) as usize,
+            enabled: raw >> 7 == 0,
+        })
+    }
+}
+
+struct ModcardsViewMut<'a> {
+    save: &'a mut Save,
+}
+
+impl<'a> crate::ModcardsViewMut for ModcardsViewMut<'a> {
+    fn set_modcards(&mut self, modcards: &[crate::Modcard]) -> Result<(), crate::Error> {
+        crate::check_range("modcard count", modcards.len(), MAX_INSTALLED_MODCARDS + 1)?;
+        for modcard in modcards {
+            crate::check_range("modcard id", modcard.id, NUM_MODCARDS)?;
+        }
+
+        self.save.buf[0x65f0] = modcards.len() as u8;
+        for (i, modcard) in modcards.iter().enumerate() {
+            self.save.buf[MODCARDS_OFFSET + i] =
+                modcard.id as u8 | if modcard.enabled { 0 } else { 0x80 };
+        }
+        self.save.modcards_dirty = true;
+        Ok(())
+    }
+}
+
+struct LibraryView<'a> {
+    save: &'a Save,
+}
+
+impl<'a> crate::LibraryView for LibraryView<'a> {
+    fn num_chips(&self) -> usize {
+        NUM_CHIPS
+    }
+
+    fn count(&self, id: usize, code_slot: usize) -> u8 {
+        self.save.buf[0x2230 + id * 0xc + code_slot]
+    }
+}
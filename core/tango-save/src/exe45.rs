@@ -0,0 +1,152 @@
+use byteorder::ByteOrder;
+
+const SRAM_SIZE: usize = 0xc7a8;
+const MASK_OFFSET: usize = 0x3c84;
+const GAME_NAME_OFFSET: usize = 0x4ba8;
+const CHECKSUM_OFFSET: usize = 0x4b88;
+
+const GAME_NAMES: &[&[u8]] = &[b"ROCKMANEXE4RO 040607", b"ROCKMANEXE4RO 041217"];
+
+const NUM_CHIPS: usize = 389;
+
+#[derive(Clone, Copy)]
+pub struct EXE45 {}
+
+pub static ROCKEXE45ROBR4J_00: EXE45 = EXE45 {};
+
+impl crate::Game for EXE45 {
+    fn family(&self) -> &'static str {
+        "exe45"
+    }
+
+    fn load_save(&self, dump: &[u8]) -> Result<Box<dyn crate::Save + Send + Sync>, crate::Error> {
+        crate::check_size(dump, 0, SRAM_SIZE)?;
+        let mut buf = dump[..SRAM_SIZE].to_vec();
+        crate::mask(&mut buf, MASK_OFFSET);
+
+        let game_name = crate::read_game_name(&buf, GAME_NAME_OFFSET);
+        if !GAME_NAMES.contains(&game_name) {
+            return Err(crate::Error::UnknownGameName(
+                String::from_utf8_lossy(game_name).to_string(),
+            ));
+        }
+
+        let save = Save { buf };
+        crate::check_checksum(&save)?;
+        Ok(Box::new(save))
+    }
+}
+
+pub struct Save {
+    buf: Vec<u8>,
+}
+
+impl Save {
+    fn current_navi(&self) -> usize {
+        self.buf[0x4ad1] as usize
+    }
+
+    /// Each navi has their own folder, and only the current navi's is visible.
+    fn chip_offset(&self, chip_index: usize) -> usize {
+        0x7500 + self.current_navi() * (crate::CHIPS_PER_FOLDER * 2) + chip_index * 2
+    }
+}
+
+impl crate::Save for Save {
+    fn as_raw(&self) -> &[u8] {
+        &self.buf
+    }
+
+    fn checksum(&self) -> u32 {
+        byteorder::LittleEndian::read_u32(&self.buf[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4])
+    }
+
+    fn compute_checksum(&self) -> u32 {
+        crate::sum_bytes(&self.buf, CHECKSUM_OFFSET).wrapping_add(0x38)
+    }
+
+    fn rebuild_checksum(&mut self) {
+        let checksum = self.compute_checksum();
+        byteorder::LittleEndian::write_u32(
+            &mut self.buf[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4],
+            checksum,
+        );
+    }
+
+    fn write_sram_dump(&self, dump: &mut [u8]) {
+        let buf = &mut dump[..SRAM_SIZE];
+        buf.copy_from_slice(&self.buf);
+        crate::mask(buf, MASK_OFFSET);
+    }
+
+    fn folder_view(&self) -> Option<Box<dyn crate::FolderView + '_>> {
+        Some(Box::new(FolderView { save: self }))
+    }
+
+    fn folder_view_mut(&mut self) -> Option<Box<dyn crate::FolderViewMut + '_>> {
+        Some(Box::new(FolderViewMut { save: self }))
+    }
+
+    fn library_view(&self) -> Option<Box<dyn crate::LibraryView + '_>> {
+        Some(Box::new(LibraryView { save: self }))
+    }
+}
+
+struct FolderView<'a> {
+    save: &'a Save,
+}
+
+impl<'a> crate::FolderView for FolderView<'a> {
+    fn count(&self) -> usize {
+        1
+    }
+
+    fn equipped_folder_index(&self) -> usize {
+        0
+    }
+
+    fn chip(&self, _folder_index: usize, chip_index: usize) -> Option<crate::Chip> {
+        let offset = self.save.chip_offset(chip_index);
+        crate::Chip::from_raw(byteorder::LittleEndian::read_u16(
+            &self.save.buf[offset..offset + 2],
+        ))
+    }
+}
+
+struct FolderViewMut<'a> {
+    save: &'a mut Save,
+}
+
+impl<'a> crate::FolderViewMut for FolderViewMut<'a> {
+    fn set_equipped_folder_index(&mut self, folder_index: usize) -> Result<(), crate::Error> {
+        crate::check_range("folder index", folder_index, 1)
+    }
+
+    fn set_chip(
+        &mut self,
+        folder_index: usize,
+        chip_index: usize,
+        chip: Option<crate::Chip>,
+    ) -> Result<(), crate::Error> {
+        crate::check_range("folder index", folder_index, 1)?;
+        crate::check_range("chip index", chip_index, crate::CHIPS_PER_FOLDER)?;
+        let raw = chip.map(|chip| chip.to_raw()).transpose()?.unwrap_or(0);
+        let offset = self.save.chip_offset(chip_index);
+        byteorder::LittleEndian::write_u16(&mut self.save.buf[offset..offset + 2], raw);
+        Ok(())
+    }
+}
+
+struct LibraryView<'a> {
+    save: &'a Save,
+}
+
+impl<'a> crate::LibraryView for LibraryView<'a> {
+    fn num_chips(&self) -> usize {
+        NUM_CHIPS
+    }
+
+    fn count(&self, id: usize, code_slot: usize) -> u8 {
+        self.save.buf[0x52c8 + id * 0xc + code_slot]
+    }
+}
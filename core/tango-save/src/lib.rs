@@ -0,0 +1,449 @@
+use byteorder::ByteOrder;
+
+mod bn1;
+mod bn2;
+mod bn3;
+mod bn4;
+mod bn5;
+mod bn6;
+mod exe45;
+pub mod loadout;
+
+/// Every ROM name and revision that has a save format, in the same order as `hooks::get`.
+pub const SUPPORTED_ROMS: &[(&[u8], u8)] = &[
+    (b"MEGAMAN6_FXXBR6E", 0x00),
+    (b"MEGAMAN6_GXXBR5E", 0x00),
+    (b"ROCKEXE6_RXXBR6J", 0x00),
+    (b"ROCKEXE6_GXXBR5J", 0x00),
+    (b"MEGAMAN5_TP_BRBE", 0x00),
+    (b"MEGAMAN5_TC_BRKE", 0x00),
+    (b"ROCKEXE5_TOBBRBJ", 0x00),
+    (b"ROCKEXE5_TOCBRKJ", 0x00),
+    (b"ROCKEXE4.5ROBR4J", 0x00),
+    (b"MEGAMANBN4BMB4BE", 0x00),
+    (b"MEGAMANBN4RSB4WE", 0x00),
+    (b"ROCK_EXE4_BMB4BJ", 0x00),
+    (b"ROCK_EXE4_BMB4BJ", 0x01),
+    (b"ROCK_EXE4_RSB4WJ", 0x00),
+    (b"ROCK_EXE4_RSB4WJ", 0x01),
+    (b"MEGA_EXE3_BLA3XE", 0x00),
+    (b"MEGA_EXE3_WHA6BE", 0x00),
+    (b"ROCK_EXE3_BKA3XJ", 0x01),
+    (b"ROCKMAN_EXE3A6BJ", 0x01),
+    (b"MEGAMAN_EXE2AE2E", 0x00),
+    (b"ROCKMAN_EXE2AE2J", 0x01),
+    (b"MEGAMAN_BN\0\0AREE", 0x00),
+    (b"ROCKMAN_EXE\0AREJ", 0x00),
+];
+
+pub fn get(rom_name: &[u8], rom_revision: u8) -> Option<&'static (dyn Game + Send + Sync)> {
+    Some(match (rom_name, rom_revision) {
+        (b"MEGAMAN6_FXXBR6E", 0x00) => &bn6::MEGAMAN6_FXXBR6E_00,
+        (b"MEGAMAN6_GXXBR5E", 0x00) => &bn6::MEGAMAN6_GXXBR5E_00,
+        (b"ROCKEXE6_RXXBR6J", 0x00) => &bn6::ROCKEXE6_RXXBR6J_00,
+        (b"ROCKEXE6_GXXBR5J", 0x00) => &bn6::ROCKEXE6_GXXBR5J_00,
+        (b"MEGAMAN5_TP_BRBE", 0x00) => &bn5::MEGAMAN5_TP_BRBE_00,
+        (b"MEGAMAN5_TC_BRKE", 0x00) => &bn5::MEGAMAN5_TC_BRKE_00,
+        (b"ROCKEXE5_TOBBRBJ", 0x00) => &bn5::ROCKEXE5_TOBBRBJ_00,
+        (b"ROCKEXE5_TOCBRKJ", 0x00) => &bn5::ROCKEXE5_TOCBRKJ_00,
+        (b"ROCKEXE4.5ROBR4J", 0x00) => &exe45::ROCKEXE45ROBR4J_00,
+        (b"MEGAMANBN4BMB4BE", 0x00) => &bn4::MEGAMANBN4BMB4BE_00,
+        (b"MEGAMANBN4RSB4WE", 0x00) => &bn4::MEGAMANBN4RSB4WE_00,
+        (b"ROCK_EXE4_BMB4BJ", 0x00) => &bn4::ROCK_EXE4_BMB4BJ_00,
+        (b"ROCK_EXE4_BMB4BJ", 0x01) => &bn4::ROCK_EXE4_BMB4BJ_01,
+        (b"ROCK_EXE4_RSB4WJ", 0x00) => &bn4::ROCK_EXE4_RSB4WJ_00,
+        (b"ROCK_EXE4_RSB4WJ", 0x01) => &bn4::ROCK_EXE4_RSB4WJ_01,
+        (b"MEGA_EXE3_BLA3XE", 0x00) => &bn3::MEGA_EXE3_BLA3XE_00,
+        (b"MEGA_EXE3_WHA6BE", 0x00) => &bn3::MEGA_EXE3_WHA6BE_00,
+        (b"ROCK_EXE3_BKA3XJ", 0x01) => &bn3::ROCK_EXE3_BKA3XJ_01,
+        (b"ROCKMAN_EXE3A6BJ", 0x01) => &bn3::ROCKMAN_EXE3A6BJ_01,
+        (b"MEGAMAN_EXE2AE2E", 0x00) => &bn2::MEGAMAN_EXE2AE2E_00,
+        (b"ROCKMAN_EXE2AE2J", 0x01) => &bn2::ROCKMAN_EXE2AE2J_01,
+        (b"MEGAMAN_BN\0\0AREE", 0x00) => &bn1::MEGAMAN_BNAREE_00,
+        (b"ROCKMAN_EXE\0AREJ", 0x00) => &bn1::ROCKMAN_EXEAREJ_00,
+        _ => {
+            return None;
+        }
+    })
+}
+
+/// Reads the ROM name and revision out of a GBA ROM header, for use with `get`.
+pub fn rom_name_and_revision(rom: &[u8]) -> Option<(&[u8], u8)> {
+    if rom.len() < 0xc0 {
+        return None;
+    }
+    Some((&rom[0xa0..0xb0], rom[0xbc]))
+}
+
+/// Finds every supported ROM that the given SRAM dump loads cleanly for.
+pub fn sniff(dump: &[u8]) -> Vec<(&'static [u8], u8, Box<dyn Save + Send + Sync>)> {
+    SUPPORTED_ROMS
+        .iter()
+        .filter_map(|(rom_name, rom_revision)| {
+            let save = get(rom_name, *rom_revision)?.load_save(dump).ok()?;
+            Some((*rom_name, *rom_revision, save))
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    InvalidSize {
+        expected: usize,
+        actual: usize,
+    },
+    InvalidStartOffset(u32),
+    UnknownGameName(String),
+    ChecksumMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    Unsupported(&'static str),
+    OutOfRange {
+        what: &'static str,
+        value: usize,
+        limit: usize,
+    },
+    MissingRomData(&'static str),
+    InvalidFolderSize(usize),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidSize { expected, actual } => write!(
+                f,
+                "invalid save size: expected at least {:#x} bytes but got {:#x}",
+                expected, actual
+            ),
+            Error::InvalidStartOffset(offset) => write!(f, "invalid start offset: {:#x}", offset),
+            Error::UnknownGameName(name) => write!(f, "unknown game name: {:?}", name),
+            Error::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "checksum mismatch: expected {:08x} but got {:08x}",
+                expected, actual
+            ),
+            Error::Unsupported(what) => write!(f, "{} not supported for this game", what),
+            Error::OutOfRange { what, value, limit } => {
+                write!(f, "{} out of range: {} (limit {})", what, value, limit)
+            }
+            Error::MissingRomData(what) => write!(f, "no {} known for this rom", what),
+            Error::InvalidFolderSize(size) => write!(
+                f,
+                "invalid folder size: expected {} chips but got {}",
+                CHIPS_PER_FOLDER, size
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub trait Game {
+    /// The game family, e.g. `bn6`.
+    fn family(&self) -> &'static str;
+
+    /// Unmasks and validates an SRAM dump as it is stored in a .sav file.
+    fn load_save(&self, dump: &[u8]) -> Result<Box<dyn Save + Send + Sync>, Error>;
+}
+
+pub trait Save {
+    /// The unmasked save data.
+    fn as_raw(&self) -> &[u8];
+
+    /// The checksum stored in the save.
+    fn checksum(&self) -> u32;
+
+    fn compute_checksum(&self) -> u32;
+
+    fn rebuild_checksum(&mut self);
+
+    /// Regenerates everything in the save that is derived from other parts of it, finishing with the checksum.
+    ///
+    /// Some of it, such as the NaviCust tile map, can only be rebuilt with data from the ROM.
+    fn rebuild(&mut self, _rom: &[u8]) -> Result<(), Error> {
+        self.rebuild_checksum();
+        Ok(())
+    }
+
+    /// Masks the save and writes it back into an SRAM dump, leaving the rest of the dump untouched.
+    fn write_sram_dump(&self, dump: &mut [u8]);
+
+    /// `None` for games whose folder layout isn't known, which is the case for BN1-3.
+    fn folder_view(&self) -> Option<Box<dyn FolderView + '_>> {
+        None
+    }
+
+    fn folder_view_mut(&mut self) -> Option<Box<dyn FolderViewMut + '_>> {
+        None
+    }
+
+    fn navicust_view(&self) -> Option<Box<dyn NavicustView + '_>> {
+        None
+    }
+
+    fn navicust_view_mut(&mut self) -> Option<Box<dyn NavicustViewMut + '_>> {
+        None
+    }
+
+    fn modcards_view(&self) -> Option<Box<dyn ModcardsView + '_>> {
+        None
+    }
+
+    fn modcards_view_mut(&mut self) -> Option<Box<dyn ModcardsViewMut + '_>> {
+        None
+    }
+
+    /// `None` for games whose library layout isn't known, which is the case for BN1-3.
+    fn library_view(&self) -> Option<Box<dyn LibraryView + '_>> {
+        None
+    }
+}
+
+pub const CHIP_CODES: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ*";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Chip {
+    pub id: usize,
+    pub code: char,
+}
+
+impl Chip {
+    fn from_raw(raw: u16) -> Option<Self> {
+        if raw == 0 {
+            return None;
+        }
+        Some(Chip {
+            id: (raw & 0x1ff) as usize,
+            code: *CHIP_CODES.get((raw >> 9) as usize)? as char,
+        })
+    }
+
+    fn to_raw(self) -> Result<u16, Error> {
+        if self.id >= 0x200 {
+            return Err(Error::OutOfRange {
+                what: "chip id",
+                value: self.id,
+                limit: 0x200,
+            });
+        }
+        let code = CHIP_CODES
+            .iter()
+            .position(|c| *c as char == self.code)
+            .ok_or(Error::Unsupported("chip code"))?;
+        Ok(self.id as u16 | (code as u16) << 9)
+    }
+}
+
+pub const CHIPS_PER_FOLDER: usize = 30;
+
+pub trait FolderView {
+    fn count(&self) -> usize;
+
+    fn equipped_folder_index(&self) -> usize;
+
+    fn regular_chip_index(&self, _folder_index: usize) -> Option<usize> {
+        None
+    }
+
+    fn tag_chip_indexes(&self, _folder_index: usize) -> Option<[usize; 2]> {
+        None
+    }
+
+    fn chip(&self, folder_index: usize, chip_index: usize) -> Option<Chip>;
+}
+
+pub trait FolderViewMut {
+    fn set_equipped_folder_index(&mut self, folder_index: usize) -> Result<(), Error>;
+
+    fn set_regular_chip_index(
+        &mut self,
+        _folder_index: usize,
+        _chip_index: Option<usize>,
+    ) -> Result<(), Error> {
+        Err(Error::Unsupported("regular chips"))
+    }
+
+    fn set_tag_chip_indexes(
+        &mut self,
+        _folder_index: usize,
+        _chip_indexes: Option<[usize; 2]>,
+    ) -> Result<(), Error> {
+        Err(Error::Unsupported("tag chips"))
+    }
+
+    fn set_chip(
+        &mut self,
+        folder_index: usize,
+        chip_index: usize,
+        chip: Option<Chip>,
+    ) -> Result<(), Error>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct NavicustBlock {
+    pub id: usize,
+    pub variant: usize,
+    pub col: u8,
+    pub row: u8,
+    pub rot: u8,
+    pub compressed: bool,
+}
+
+pub trait NavicustView {
+    fn width(&self) -> usize;
+
+    fn height(&self) -> usize;
+
+    fn command_line(&self) -> usize;
+
+    fn has_out_of_bounds(&self) -> bool;
+
+    fn block(&self, i: usize) -> Option<NavicustBlock>;
+}
+
+/// Edits NaviCust placements. The tile map is left stale until the save is rebuilt.
+pub trait NavicustViewMut {
+    fn set_block(&mut self, i: usize, block: Option<NavicustBlock>) -> Result<(), Error>;
+}
+
+pub const MAX_NAVICUST_BLOCKS: usize = 25;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Modcard {
+    pub id: usize,
+    pub enabled: bool,
+}
+
+pub trait ModcardsView {
+    fn count(&self) -> usize;
+
+    fn modcard(&self, i: usize) -> Option<Modcard>;
+}
+
+/// Edits the installed modcards. The game's own record of which modcards are loaded is left stale until the save is rebuilt.
+pub trait ModcardsViewMut {
+    fn set_modcards(&mut self, modcards: &[Modcard]) -> Result<(), Error>;
+}
+
+/// The chips that have been collected, as counts per chip ID and code slot.
+///
+/// The code slot indexes into the chip's list of codes in the ROM, not into `CHIP_CODES`.
+pub trait LibraryView {
+    fn num_chips(&self) -> usize;
+
+    fn count(&self, id: usize, code_slot: usize) -> u8;
+}
+
+pub const NUM_CHIP_CODE_SLOTS: usize = 4;
+
+fn check_size(dump: &[u8], start: usize, size: usize) -> Result<(), Error> {
+    if dump.len() < start + size {
+        return Err(Error::InvalidSize {
+            expected: start + size,
+            actual: dump.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Flips the save mask in or out. Only the low byte of the mask is used, and the mask itself is left untouched.
+fn mask(buf: &mut [u8], mask_offset: usize) {
+    let mask = byteorder::LittleEndian::read_u32(&buf[mask_offset..mask_offset + 4]);
+    for b in buf.iter_mut() {
+        *b ^= mask as u8;
+    }
+    byteorder::LittleEndian::write_u32(&mut buf[mask_offset..mask_offset + 4], mask);
+}
+
+/// Sums every byte of the save except for the checksum itself.
+fn sum_bytes(buf: &[u8], checksum_offset: usize) -> u32 {
+    buf.iter()
+        .enumerate()
+        .filter(|(i, _)| !(checksum_offset..checksum_offset + 4).contains(i))
+        .fold(0u32, |acc, (_, b)| acc.wrapping_add(*b as u32))
+}
+
+fn read_game_name(buf: &[u8], offset: usize) -> &[u8] {
+    &buf[offset..offset + 20]
+}
+
+fn check_checksum(save: &dyn Save) -> Result<(), Error> {
+    let expected = save.compute_checksum();
+    let actual = save.checksum();
+    if expected != actual {
+        return Err(Error::ChecksumMismatch { expected, actual });
+    }
+    Ok(())
+}
+
+/// Lays NaviCust blocks out into the tile map the game uses to look up what is placed on each square.
+fn build_navicust_tiles(
+    rom: &[u8],
+    ncp_data_offset: usize,
+    size: usize,
+    blocks: &[Option<NavicustBlock>],
+) -> Result<Vec<u8>, Error> {
+    let mut tiles = vec![0u8; size * size];
+    let center = (size / 2) as isize;
+
+    for (idx, block) in blocks.iter().enumerate() {
+        let block = if let Some(block) = block {
+            block
+        } else {
+            continue;
+        };
+
+        let pointer_offset = ncp_data_offset
+            + block.id * 0x40
+            + block.variant * 0x10
+            + if block.compressed { 0xc } else { 0x8 };
+        let shape_offset = rom
+            .get(pointer_offset..pointer_offset + 4)
+            .map(|buf| (byteorder::LittleEndian::read_u32(buf) & !0x08000000) as usize)
+            .ok_or(Error::MissingRomData("navicust program shape"))?;
+        let mut shape = rom
+            .get(shape_offset..shape_offset + size * size)
+            .ok_or(Error::MissingRomData("navicust program shape"))?
+            .to_vec();
+
+        for _ in 0..block.rot {
+            // Rotate clockwise.
+            shape = (0..size * size)
+                .map(|k| shape[(size - 1 - k % size) * size + k / size])
+                .collect();
+        }
+
+        for i in 0..size {
+            for j in 0..size {
+                if shape[i * size + j] == 0 {
+                    continue;
+                }
+                let i2 = i as isize + block.row as isize - center;
+                let j2 = j as isize + block.col as isize - center;
+                if i2 < 0 || j2 < 0 || i2 >= size as isize || j2 >= size as isize {
+                    continue;
+                }
+                tiles[i2 as usize * size + j2 as usize] = idx as u8 + 1;
+            }
+        }
+    }
+
+    Ok(tiles)
+}
+
+fn check_range(what: &'static str, value: usize, limit: usize) -> Result<(), Error> {
+    if value >= limit {
+        return Err(Error::OutOfRange { what, value, limit });
+    }
+    Ok(())
+}
+
+fn check_navicust_block(block: &NavicustBlock, size: usize) -> Result<(), Error> {
+    check_range("navicust program id", block.id, 0x40)?;
+    check_range("navicust program variant", block.variant, 4)?;
+    check_range("navicust column", block.col as usize, size)?;
+    check_range("navicust row", block.row as usize, size)?;
+    check_range("navicust rotation", block.rot as usize, 4)?;
+    Ok(())
+}
@@ -0,0 +1,111 @@
+/// A declarative description of what a save should be set up with.
+///
+/// Anything left out is not touched. The output of `savedump` is also a valid loadout, so a save can be used as a template for another.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Loadout {
+    #[serde(default)]
+    pub folders: Option<Folders>,
+    #[serde(default)]
+    pub navicust: Option<Navicust>,
+    #[serde(default)]
+    pub modcards: Option<Vec<crate::Modcard>>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Folders {
+    #[serde(default)]
+    pub equipped_folder_index: Option<usize>,
+    pub folders: Vec<Folder>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Folder {
+    pub chips: Vec<Option<crate::Chip>>,
+    #[serde(default)]
+    pub regular_chip_index: Option<usize>,
+    #[serde(default)]
+    pub tag_chip_indexes: Option<[usize; 2]>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Navicust {
+    pub blocks: Vec<crate::NavicustBlock>,
+}
+
+/// Clearing something that a game doesn't have is not an error.
+fn ignore_unsupported_if_none<T>(
+    value: Option<T>,
+    r: Result<(), crate::Error>,
+) -> Result<(), crate::Error> {
+    match r {
+        Err(crate::Error::Unsupported(_)) if value.is_none() => Ok(()),
+        r => r,
+    }
+}
+
+/// Applies a loadout to a save.
+///
+/// The save must still be rebuilt afterwards for the game to accept it.
+pub fn apply(save: &mut dyn crate::Save, loadout: &Loadout) -> Result<(), crate::Error> {
+    if let Some(folders) = loadout.folders.as_ref() {
+        let count = save
+            .folder_view()
+            .ok_or(crate::Error::Unsupported("folders"))?
+            .count();
+        if folders.folders.len() > count {
+            return Err(crate::Error::OutOfRange {
+                what: "folder count",
+                value: folders.folders.len(),
+                limit: count,
+            });
+        }
+
+        let mut view = save
+            .folder_view_mut()
+            .ok_or(crate::Error::Unsupported("folders"))?;
+        for (i, folder) in folders.folders.iter().enumerate() {
+            if folder.chips.len() != crate::CHIPS_PER_FOLDER {
+                return Err(crate::Error::InvalidFolderSize(folder.chips.len()));
+            }
+            for (j, chip) in folder.chips.iter().enumerate() {
+                view.set_chip(i, j, *chip)?;
+            }
+            ignore_unsupported_if_none(
+                folder.regular_chip_index,
+                view.set_regular_chip_index(i, folder.regular_chip_index),
+            )?;
+            ignore_unsupported_if_none(
+                folder.tag_chip_indexes,
+                view.set_tag_chip_indexes(i, folder.tag_chip_indexes),
+            )?;
+        }
+        if let Some(equipped_folder_index) = folders.equipped_folder_index {
+            view.set_equipped_folder_index(equipped_folder_index)?;
+        }
+    }
+
+    if let Some(navicust) = loadout.navicust.as_ref() {
+        if navicust.blocks.len() > crate::MAX_NAVICUST_BLOCKS {
+            return Err(crate::Error::OutOfRange {
+                what: "navicust block count",
+                value: navicust.blocks.len(),
+                limit: crate::MAX_NAVICUST_BLOCKS,
+            });
+        }
+
+        let mut view = save
+            .navicust_view_mut()
+            .ok_or(crate::Error::Unsupported("navicust"))?;
+        for i in 0..crate::MAX_NAVICUST_BLOCKS {
+            view.set_block(i, navicust.blocks.get(i).copied())?;
+        }
+    }
+
+    if let Some(modcards) = loadout.modcards.as_ref() {
+        save.modcards_view_mut()
+            .ok_or(crate::Error::Unsupported("modcards"))?
+            .set_modcards(modcards)?;
+    }
+
+    Ok(())
+}
@@ -1,3 +1,5 @@
+mod metrics;
+mod ratelimit;
 mod signaling;
 use envconfig::Envconfig;
 use routerify::ext::RequestExt;
@@ -6,10 +8,63 @@ use routerify::ext::RequestExt;
 struct Config {
     #[envconfig(from = "LISTEN_ADDR", default = "[::]:1984")]
     listen_addr: String,
+
+    #[envconfig(from = "SESSION_TIMEOUT_SECS", default = "300")]
+    session_timeout_secs: u64,
+
+    #[envconfig(from = "RATE_LIMIT_PER_MINUTE", default = "30")]
+    rate_limit_per_minute: u32,
+
+    #[envconfig(from = "RATE_LIMIT_BURST", default = "10")]
+    rate_limit_burst: u32,
+
+    /// If the server is behind a reverse proxy, rate limits should apply to the address the proxy says the request came from.
+    #[envconfig(from = "USE_X_FORWARDED_FOR", default = "false")]
+    use_x_forwarded_for: bool,
+
+    /// How many proxies in front of the server append to X-Forwarded-For. Only the entries they appended can be trusted: anything further left was sent by the client.
+    #[envconfig(from = "TRUSTED_PROXY_HOPS", default = "1")]
+    trusted_proxy_hops: usize,
 }
 
 struct State {
     signaling_server: std::sync::Arc<signaling::Server>,
+    metrics: std::sync::Arc<metrics::Metrics>,
+    rate_limiter: ratelimit::RateLimiter,
+    use_x_forwarded_for: bool,
+    trusted_proxy_hops: usize,
+}
+
+fn client_ip(
+    request: &hyper::Request<hyper::Body>,
+    use_x_forwarded_for: bool,
+    trusted_proxy_hops: usize,
+) -> std::net::IpAddr {
+    if use_x_forwarded_for && trusted_proxy_hops > 0 {
+        // Each proxy appends the address it got the request from, so the client's address is the one our outermost trusted proxy appended.
+        if let Some(ip) = request
+            .headers()
+            .get("X-Forwarded-For")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.rsplit(',').nth(trusted_proxy_hops - 1))
+            .and_then(|v| v.trim().parse().ok())
+        {
+            return ip;
+        }
+    }
+    request.remote_addr().ip()
+}
+
+async fn handle_metrics_request(
+    request: hyper::Request<hyper::Body>,
+) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+    let state = request.data::<State>().unwrap();
+    let body = state
+        .metrics
+        .render(state.signaling_server.num_active_sessions().await);
+    Ok(hyper::Response::builder()
+        .header(hyper::header::CONTENT_TYPE, "text/plain; version=0.0.4")
+        .body(body.into())?)
 }
 
 async fn handle_signaling_request(
@@ -26,6 +81,28 @@ async fn handle_signaling_request(
             )?);
     }
 
+    let state = request.data::<State>().unwrap();
+    let ip = client_ip(
+        &request,
+        state.use_x_forwarded_for,
+        state.trusted_proxy_hops,
+    );
+    if !state.rate_limiter.check(ip) {
+        log::info!("rate limited: {}", ip);
+        state
+            .metrics
+            .rate_limited
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        return Ok(hyper::Response::builder()
+            .status(hyper::StatusCode::TOO_MANY_REQUESTS)
+            .body(
+                hyper::StatusCode::TOO_MANY_REQUESTS
+                    .canonical_reason()
+                    .unwrap()
+                    .into(),
+            )?);
+    }
+
     let (response, websocket) = hyper_tungstenite::upgrade(
         &mut request,
         Some(tungstenite::protocol::WebSocketConfig {
@@ -52,13 +129,25 @@ async fn handle_signaling_request(
     Ok(response)
 }
 
-fn router() -> routerify::Router<hyper::Body, anyhow::Error> {
+fn router(config: &Config) -> routerify::Router<hyper::Body, anyhow::Error> {
+    let metrics = std::sync::Arc::new(metrics::Metrics::new());
     routerify::Router::builder()
         .data(State {
-            signaling_server: std::sync::Arc::new(signaling::Server::new()),
+            signaling_server: std::sync::Arc::new(signaling::Server::new(
+                std::time::Duration::from_secs(config.session_timeout_secs),
+                metrics.clone(),
+            )),
+            metrics,
+            rate_limiter: ratelimit::RateLimiter::new(
+                config.rate_limit_per_minute,
+                config.rate_limit_burst,
+            ),
+            use_x_forwarded_for: config.use_x_forwarded_for,
+            trusted_proxy_hops: config.trusted_proxy_hops,
         })
         .get("/", handle_signaling_request)
         .get("/signaling", handle_signaling_request)
+        .get("/metrics", handle_metrics_request)
         .build()
         .unwrap()
 }
@@ -71,7 +160,7 @@ async fn main() -> anyhow::Result<()> {
     log::info!("welcome to tango-server {}!", git_version::git_version!());
     let config = Config::init_from_env().unwrap();
     let addr = config.listen_addr.parse()?;
-    let router = router();
+    let router = router(&config);
     let service = routerify::RouterService::new(router).unwrap();
     hyper::Server::bind(&addr).serve(service).await?;
     Ok(())
@@ -0,0 +1,84 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counters exported in the Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    pub connections: AtomicU64,
+    pub handshakes_completed: AtomicU64,
+    pub sessions_expired: AtomicU64,
+    pub sessions_full: AtomicU64,
+    pub rate_limited: AtomicU64,
+    pub errors: AtomicU64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn render(&self, active_sessions: usize) -> String {
+        let mut buf = String::new();
+
+        writeln!(
+            buf,
+            "# HELP tango_signaling_active_sessions Number of sessions currently open."
+        )
+        .unwrap();
+        writeln!(buf, "# TYPE tango_signaling_active_sessions gauge").unwrap();
+        writeln!(buf, "tango_signaling_active_sessions {}", active_sessions).unwrap();
+
+        writeln!(
+            buf,
+            "# HELP tango_signaling_connections_total Number of signaling connections accepted."
+        )
+        .unwrap();
+        writeln!(buf, "# TYPE tango_signaling_connections_total counter").unwrap();
+        writeln!(
+            buf,
+            "tango_signaling_connections_total {}",
+            self.connections.load(Ordering::Relaxed)
+        )
+        .unwrap();
+
+        writeln!(
+            buf,
+            "# HELP tango_signaling_handshakes_completed_total Number of offers that were answered."
+        )
+        .unwrap();
+        writeln!(
+            buf,
+            "# TYPE tango_signaling_handshakes_completed_total counter"
+        )
+        .unwrap();
+        writeln!(
+            buf,
+            "tango_signaling_handshakes_completed_total {}",
+            self.handshakes_completed.load(Ordering::Relaxed)
+        )
+        .unwrap();
+
+        writeln!(
+            buf,
+            "# HELP tango_signaling_failures_total Number of signaling attempts that failed, by reason."
+        )
+        .unwrap();
+        writeln!(buf, "# TYPE tango_signaling_failures_total counter").unwrap();
+        for (reason, counter) in [
+            ("session_expired", &self.sessions_expired),
+            ("session_full", &self.sessions_full),
+            ("rate_limited", &self.rate_limited),
+            ("error", &self.errors),
+        ] {
+            writeln!(
+                buf,
+                "tango_signaling_failures_total{{reason=\"{}\"}} {}",
+                reason,
+                counter.load(Ordering::Relaxed)
+            )
+            .unwrap();
+        }
+
+        buf
+    }
+}
@@ -0,0 +1,93 @@
+struct Bucket {
+    tokens: f64,
+    last_refill: std::time::Instant,
+
+    /// When the address last connected. Unlike the last refill, pruning doesn't touch this.
+    last_seen: std::time::Instant,
+}
+
+/// Limits how often each IP address may connect, using a token bucket per address.
+pub struct RateLimiter {
+    per_second: f64,
+    burst: f64,
+    buckets: parking_lot::Mutex<Buckets>,
+}
+
+struct Buckets {
+    by_addr: std::collections::HashMap<std::net::IpAddr, Bucket>,
+    last_pruned: std::time::Instant,
+}
+
+/// How often buckets that have filled back up are dropped, as they are no different from a new bucket.
+const PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// The most buckets to keep, no matter how many addresses are connecting.
+///
+/// Past this, the least recently used half are dropped all at once, so that a flood from many addresses neither grows memory without bound nor makes every check scan every bucket.
+const MAX_BUCKETS: usize = 100000;
+
+impl RateLimiter {
+    pub fn new(per_minute: u32, burst: u32) -> Self {
+        Self {
+            per_second: per_minute as f64 / 60.0,
+            burst: burst as f64,
+            buckets: parking_lot::Mutex::new(Buckets {
+                by_addr: std::collections::HashMap::new(),
+                last_pruned: std::time::Instant::now(),
+            }),
+        }
+    }
+
+    fn refill(&self, bucket: &mut Bucket, now: std::time::Instant) {
+        bucket.tokens = (bucket.tokens
+            + (now - bucket.last_refill).as_secs_f64() * self.per_second)
+            .min(self.burst);
+        bucket.last_refill = now;
+    }
+
+    /// Takes a token for the given address, returning false if it has none left.
+    pub fn check(&self, addr: std::net::IpAddr) -> bool {
+        let now = std::time::Instant::now();
+        let mut buckets = self.buckets.lock();
+
+        if now - buckets.last_pruned >= PRUNE_INTERVAL {
+            buckets.by_addr.retain(|_, bucket| {
+                self.refill(bucket, now);
+                bucket.tokens < self.burst
+            });
+            buckets.last_pruned = now;
+        }
+
+        if buckets.by_addr.len() >= MAX_BUCKETS {
+            // Ties are broken by address, so that no more than half are dropped even if many were last seen at the same time.
+            let mut by_last_seen = buckets
+                .by_addr
+                .iter()
+                .map(|(addr, bucket)| (bucket.last_seen, *addr))
+                .collect::<Vec<_>>();
+            let mid = by_last_seen.len() / 2;
+            by_last_seen.select_nth_unstable(mid);
+            for (_, addr) in &by_last_seen[..mid] {
+                buckets.by_addr.remove(addr);
+            }
+            log::warn!(
+                "too many rate limit buckets, dropped the least recently used down to {}",
+                buckets.by_addr.len()
+            );
+        }
+
+        let bucket = buckets.by_addr.entry(addr).or_insert_with(|| Bucket {
+            tokens: self.burst,
+            last_refill: now,
+            last_seen: now,
+        });
+        self.refill(bucket, now);
+        bucket.last_seen = now;
+
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+}
@@ -1,14 +1,22 @@
 use futures_util::{SinkExt, StreamExt, TryStreamExt};
 use prost::Message;
 
+use crate::metrics;
+
+type Sink = futures_util::stream::SplitSink<
+    hyper_tungstenite::WebSocketStream<hyper::upgrade::Upgraded>,
+    tungstenite::Message,
+>;
+
 struct Session {
     offer_sdp: String,
-    sinks: Vec<
-        futures_util::stream::SplitSink<
-            hyper_tungstenite::WebSocketStream<hyper::upgrade::Upgraded>,
-            tungstenite::Message,
-        >,
-    >,
+
+    /// Candidates from the offerer that arrived before anyone else joined, to be sent along with the offer.
+    pending_candidates: Vec<tango_protos::signaling::packet::IceCandidate>,
+
+    /// The answerer rolls back its own offer when it joins, so any candidates it sends before its answer are for a description that no longer exists.
+    answered: bool,
+    sinks: Vec<Sink>,
 }
 
 pub struct Server {
@@ -17,34 +25,91 @@ pub struct Server {
             std::collections::HashMap<String, std::sync::Arc<tokio::sync::Mutex<Session>>>,
         >,
     >,
+    session_timeout: std::time::Duration,
+    metrics: std::sync::Arc<metrics::Metrics>,
+}
+
+async fn send_error(
+    sink: &mut Sink,
+    reason: tango_protos::signaling::packet::error::Reason,
+) -> anyhow::Result<()> {
+    sink.send(tungstenite::Message::Binary(
+        tango_protos::signaling::Packet {
+            which: Some(tango_protos::signaling::packet::Which::Error(
+                tango_protos::signaling::packet::Error {
+                    reason: reason as i32,
+                },
+            )),
+        }
+        .encode_to_vec(),
+    ))
+    .await?;
+    sink.close().await?;
+    Ok(())
 }
 
 impl Server {
-    pub fn new() -> Server {
+    pub fn new(
+        session_timeout: std::time::Duration,
+        metrics: std::sync::Arc<metrics::Metrics>,
+    ) -> Server {
         Server {
             sessions: std::sync::Arc::new(
                 tokio::sync::Mutex::new(std::collections::HashMap::new()),
             ),
+            session_timeout,
+            metrics,
         }
     }
 
+    pub async fn num_active_sessions(&self) -> usize {
+        self.sessions.lock().await.len()
+    }
+
     pub async fn handle_stream(
         &self,
         ws: hyper_tungstenite::WebSocketStream<hyper::upgrade::Upgraded>,
     ) -> anyhow::Result<()> {
+        self.metrics
+            .connections
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
         let (tx, mut rx) = ws.split();
-        let session_id_for_cleanup = std::sync::Arc::new(tokio::sync::Mutex::new(None));
+        let session_for_cleanup = std::sync::Arc::new(tokio::sync::Mutex::new(None));
+
+        // Nobody should need more than this long to finish signaling, so we don't keep half-finished sessions around forever.
+        let deadline = tokio::time::Instant::now() + self.session_timeout;
 
         let r = {
             let sessions = self.sessions.clone();
-            let session_id_for_cleanup = session_id_for_cleanup.clone();
+            let session_for_cleanup = session_for_cleanup.clone();
+            let metrics = self.metrics.clone();
             (move || async move {
                 let mut session = None;
                 let mut tx = Some(tx);
                 let mut me: usize = 0;
 
                 loop {
-                    let msg = match rx.try_next().await? {
+                    let msg = match tokio::time::timeout_at(deadline, rx.try_next()).await {
+                        Ok(msg) => msg?,
+                        Err(_) => {
+                            log::info!("session expired");
+                            metrics
+                                .sessions_expired
+                                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                            let reason =
+                                tango_protos::signaling::packet::error::Reason::SessionExpired;
+                            if let Some(session) = session.as_ref() {
+                                let mut session = session.lock().await;
+                                send_error(&mut session.sinks[me], reason).await?;
+                            } else if let Some(tx) = tx.as_mut() {
+                                send_error(tx, reason).await?;
+                            }
+                            break;
+                        }
+                    };
+
+                    let msg = match msg {
                         Some(tungstenite::Message::Binary(d)) => {
                             tango_protos::signaling::Packet::decode(bytes::Bytes::from(d))?
                         }
@@ -58,41 +123,51 @@ impl Server {
                     log::debug!("received message: {:?}", msg);
                     match msg.which {
                         Some(tango_protos::signaling::packet::Which::Start(start)) => {
+                            if session.is_some() {
+                                anyhow::bail!("attempted to start twice");
+                            }
+
                             let mut sessions = sessions.lock().await;
-                            session =
-                                Some(if let Some(session) = sessions.remove(&start.session_id) {
-                                    session
-                                } else {
-                                    sessions
-                                        .entry(start.session_id.clone())
-                                        .or_insert_with(|| {
-                                            std::sync::Arc::new(tokio::sync::Mutex::new(Session {
-                                                offer_sdp: start.offer_sdp.clone(),
-                                                sinks: vec![],
-                                            }))
-                                        })
-                                        .clone()
-                                });
-
-                            let session = if let Some(session) = session.as_ref() {
-                                session
-                            } else {
-                                anyhow::bail!("no such session");
-                            };
-                            let mut session = session.lock().await;
-                            *session_id_for_cleanup.lock().await = Some(start.session_id.clone());
-                            let offer_sdp = session.offer_sdp.to_string();
+                            let s = sessions
+                                .entry(start.session_id.clone())
+                                .or_insert_with(|| {
+                                    std::sync::Arc::new(tokio::sync::Mutex::new(Session {
+                                        offer_sdp: start.offer_sdp.clone(),
+                                        pending_candidates: vec![],
+                                        answered: false,
+                                        sinks: vec![],
+                                    }))
+                                })
+                                .clone();
 
-                            me = session.sinks.len();
-                            let tx = if let Some(tx) = tx.take() {
+                            let mut tx = if let Some(tx) = tx.take() {
                                 tx
                             } else {
                                 anyhow::bail!("attempted to take tx twice");
                             };
-                            session.sinks.push(tx);
+
+                            let mut s_guard = s.lock().await;
+                            if s_guard.sinks.len() >= 2 {
+                                log::info!("rejecting third client for session");
+                                metrics
+                                    .sessions_full
+                                    .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                                send_error(
+                                    &mut tx,
+                                    tango_protos::signaling::packet::error::Reason::SessionFull,
+                                )
+                                .await?;
+                                return Ok(());
+                            }
+
+                            *session_for_cleanup.lock().await = Some((start.session_id.clone(), s.clone()));
+                            let offer_sdp = s_guard.offer_sdp.to_string();
+
+                            me = s_guard.sinks.len();
+                            s_guard.sinks.push(tx);
 
                             if me == 1 {
-                                session.sinks[me]
+                                s_guard.sinks[me]
                                     .send(tungstenite::Message::Binary(
                                         tango_protos::signaling::Packet {
                                             which: Some(
@@ -106,7 +181,27 @@ impl Server {
                                         .encode_to_vec(),
                                     ))
                                     .await?;
+
+                                for ice_candidate in
+                                    std::mem::take(&mut s_guard.pending_candidates)
+                                {
+                                    s_guard.sinks[me]
+                                        .send(tungstenite::Message::Binary(
+                                            tango_protos::signaling::Packet {
+                                                which: Some(
+                                                    tango_protos::signaling::packet::Which::IceCandidate(
+                                                        ice_candidate,
+                                                    ),
+                                                ),
+                                            }
+                                            .encode_to_vec(),
+                                        ))
+                                        .await?;
+                                }
                             }
+
+                            drop(s_guard);
+                            session = Some(s);
                         }
                         Some(tango_protos::signaling::packet::Which::Offer(_)) => {
                             anyhow::bail!(
@@ -121,6 +216,7 @@ impl Server {
                                 }
                             };
                             let mut session = session.lock().await;
+                            session.answered = true;
                             session.sinks[0]
                                 .send(tungstenite::Message::Binary(
                                     tango_protos::signaling::Packet {
@@ -135,6 +231,9 @@ impl Server {
                                     .encode_to_vec(),
                                 ))
                                 .await?;
+                            metrics
+                                .handshakes_completed
+                                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
                         }
                         Some(tango_protos::signaling::packet::Which::IceCandidate(
                             ice_candidate,
@@ -146,21 +245,34 @@ impl Server {
                                 }
                             };
                             let mut session = session.lock().await;
-                            session.sinks[1 - me]
+                            if me == 1 && !session.answered {
+                                log::debug!("dropping ice candidate sent before answer");
+                                continue;
+                            }
+                            if session.sinks.len() < 2 {
+                                session.pending_candidates.push(ice_candidate);
+                                continue;
+                            }
+
+                            // The other side may have already hung up once it connected, which is fine.
+                            if let Err(e) = session.sinks[1 - me]
                                 .send(tungstenite::Message::Binary(
                                     tango_protos::signaling::Packet {
                                         which: Some(
                                             tango_protos::signaling::packet::Which::IceCandidate(
-                                                tango_protos::signaling::packet::IceCandidate {
-                                                    candidate: ice_candidate.candidate,
-                                                    mid: ice_candidate.mid,
-                                                },
+                                                ice_candidate,
                                             ),
                                         ),
                                     }
                                     .encode_to_vec(),
                                 ))
-                                .await?;
+                                .await
+                            {
+                                log::info!("failed to relay ice candidate: {}", e);
+                            }
+                        }
+                        Some(tango_protos::signaling::packet::Which::Error(_)) => {
+                            anyhow::bail!("received error from client: only the server may send errors");
                         }
                         p => anyhow::bail!("unknown packet: {:?}", p),
                     }
@@ -170,9 +282,22 @@ impl Server {
             .await
         };
 
-        if let Some(session_id) = &*session_id_for_cleanup.lock().await {
+        if r.is_err() {
+            self.metrics
+                .errors
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        }
+
+        if let Some((session_id, session)) = &*session_for_cleanup.lock().await {
+            // The session may have already been cleaned up by the other side and replaced by a new session with the same ID, which we must leave alone.
             let mut sessions = self.sessions.lock().await;
-            sessions.remove(session_id);
+            if sessions
+                .get(session_id)
+                .map(|s| std::sync::Arc::ptr_eq(s, session))
+                .unwrap_or(false)
+            {
+                sessions.remove(session_id);
+            }
         }
 
         r
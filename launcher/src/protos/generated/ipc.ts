@@ -9,6 +9,13 @@ export enum ExitCode {
   EXIT_CODE_LOST_CONNECTION = 10,
   EXIT_CODE_PROTOCOL_VERSION_TOO_OLD = 11,
   EXIT_CODE_PROTOCOL_VERSION_TOO_NEW = 12,
+  EXIT_CODE_DESYNC = 13,
+  EXIT_CODE_PATCH_MISMATCH = 14,
+  EXIT_CODE_MATCH_TYPE_MISMATCH = 15,
+  EXIT_CODE_INCOMPATIBLE_GAMES = 16,
+  EXIT_CODE_GAME_MISMATCH = 17,
+  EXIT_CODE_INVALID_COMMITMENT = 18,
+  EXIT_CODE_OPPONENT_LEFT = 19,
   EXIT_CODE_RUST_PANIC = 101,
   UNRECOGNIZED = -1,
 }
@@ -27,6 +34,27 @@ export function exitCodeFromJSON(object: any): ExitCode {
     case 12:
     case "EXIT_CODE_PROTOCOL_VERSION_TOO_NEW":
       return ExitCode.EXIT_CODE_PROTOCOL_VERSION_TOO_NEW;
+    case 13:
+    case "EXIT_CODE_DESYNC":
+      return ExitCode.EXIT_CODE_DESYNC;
+    case 14:
+    case "EXIT_CODE_PATCH_MISMATCH":
+      return ExitCode.EXIT_CODE_PATCH_MISMATCH;
+    case 15:
+    case "EXIT_CODE_MATCH_TYPE_MISMATCH":
+      return ExitCode.EXIT_CODE_MATCH_TYPE_MISMATCH;
+    case 16:
+    case "EXIT_CODE_INCOMPATIBLE_GAMES":
+      return ExitCode.EXIT_CODE_INCOMPATIBLE_GAMES;
+    case 17:
+    case "EXIT_CODE_GAME_MISMATCH":
+      return ExitCode.EXIT_CODE_GAME_MISMATCH;
+    case 18:
+    case "EXIT_CODE_INVALID_COMMITMENT":
+      return ExitCode.EXIT_CODE_INVALID_COMMITMENT;
+    case 19:
+    case "EXIT_CODE_OPPONENT_LEFT":
+      return ExitCode.EXIT_CODE_OPPONENT_LEFT;
     case 101:
     case "EXIT_CODE_RUST_PANIC":
       return ExitCode.EXIT_CODE_RUST_PANIC;
@@ -47,6 +75,20 @@ export function exitCodeToJSON(object: ExitCode): string {
       return "EXIT_CODE_PROTOCOL_VERSION_TOO_OLD";
     case ExitCode.EXIT_CODE_PROTOCOL_VERSION_TOO_NEW:
       return "EXIT_CODE_PROTOCOL_VERSION_TOO_NEW";
+    case ExitCode.EXIT_CODE_DESYNC:
+      return "EXIT_CODE_DESYNC";
+    case ExitCode.EXIT_CODE_PATCH_MISMATCH:
+      return "EXIT_CODE_PATCH_MISMATCH";
+    case ExitCode.EXIT_CODE_MATCH_TYPE_MISMATCH:
+      return "EXIT_CODE_MATCH_TYPE_MISMATCH";
+    case ExitCode.EXIT_CODE_INCOMPATIBLE_GAMES:
+      return "EXIT_CODE_INCOMPATIBLE_GAMES";
+    case ExitCode.EXIT_CODE_GAME_MISMATCH:
+      return "EXIT_CODE_GAME_MISMATCH";
+    case ExitCode.EXIT_CODE_INVALID_COMMITMENT:
+      return "EXIT_CODE_INVALID_COMMITMENT";
+    case ExitCode.EXIT_CODE_OPPONENT_LEFT:
+      return "EXIT_CODE_OPPONENT_LEFT";
     case ExitCode.EXIT_CODE_RUST_PANIC:
       return "EXIT_CODE_RUST_PANIC";
     default:
@@ -59,6 +101,11 @@ export interface FromCoreMessage {
   smuggleEv: FromCoreMessage_SmuggleEvent | undefined;
   connectionQualityEv: FromCoreMessage_ConnectionQualityEvent | undefined;
   roundEndedEv: FromCoreMessage_RoundEndedEvent | undefined;
+  desyncEv: FromCoreMessage_DesyncEvent | undefined;
+  roundStatsEv: FromCoreMessage_RoundStatsEvent | undefined;
+  reconnectingEv: FromCoreMessage_ReconnectingEvent | undefined;
+  reconnectedEv: FromCoreMessage_ReconnectedEvent | undefined;
+  goodbyeEv: FromCoreMessage_GoodbyeEvent | undefined;
 }
 
 export interface FromCoreMessage_StateEvent {
@@ -131,9 +178,106 @@ export interface FromCoreMessage_RoundEndedEvent {
   replayFilename: string;
 }
 
+export interface FromCoreMessage_DesyncEvent {
+  roundNumber: number;
+  tick: number;
+  lastMatchingTick: number;
+}
+
+export interface FromCoreMessage_RoundStatsEvent {
+  roundNumber: number;
+  inputDelay: number;
+  remoteInputDelay: number;
+  numRttSamples: number;
+  rttP50: number;
+  rttP90: number;
+  rttP99: number;
+  jitter: number;
+  numRollbacks: number;
+  framesResimulated: number;
+  maxPredictionDepth: number;
+  tpsAdjustments: FromCoreMessage_RoundStatsEvent_TpsAdjustment[];
+  maxRollbackDepth: number;
+}
+
+export interface FromCoreMessage_RoundStatsEvent_TpsAdjustment {
+  tick: number;
+  adjustment: number;
+}
+
+export interface FromCoreMessage_ReconnectingEvent {}
+
+export interface FromCoreMessage_ReconnectedEvent {}
+
+/** The opponent left the match on purpose, rather than losing their connection. */
+export interface FromCoreMessage_GoodbyeEvent {
+  reason: FromCoreMessage_GoodbyeEvent_Reason;
+}
+
+export enum FromCoreMessage_GoodbyeEvent_Reason {
+  UNKNOWN = 0,
+  FORFEIT = 1,
+  USER_QUIT = 2,
+  DESYNC = 3,
+  VERSION_ERROR = 4,
+  CRASH = 5,
+  UNRECOGNIZED = -1,
+}
+
+export function fromCoreMessage_GoodbyeEvent_ReasonFromJSON(
+  object: any
+): FromCoreMessage_GoodbyeEvent_Reason {
+  switch (object) {
+    case 0:
+    case "UNKNOWN":
+      return FromCoreMessage_GoodbyeEvent_Reason.UNKNOWN;
+    case 1:
+    case "FORFEIT":
+      return FromCoreMessage_GoodbyeEvent_Reason.FORFEIT;
+    case 2:
+    case "USER_QUIT":
+      return FromCoreMessage_GoodbyeEvent_Reason.USER_QUIT;
+    case 3:
+    case "DESYNC":
+      return FromCoreMessage_GoodbyeEvent_Reason.DESYNC;
+    case 4:
+    case "VERSION_ERROR":
+      return FromCoreMessage_GoodbyeEvent_Reason.VERSION_ERROR;
+    case 5:
+    case "CRASH":
+      return FromCoreMessage_GoodbyeEvent_Reason.CRASH;
+    case -1:
+    case "UNRECOGNIZED":
+    default:
+      return FromCoreMessage_GoodbyeEvent_Reason.UNRECOGNIZED;
+  }
+}
+
+export function fromCoreMessage_GoodbyeEvent_ReasonToJSON(
+  object: FromCoreMessage_GoodbyeEvent_Reason
+): string {
+  switch (object) {
+    case FromCoreMessage_GoodbyeEvent_Reason.UNKNOWN:
+      return "UNKNOWN";
+    case FromCoreMessage_GoodbyeEvent_Reason.FORFEIT:
+      return "FORFEIT";
+    case FromCoreMessage_GoodbyeEvent_Reason.USER_QUIT:
+      return "USER_QUIT";
+    case FromCoreMessage_GoodbyeEvent_Reason.DESYNC:
+      return "DESYNC";
+    case FromCoreMessage_GoodbyeEvent_Reason.VERSION_ERROR:
+      return "VERSION_ERROR";
+    case FromCoreMessage_GoodbyeEvent_Reason.CRASH:
+      return "CRASH";
+    default:
+      return "UNKNOWN";
+  }
+}
+
 export interface ToCoreMessage {
   startReq: ToCoreMessage_StartRequest | undefined;
   smuggleReq: ToCoreMessage_SmuggleRequest | undefined;
+  forfeitReq: ToCoreMessage_ForfeitRequest | undefined;
 }
 
 export interface ToCoreMessage_StartRequest {
@@ -142,6 +286,9 @@ export interface ToCoreMessage_StartRequest {
   savePath: string;
   windowScale: number;
   settings: ToCoreMessage_StartRequest_MatchSettings | undefined;
+  patchPath?: string | undefined;
+  /** Which games this one can play against, as the launcher knows it: a patch can move its game out of its base ROM's family. */
+  netplayCompatibility?: string | undefined;
 }
 
 export interface ToCoreMessage_StartRequest_MatchSettings {
@@ -153,21 +300,35 @@ export interface ToCoreMessage_StartRequest_MatchSettings {
   matchSubtype: number;
   replaysPath: string;
   replayMetadata: Uint8Array;
+  /** Unused: the RNG seed is now agreed on with the opponent by the core. */
   rngSeed: Uint8Array;
   opponentNickname?: string | undefined;
   maxQueueLength: number;
+  spectatorListenAddr?: string | undefined;
+  autoInputDelay: boolean;
+  shadowPatchPath?: string | undefined;
+  /** Where this player's replay signing key is kept. It is created if it doesn't exist yet. */
+  signingKeyPath?: string | undefined;
 }
 
 export interface ToCoreMessage_SmuggleRequest {
   data: Uint8Array;
 }
 
+/** Gives up the match in progress: the opponent is told we forfeited, and the round's replay records it. */
+export interface ToCoreMessage_ForfeitRequest {}
+
 function createBaseFromCoreMessage(): FromCoreMessage {
   return {
     stateEv: undefined,
     smuggleEv: undefined,
     connectionQualityEv: undefined,
     roundEndedEv: undefined,
+    desyncEv: undefined,
+    roundStatsEv: undefined,
+    reconnectingEv: undefined,
+    reconnectedEv: undefined,
+    goodbyeEv: undefined,
   };
 }
 
@@ -200,6 +361,36 @@ export const FromCoreMessage = {
         writer.uint32(34).fork()
       ).ldelim();
     }
+    if (message.desyncEv !== undefined) {
+      FromCoreMessage_DesyncEvent.encode(
+        message.desyncEv,
+        writer.uint32(42).fork()
+      ).ldelim();
+    }
+    if (message.roundStatsEv !== undefined) {
+      FromCoreMessage_RoundStatsEvent.encode(
+        message.roundStatsEv,
+        writer.uint32(50).fork()
+      ).ldelim();
+    }
+    if (message.reconnectingEv !== undefined) {
+      FromCoreMessage_ReconnectingEvent.encode(
+        message.reconnectingEv,
+        writer.uint32(58).fork()
+      ).ldelim();
+    }
+    if (message.reconnectedEv !== undefined) {
+      FromCoreMessage_ReconnectedEvent.encode(
+        message.reconnectedEv,
+        writer.uint32(66).fork()
+      ).ldelim();
+    }
+    if (message.goodbyeEv !== undefined) {
+      FromCoreMessage_GoodbyeEvent.encode(
+        message.goodbyeEv,
+        writer.uint32(74).fork()
+      ).ldelim();
+    }
     return writer;
   },
 
@@ -235,6 +426,36 @@ export const FromCoreMessage = {
             reader.uint32()
           );
           break;
+        case 5:
+          message.desyncEv = FromCoreMessage_DesyncEvent.decode(
+            reader,
+            reader.uint32()
+          );
+          break;
+        case 6:
+          message.roundStatsEv = FromCoreMessage_RoundStatsEvent.decode(
+            reader,
+            reader.uint32()
+          );
+          break;
+        case 7:
+          message.reconnectingEv = FromCoreMessage_ReconnectingEvent.decode(
+            reader,
+            reader.uint32()
+          );
+          break;
+        case 8:
+          message.reconnectedEv = FromCoreMessage_ReconnectedEvent.decode(
+            reader,
+            reader.uint32()
+          );
+          break;
+        case 9:
+          message.goodbyeEv = FromCoreMessage_GoodbyeEvent.decode(
+            reader,
+            reader.uint32()
+          );
+          break;
         default:
           reader.skipType(tag & 7);
           break;
@@ -259,6 +480,21 @@ export const FromCoreMessage = {
       roundEndedEv: isSet(object.roundEndedEv)
         ? FromCoreMessage_RoundEndedEvent.fromJSON(object.roundEndedEv)
         : undefined,
+      desyncEv: isSet(object.desyncEv)
+        ? FromCoreMessage_DesyncEvent.fromJSON(object.desyncEv)
+        : undefined,
+      roundStatsEv: isSet(object.roundStatsEv)
+        ? FromCoreMessage_RoundStatsEvent.fromJSON(object.roundStatsEv)
+        : undefined,
+      reconnectingEv: isSet(object.reconnectingEv)
+        ? FromCoreMessage_ReconnectingEvent.fromJSON(object.reconnectingEv)
+        : undefined,
+      reconnectedEv: isSet(object.reconnectedEv)
+        ? FromCoreMessage_ReconnectedEvent.fromJSON(object.reconnectedEv)
+        : undefined,
+      goodbyeEv: isSet(object.goodbyeEv)
+        ? FromCoreMessage_GoodbyeEvent.fromJSON(object.goodbyeEv)
+        : undefined,
     };
   },
 
@@ -282,6 +518,26 @@ export const FromCoreMessage = {
       (obj.roundEndedEv = message.roundEndedEv
         ? FromCoreMessage_RoundEndedEvent.toJSON(message.roundEndedEv)
         : undefined);
+    message.desyncEv !== undefined &&
+      (obj.desyncEv = message.desyncEv
+        ? FromCoreMessage_DesyncEvent.toJSON(message.desyncEv)
+        : undefined);
+    message.roundStatsEv !== undefined &&
+      (obj.roundStatsEv = message.roundStatsEv
+        ? FromCoreMessage_RoundStatsEvent.toJSON(message.roundStatsEv)
+        : undefined);
+    message.reconnectingEv !== undefined &&
+      (obj.reconnectingEv = message.reconnectingEv
+        ? FromCoreMessage_ReconnectingEvent.toJSON(message.reconnectingEv)
+        : undefined);
+    message.reconnectedEv !== undefined &&
+      (obj.reconnectedEv = message.reconnectedEv
+        ? FromCoreMessage_ReconnectedEvent.toJSON(message.reconnectedEv)
+        : undefined);
+    message.goodbyeEv !== undefined &&
+      (obj.goodbyeEv = message.goodbyeEv
+        ? FromCoreMessage_GoodbyeEvent.toJSON(message.goodbyeEv)
+        : undefined);
     return obj;
   },
 
@@ -308,6 +564,26 @@ export const FromCoreMessage = {
       object.roundEndedEv !== undefined && object.roundEndedEv !== null
         ? FromCoreMessage_RoundEndedEvent.fromPartial(object.roundEndedEv)
         : undefined;
+    message.desyncEv =
+      object.desyncEv !== undefined && object.desyncEv !== null
+        ? FromCoreMessage_DesyncEvent.fromPartial(object.desyncEv)
+        : undefined;
+    message.roundStatsEv =
+      object.roundStatsEv !== undefined && object.roundStatsEv !== null
+        ? FromCoreMessage_RoundStatsEvent.fromPartial(object.roundStatsEv)
+        : undefined;
+    message.reconnectingEv =
+      object.reconnectingEv !== undefined && object.reconnectingEv !== null
+        ? FromCoreMessage_ReconnectingEvent.fromPartial(object.reconnectingEv)
+        : undefined;
+    message.reconnectedEv =
+      object.reconnectedEv !== undefined && object.reconnectedEv !== null
+        ? FromCoreMessage_ReconnectedEvent.fromPartial(object.reconnectedEv)
+        : undefined;
+    message.goodbyeEv =
+      object.goodbyeEv !== undefined && object.goodbyeEv !== null
+        ? FromCoreMessage_GoodbyeEvent.fromPartial(object.goodbyeEv)
+        : undefined;
     return message;
   },
 };
@@ -551,8 +827,530 @@ export const FromCoreMessage_RoundEndedEvent = {
   },
 };
 
+function createBaseFromCoreMessage_DesyncEvent(): FromCoreMessage_DesyncEvent {
+  return { roundNumber: 0, tick: 0, lastMatchingTick: 0 };
+}
+
+export const FromCoreMessage_DesyncEvent = {
+  encode(
+    message: FromCoreMessage_DesyncEvent,
+    writer: _m0.Writer = _m0.Writer.create()
+  ): _m0.Writer {
+    if (message.roundNumber !== 0) {
+      writer.uint32(8).uint32(message.roundNumber);
+    }
+    if (message.tick !== 0) {
+      writer.uint32(16).uint32(message.tick);
+    }
+    if (message.lastMatchingTick !== 0) {
+      writer.uint32(24).uint32(message.lastMatchingTick);
+    }
+    return writer;
+  },
+
+  decode(
+    input: _m0.Reader | Uint8Array,
+    length?: number
+  ): FromCoreMessage_DesyncEvent {
+    const reader = input instanceof _m0.Reader ? input : new _m0.Reader(input);
+    let end = length === undefined ? reader.len : reader.pos + length;
+    const message = createBaseFromCoreMessage_DesyncEvent();
+    while (reader.pos < end) {
+      const tag = reader.uint32();
+      switch (tag >>> 3) {
+        case 1:
+          message.roundNumber = reader.uint32();
+          break;
+        case 2:
+          message.tick = reader.uint32();
+          break;
+        case 3:
+          message.lastMatchingTick = reader.uint32();
+          break;
+        default:
+          reader.skipType(tag & 7);
+          break;
+      }
+    }
+    return message;
+  },
+
+  fromJSON(object: any): FromCoreMessage_DesyncEvent {
+    return {
+      roundNumber: isSet(object.roundNumber) ? Number(object.roundNumber) : 0,
+      tick: isSet(object.tick) ? Number(object.tick) : 0,
+      lastMatchingTick: isSet(object.lastMatchingTick)
+        ? Number(object.lastMatchingTick)
+        : 0,
+    };
+  },
+
+  toJSON(message: FromCoreMessage_DesyncEvent): unknown {
+    const obj: any = {};
+    message.roundNumber !== undefined &&
+      (obj.roundNumber = Math.round(message.roundNumber));
+    message.tick !== undefined && (obj.tick = Math.round(message.tick));
+    message.lastMatchingTick !== undefined &&
+      (obj.lastMatchingTick = Math.round(message.lastMatchingTick));
+    return obj;
+  },
+
+  fromPartial<I extends Exact<DeepPartial<FromCoreMessage_DesyncEvent>, I>>(
+    object: I
+  ): FromCoreMessage_DesyncEvent {
+    const message = createBaseFromCoreMessage_DesyncEvent();
+    message.roundNumber = object.roundNumber ?? 0;
+    message.tick = object.tick ?? 0;
+    message.lastMatchingTick = object.lastMatchingTick ?? 0;
+    return message;
+  },
+};
+
+function createBaseFromCoreMessage_RoundStatsEvent(): FromCoreMessage_RoundStatsEvent {
+  return {
+    roundNumber: 0,
+    inputDelay: 0,
+    remoteInputDelay: 0,
+    numRttSamples: 0,
+    rttP50: 0,
+    rttP90: 0,
+    rttP99: 0,
+    jitter: 0,
+    numRollbacks: 0,
+    framesResimulated: 0,
+    maxPredictionDepth: 0,
+    tpsAdjustments: [],
+    maxRollbackDepth: 0,
+  };
+}
+
+export const FromCoreMessage_RoundStatsEvent = {
+  encode(
+    message: FromCoreMessage_RoundStatsEvent,
+    writer: _m0.Writer = _m0.Writer.create()
+  ): _m0.Writer {
+    if (message.roundNumber !== 0) {
+      writer.uint32(8).uint32(message.roundNumber);
+    }
+    if (message.inputDelay !== 0) {
+      writer.uint32(16).uint32(message.inputDelay);
+    }
+    if (message.remoteInputDelay !== 0) {
+      writer.uint32(24).uint32(message.remoteInputDelay);
+    }
+    if (message.numRttSamples !== 0) {
+      writer.uint32(32).uint32(message.numRttSamples);
+    }
+    if (message.rttP50 !== 0) {
+      writer.uint32(40).uint64(message.rttP50);
+    }
+    if (message.rttP90 !== 0) {
+      writer.uint32(48).uint64(message.rttP90);
+    }
+    if (message.rttP99 !== 0) {
+      writer.uint32(56).uint64(message.rttP99);
+    }
+    if (message.jitter !== 0) {
+      writer.uint32(64).uint64(message.jitter);
+    }
+    if (message.numRollbacks !== 0) {
+      writer.uint32(72).uint32(message.numRollbacks);
+    }
+    if (message.framesResimulated !== 0) {
+      writer.uint32(80).uint64(message.framesResimulated);
+    }
+    if (message.maxPredictionDepth !== 0) {
+      writer.uint32(88).uint32(message.maxPredictionDepth);
+    }
+    for (const v of message.tpsAdjustments) {
+      FromCoreMessage_RoundStatsEvent_TpsAdjustment.encode(
+        v!,
+        writer.uint32(98).fork()
+      ).ldelim();
+    }
+    if (message.maxRollbackDepth !== 0) {
+      writer.uint32(104).uint32(message.maxRollbackDepth);
+    }
+    return writer;
+  },
+
+  decode(
+    input: _m0.Reader | Uint8Array,
+    length?: number
+  ): FromCoreMessage_RoundStatsEvent {
+    const reader = input instanceof _m0.Reader ? input : new _m0.Reader(input);
+    let end = length === undefined ? reader.len : reader.pos + length;
+    const message = createBaseFromCoreMessage_RoundStatsEvent();
+    while (reader.pos < end) {
+      const tag = reader.uint32();
+      switch (tag >>> 3) {
+        case 1:
+          message.roundNumber = reader.uint32();
+          break;
+        case 2:
+          message.inputDelay = reader.uint32();
+          break;
+        case 3:
+          message.remoteInputDelay = reader.uint32();
+          break;
+        case 4:
+          message.numRttSamples = reader.uint32();
+          break;
+        case 5:
+          message.rttP50 = longToNumber(reader.uint64() as Long);
+          break;
+        case 6:
+          message.rttP90 = longToNumber(reader.uint64() as Long);
+          break;
+        case 7:
+          message.rttP99 = longToNumber(reader.uint64() as Long);
+          break;
+        case 8:
+          message.jitter = longToNumber(reader.uint64() as Long);
+          break;
+        case 9:
+          message.numRollbacks = reader.uint32();
+          break;
+        case 10:
+          message.framesResimulated = longToNumber(reader.uint64() as Long);
+          break;
+        case 11:
+          message.maxPredictionDepth = reader.uint32();
+          break;
+        case 12:
+          message.tpsAdjustments.push(
+            FromCoreMessage_RoundStatsEvent_TpsAdjustment.decode(
+              reader,
+              reader.uint32()
+            )
+          );
+          break;
+        case 13:
+          message.maxRollbackDepth = reader.uint32();
+          break;
+        default:
+          reader.skipType(tag & 7);
+          break;
+      }
+    }
+    return message;
+  },
+
+  fromJSON(object: any): FromCoreMessage_RoundStatsEvent {
+    return {
+      roundNumber: isSet(object.roundNumber) ? Number(object.roundNumber) : 0,
+      inputDelay: isSet(object.inputDelay) ? Number(object.inputDelay) : 0,
+      remoteInputDelay: isSet(object.remoteInputDelay)
+        ? Number(object.remoteInputDelay)
+        : 0,
+      numRttSamples: isSet(object.numRttSamples)
+        ? Number(object.numRttSamples)
+        : 0,
+      rttP50: isSet(object.rttP50) ? Number(object.rttP50) : 0,
+      rttP90: isSet(object.rttP90) ? Number(object.rttP90) : 0,
+      rttP99: isSet(object.rttP99) ? Number(object.rttP99) : 0,
+      jitter: isSet(object.jitter) ? Number(object.jitter) : 0,
+      numRollbacks: isSet(object.numRollbacks)
+        ? Number(object.numRollbacks)
+        : 0,
+      framesResimulated: isSet(object.framesResimulated)
+        ? Number(object.framesResimulated)
+        : 0,
+      maxPredictionDepth: isSet(object.maxPredictionDepth)
+        ? Number(object.maxPredictionDepth)
+        : 0,
+      tpsAdjustments: Array.isArray(object?.tpsAdjustments)
+        ? object.tpsAdjustments.map((e: any) =>
+            FromCoreMessage_RoundStatsEvent_TpsAdjustment.fromJSON(e)
+          )
+        : [],
+      maxRollbackDepth: isSet(object.maxRollbackDepth)
+        ? Number(object.maxRollbackDepth)
+        : 0,
+    };
+  },
+
+  toJSON(message: FromCoreMessage_RoundStatsEvent): unknown {
+    const obj: any = {};
+    message.roundNumber !== undefined &&
+      (obj.roundNumber = Math.round(message.roundNumber));
+    message.inputDelay !== undefined &&
+      (obj.inputDelay = Math.round(message.inputDelay));
+    message.remoteInputDelay !== undefined &&
+      (obj.remoteInputDelay = Math.round(message.remoteInputDelay));
+    message.numRttSamples !== undefined &&
+      (obj.numRttSamples = Math.round(message.numRttSamples));
+    message.rttP50 !== undefined && (obj.rttP50 = Math.round(message.rttP50));
+    message.rttP90 !== undefined && (obj.rttP90 = Math.round(message.rttP90));
+    message.rttP99 !== undefined && (obj.rttP99 = Math.round(message.rttP99));
+    message.jitter !== undefined && (obj.jitter = Math.round(message.jitter));
+    message.numRollbacks !== undefined &&
+      (obj.numRollbacks = Math.round(message.numRollbacks));
+    message.framesResimulated !== undefined &&
+      (obj.framesResimulated = Math.round(message.framesResimulated));
+    message.maxPredictionDepth !== undefined &&
+      (obj.maxPredictionDepth = Math.round(message.maxPredictionDepth));
+    if (message.tpsAdjustments) {
+      obj.tpsAdjustments = message.tpsAdjustments.map((e) =>
+        e ? FromCoreMessage_RoundStatsEvent_TpsAdjustment.toJSON(e) : undefined
+      );
+    } else {
+      obj.tpsAdjustments = [];
+    }
+    message.maxRollbackDepth !== undefined &&
+      (obj.maxRollbackDepth = Math.round(message.maxRollbackDepth));
+    return obj;
+  },
+
+  fromPartial<I extends Exact<DeepPartial<FromCoreMessage_RoundStatsEvent>, I>>(
+    object: I
+  ): FromCoreMessage_RoundStatsEvent {
+    const message = createBaseFromCoreMessage_RoundStatsEvent();
+    message.roundNumber = object.roundNumber ?? 0;
+    message.inputDelay = object.inputDelay ?? 0;
+    message.remoteInputDelay = object.remoteInputDelay ?? 0;
+    message.numRttSamples = object.numRttSamples ?? 0;
+    message.rttP50 = object.rttP50 ?? 0;
+    message.rttP90 = object.rttP90 ?? 0;
+    message.rttP99 = object.rttP99 ?? 0;
+    message.jitter = object.jitter ?? 0;
+    message.numRollbacks = object.numRollbacks ?? 0;
+    message.framesResimulated = object.framesResimulated ?? 0;
+    message.maxPredictionDepth = object.maxPredictionDepth ?? 0;
+    message.tpsAdjustments =
+      object.tpsAdjustments?.map((e) =>
+        FromCoreMessage_RoundStatsEvent_TpsAdjustment.fromPartial(e)
+      ) || [];
+    message.maxRollbackDepth = object.maxRollbackDepth ?? 0;
+    return message;
+  },
+};
+
+function createBaseFromCoreMessage_RoundStatsEvent_TpsAdjustment(): FromCoreMessage_RoundStatsEvent_TpsAdjustment {
+  return { tick: 0, adjustment: 0 };
+}
+
+export const FromCoreMessage_RoundStatsEvent_TpsAdjustment = {
+  encode(
+    message: FromCoreMessage_RoundStatsEvent_TpsAdjustment,
+    writer: _m0.Writer = _m0.Writer.create()
+  ): _m0.Writer {
+    if (message.tick !== 0) {
+      writer.uint32(8).uint32(message.tick);
+    }
+    if (message.adjustment !== 0) {
+      writer.uint32(21).float(message.adjustment);
+    }
+    return writer;
+  },
+
+  decode(
+    input: _m0.Reader | Uint8Array,
+    length?: number
+  ): FromCoreMessage_RoundStatsEvent_TpsAdjustment {
+    const reader = input instanceof _m0.Reader ? input : new _m0.Reader(input);
+    let end = length === undefined ? reader.len : reader.pos + length;
+    const message = createBaseFromCoreMessage_RoundStatsEvent_TpsAdjustment();
+    while (reader.pos < end) {
+      const tag = reader.uint32();
+      switch (tag >>> 3) {
+        case 1:
+          message.tick = reader.uint32();
+          break;
+        case 2:
+          message.adjustment = reader.float();
+          break;
+        default:
+          reader.skipType(tag & 7);
+          break;
+      }
+    }
+    return message;
+  },
+
+  fromJSON(object: any): FromCoreMessage_RoundStatsEvent_TpsAdjustment {
+    return {
+      tick: isSet(object.tick) ? Number(object.tick) : 0,
+      adjustment: isSet(object.adjustment) ? Number(object.adjustment) : 0,
+    };
+  },
+
+  toJSON(message: FromCoreMessage_RoundStatsEvent_TpsAdjustment): unknown {
+    const obj: any = {};
+    message.tick !== undefined && (obj.tick = Math.round(message.tick));
+    message.adjustment !== undefined && (obj.adjustment = message.adjustment);
+    return obj;
+  },
+
+  fromPartial<
+    I extends Exact<
+      DeepPartial<FromCoreMessage_RoundStatsEvent_TpsAdjustment>,
+      I
+    >
+  >(object: I): FromCoreMessage_RoundStatsEvent_TpsAdjustment {
+    const message = createBaseFromCoreMessage_RoundStatsEvent_TpsAdjustment();
+    message.tick = object.tick ?? 0;
+    message.adjustment = object.adjustment ?? 0;
+    return message;
+  },
+};
+
+function createBaseFromCoreMessage_ReconnectingEvent(): FromCoreMessage_ReconnectingEvent {
+  return {};
+}
+
+export const FromCoreMessage_ReconnectingEvent = {
+  encode(
+    _: FromCoreMessage_ReconnectingEvent,
+    writer: _m0.Writer = _m0.Writer.create()
+  ): _m0.Writer {
+    return writer;
+  },
+
+  decode(
+    input: _m0.Reader | Uint8Array,
+    length?: number
+  ): FromCoreMessage_ReconnectingEvent {
+    const reader = input instanceof _m0.Reader ? input : new _m0.Reader(input);
+    let end = length === undefined ? reader.len : reader.pos + length;
+    const message = createBaseFromCoreMessage_ReconnectingEvent();
+    while (reader.pos < end) {
+      const tag = reader.uint32();
+      switch (tag >>> 3) {
+        default:
+          reader.skipType(tag & 7);
+          break;
+      }
+    }
+    return message;
+  },
+
+  fromJSON(_: any): FromCoreMessage_ReconnectingEvent {
+    return {};
+  },
+
+  toJSON(_: FromCoreMessage_ReconnectingEvent): unknown {
+    const obj: any = {};
+    return obj;
+  },
+
+  fromPartial<
+    I extends Exact<DeepPartial<FromCoreMessage_ReconnectingEvent>, I>
+  >(_: I): FromCoreMessage_ReconnectingEvent {
+    const message = createBaseFromCoreMessage_ReconnectingEvent();
+    return message;
+  },
+};
+
+function createBaseFromCoreMessage_ReconnectedEvent(): FromCoreMessage_ReconnectedEvent {
+  return {};
+}
+
+export const FromCoreMessage_ReconnectedEvent = {
+  encode(
+    _: FromCoreMessage_ReconnectedEvent,
+    writer: _m0.Writer = _m0.Writer.create()
+  ): _m0.Writer {
+    return writer;
+  },
+
+  decode(
+    input: _m0.Reader | Uint8Array,
+    length?: number
+  ): FromCoreMessage_ReconnectedEvent {
+    const reader = input instanceof _m0.Reader ? input : new _m0.Reader(input);
+    let end = length === undefined ? reader.len : reader.pos + length;
+    const message = createBaseFromCoreMessage_ReconnectedEvent();
+    while (reader.pos < end) {
+      const tag = reader.uint32();
+      switch (tag >>> 3) {
+        default:
+          reader.skipType(tag & 7);
+          break;
+      }
+    }
+    return message;
+  },
+
+  fromJSON(_: any): FromCoreMessage_ReconnectedEvent {
+    return {};
+  },
+
+  toJSON(_: FromCoreMessage_ReconnectedEvent): unknown {
+    const obj: any = {};
+    return obj;
+  },
+
+  fromPartial<
+    I extends Exact<DeepPartial<FromCoreMessage_ReconnectedEvent>, I>
+  >(_: I): FromCoreMessage_ReconnectedEvent {
+    const message = createBaseFromCoreMessage_ReconnectedEvent();
+    return message;
+  },
+};
+
+function createBaseFromCoreMessage_GoodbyeEvent(): FromCoreMessage_GoodbyeEvent {
+  return { reason: 0 };
+}
+
+export const FromCoreMessage_GoodbyeEvent = {
+  encode(
+    message: FromCoreMessage_GoodbyeEvent,
+    writer: _m0.Writer = _m0.Writer.create()
+  ): _m0.Writer {
+    if (message.reason !== 0) {
+      writer.uint32(8).int32(message.reason);
+    }
+    return writer;
+  },
+
+  decode(
+    input: _m0.Reader | Uint8Array,
+    length?: number
+  ): FromCoreMessage_GoodbyeEvent {
+    const reader = input instanceof _m0.Reader ? input : new _m0.Reader(input);
+    let end = length === undefined ? reader.len : reader.pos + length;
+    const message = createBaseFromCoreMessage_GoodbyeEvent();
+    while (reader.pos < end) {
+      const tag = reader.uint32();
+      switch (tag >>> 3) {
+        case 1:
+          message.reason = reader.int32() as any;
+          break;
+        default:
+          reader.skipType(tag & 7);
+          break;
+      }
+    }
+    return message;
+  },
+
+  fromJSON(object: any): FromCoreMessage_GoodbyeEvent {
+    return {
+      reason: isSet(object.reason)
+        ? fromCoreMessage_GoodbyeEvent_ReasonFromJSON(object.reason)
+        : 0,
+    };
+  },
+
+  toJSON(message: FromCoreMessage_GoodbyeEvent): unknown {
+    const obj: any = {};
+    message.reason !== undefined &&
+      (obj.reason = fromCoreMessage_GoodbyeEvent_ReasonToJSON(message.reason));
+    return obj;
+  },
+
+  fromPartial<I extends Exact<DeepPartial<FromCoreMessage_GoodbyeEvent>, I>>(
+    object: I
+  ): FromCoreMessage_GoodbyeEvent {
+    const message = createBaseFromCoreMessage_GoodbyeEvent();
+    message.reason = object.reason ?? 0;
+    return message;
+  },
+};
+
 function createBaseToCoreMessage(): ToCoreMessage {
-  return { startReq: undefined, smuggleReq: undefined };
+  return { startReq: undefined, smuggleReq: undefined, forfeitReq: undefined };
 }
 
 export const ToCoreMessage = {
@@ -572,6 +1370,12 @@ export const ToCoreMessage = {
         writer.uint32(18).fork()
       ).ldelim();
     }
+    if (message.forfeitReq !== undefined) {
+      ToCoreMessage_ForfeitRequest.encode(
+        message.forfeitReq,
+        writer.uint32(26).fork()
+      ).ldelim();
+    }
     return writer;
   },
 
@@ -594,6 +1398,12 @@ export const ToCoreMessage = {
             reader.uint32()
           );
           break;
+        case 3:
+          message.forfeitReq = ToCoreMessage_ForfeitRequest.decode(
+            reader,
+            reader.uint32()
+          );
+          break;
         default:
           reader.skipType(tag & 7);
           break;
@@ -610,6 +1420,9 @@ export const ToCoreMessage = {
       smuggleReq: isSet(object.smuggleReq)
         ? ToCoreMessage_SmuggleRequest.fromJSON(object.smuggleReq)
         : undefined,
+      forfeitReq: isSet(object.forfeitReq)
+        ? ToCoreMessage_ForfeitRequest.fromJSON(object.forfeitReq)
+        : undefined,
     };
   },
 
@@ -623,6 +1436,10 @@ export const ToCoreMessage = {
       (obj.smuggleReq = message.smuggleReq
         ? ToCoreMessage_SmuggleRequest.toJSON(message.smuggleReq)
         : undefined);
+    message.forfeitReq !== undefined &&
+      (obj.forfeitReq = message.forfeitReq
+        ? ToCoreMessage_ForfeitRequest.toJSON(message.forfeitReq)
+        : undefined);
     return obj;
   },
 
@@ -638,6 +1455,10 @@ export const ToCoreMessage = {
       object.smuggleReq !== undefined && object.smuggleReq !== null
         ? ToCoreMessage_SmuggleRequest.fromPartial(object.smuggleReq)
         : undefined;
+    message.forfeitReq =
+      object.forfeitReq !== undefined && object.forfeitReq !== null
+        ? ToCoreMessage_ForfeitRequest.fromPartial(object.forfeitReq)
+        : undefined;
     return message;
   },
 };
@@ -649,6 +1470,8 @@ function createBaseToCoreMessage_StartRequest(): ToCoreMessage_StartRequest {
     savePath: "",
     windowScale: 0,
     settings: undefined,
+    patchPath: undefined,
+    netplayCompatibility: undefined,
   };
 }
 
@@ -675,6 +1498,12 @@ export const ToCoreMessage_StartRequest = {
         writer.uint32(42).fork()
       ).ldelim();
     }
+    if (message.patchPath !== undefined) {
+      writer.uint32(50).string(message.patchPath);
+    }
+    if (message.netplayCompatibility !== undefined) {
+      writer.uint32(58).string(message.netplayCompatibility);
+    }
     return writer;
   },
 
@@ -706,6 +1535,12 @@ export const ToCoreMessage_StartRequest = {
             reader.uint32()
           );
           break;
+        case 6:
+          message.patchPath = reader.string();
+          break;
+        case 7:
+          message.netplayCompatibility = reader.string();
+          break;
         default:
           reader.skipType(tag & 7);
           break;
@@ -723,6 +1558,10 @@ export const ToCoreMessage_StartRequest = {
       settings: isSet(object.settings)
         ? ToCoreMessage_StartRequest_MatchSettings.fromJSON(object.settings)
         : undefined,
+      patchPath: isSet(object.patchPath) ? String(object.patchPath) : undefined,
+      netplayCompatibility: isSet(object.netplayCompatibility)
+        ? String(object.netplayCompatibility)
+        : undefined,
     };
   },
 
@@ -738,6 +1577,9 @@ export const ToCoreMessage_StartRequest = {
       (obj.settings = message.settings
         ? ToCoreMessage_StartRequest_MatchSettings.toJSON(message.settings)
         : undefined);
+    message.patchPath !== undefined && (obj.patchPath = message.patchPath);
+    message.netplayCompatibility !== undefined &&
+      (obj.netplayCompatibility = message.netplayCompatibility);
     return obj;
   },
 
@@ -753,6 +1595,8 @@ export const ToCoreMessage_StartRequest = {
       object.settings !== undefined && object.settings !== null
         ? ToCoreMessage_StartRequest_MatchSettings.fromPartial(object.settings)
         : undefined;
+    message.patchPath = object.patchPath ?? undefined;
+    message.netplayCompatibility = object.netplayCompatibility ?? undefined;
     return message;
   },
 };
@@ -770,6 +1614,10 @@ function createBaseToCoreMessage_StartRequest_MatchSettings(): ToCoreMessage_Sta
     rngSeed: new Uint8Array(),
     opponentNickname: undefined,
     maxQueueLength: 0,
+    spectatorListenAddr: undefined,
+    autoInputDelay: false,
+    shadowPatchPath: undefined,
+    signingKeyPath: undefined,
   };
 }
 
@@ -811,6 +1659,18 @@ export const ToCoreMessage_StartRequest_MatchSettings = {
     if (message.maxQueueLength !== 0) {
       writer.uint32(88).uint32(message.maxQueueLength);
     }
+    if (message.spectatorListenAddr !== undefined) {
+      writer.uint32(98).string(message.spectatorListenAddr);
+    }
+    if (message.autoInputDelay === true) {
+      writer.uint32(104).bool(message.autoInputDelay);
+    }
+    if (message.shadowPatchPath !== undefined) {
+      writer.uint32(114).string(message.shadowPatchPath);
+    }
+    if (message.signingKeyPath !== undefined) {
+      writer.uint32(122).string(message.signingKeyPath);
+    }
     return writer;
   },
 
@@ -857,6 +1717,18 @@ export const ToCoreMessage_StartRequest_MatchSettings = {
         case 11:
           message.maxQueueLength = reader.uint32();
           break;
+        case 12:
+          message.spectatorListenAddr = reader.string();
+          break;
+        case 13:
+          message.autoInputDelay = reader.bool();
+          break;
+        case 14:
+          message.shadowPatchPath = reader.string();
+          break;
+        case 15:
+          message.signingKeyPath = reader.string();
+          break;
         default:
           reader.skipType(tag & 7);
           break;
@@ -894,6 +1766,18 @@ export const ToCoreMessage_StartRequest_MatchSettings = {
       maxQueueLength: isSet(object.maxQueueLength)
         ? Number(object.maxQueueLength)
         : 0,
+      spectatorListenAddr: isSet(object.spectatorListenAddr)
+        ? String(object.spectatorListenAddr)
+        : undefined,
+      autoInputDelay: isSet(object.autoInputDelay)
+        ? Boolean(object.autoInputDelay)
+        : false,
+      shadowPatchPath: isSet(object.shadowPatchPath)
+        ? String(object.shadowPatchPath)
+        : undefined,
+      signingKeyPath: isSet(object.signingKeyPath)
+        ? String(object.signingKeyPath)
+        : undefined,
     };
   },
 
@@ -927,6 +1811,14 @@ export const ToCoreMessage_StartRequest_MatchSettings = {
       (obj.opponentNickname = message.opponentNickname);
     message.maxQueueLength !== undefined &&
       (obj.maxQueueLength = Math.round(message.maxQueueLength));
+    message.spectatorListenAddr !== undefined &&
+      (obj.spectatorListenAddr = message.spectatorListenAddr);
+    message.autoInputDelay !== undefined &&
+      (obj.autoInputDelay = message.autoInputDelay);
+    message.shadowPatchPath !== undefined &&
+      (obj.shadowPatchPath = message.shadowPatchPath);
+    message.signingKeyPath !== undefined &&
+      (obj.signingKeyPath = message.signingKeyPath);
     return obj;
   },
 
@@ -945,6 +1837,10 @@ export const ToCoreMessage_StartRequest_MatchSettings = {
     message.rngSeed = object.rngSeed ?? new Uint8Array();
     message.opponentNickname = object.opponentNickname ?? undefined;
     message.maxQueueLength = object.maxQueueLength ?? 0;
+    message.spectatorListenAddr = object.spectatorListenAddr ?? undefined;
+    message.autoInputDelay = object.autoInputDelay ?? false;
+    message.shadowPatchPath = object.shadowPatchPath ?? undefined;
+    message.signingKeyPath = object.signingKeyPath ?? undefined;
     return message;
   },
 };
@@ -1011,6 +1907,53 @@ export const ToCoreMessage_SmuggleRequest = {
   },
 };
 
+function createBaseToCoreMessage_ForfeitRequest(): ToCoreMessage_ForfeitRequest {
+  return {};
+}
+
+export const ToCoreMessage_ForfeitRequest = {
+  encode(
+    _: ToCoreMessage_ForfeitRequest,
+    writer: _m0.Writer = _m0.Writer.create()
+  ): _m0.Writer {
+    return writer;
+  },
+
+  decode(
+    input: _m0.Reader | Uint8Array,
+    length?: number
+  ): ToCoreMessage_ForfeitRequest {
+    const reader = input instanceof _m0.Reader ? input : new _m0.Reader(input);
+    let end = length === undefined ? reader.len : reader.pos + length;
+    const message = createBaseToCoreMessage_ForfeitRequest();
+    while (reader.pos < end) {
+      const tag = reader.uint32();
+      switch (tag >>> 3) {
+        default:
+          reader.skipType(tag & 7);
+          break;
+      }
+    }
+    return message;
+  },
+
+  fromJSON(_: any): ToCoreMessage_ForfeitRequest {
+    return {};
+  },
+
+  toJSON(_: ToCoreMessage_ForfeitRequest): unknown {
+    const obj: any = {};
+    return obj;
+  },
+
+  fromPartial<I extends Exact<DeepPartial<ToCoreMessage_ForfeitRequest>, I>>(
+    _: I
+  ): ToCoreMessage_ForfeitRequest {
+    const message = createBaseToCoreMessage_ForfeitRequest();
+    return message;
+  },
+};
+
 declare var self: any | undefined;
 declare var window: any | undefined;
 declare var global: any | undefined;
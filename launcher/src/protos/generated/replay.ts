@@ -9,6 +9,7 @@ export interface ReplayMetadata {
   linkCode: string;
   localSide: ReplayMetadata_Side | undefined;
   remoteSide: ReplayMetadata_Side | undefined;
+  repair: ReplayMetadata_Repair | undefined;
 }
 
 export interface ReplayMetadata_GameInfo {
@@ -19,6 +20,7 @@ export interface ReplayMetadata_GameInfo {
 export interface ReplayMetadata_GameInfo_Patch {
   name: string;
   version: string;
+  hash: Uint8Array;
 }
 
 export interface ReplayMetadata_Side {
@@ -27,8 +29,22 @@ export interface ReplayMetadata_Side {
   revealSetup: boolean;
 }
 
+/** Set if the replay was cut off and later repaired. */
+export interface ReplayMetadata_Repair {
+  /** How many input pairs could be salvaged: the round is missing everything after these. */
+  numInputs: number;
+  /** Where in the original file the readable data ended. */
+  truncatedAt: number;
+}
+
 function createBaseReplayMetadata(): ReplayMetadata {
-  return { ts: 0, linkCode: "", localSide: undefined, remoteSide: undefined };
+  return {
+    ts: 0,
+    linkCode: "",
+    localSide: undefined,
+    remoteSide: undefined,
+    repair: undefined,
+  };
 }
 
 export const ReplayMetadata = {
@@ -54,6 +70,12 @@ export const ReplayMetadata = {
         writer.uint32(34).fork()
       ).ldelim();
     }
+    if (message.repair !== undefined) {
+      ReplayMetadata_Repair.encode(
+        message.repair,
+        writer.uint32(42).fork()
+      ).ldelim();
+    }
     return writer;
   },
 
@@ -82,6 +104,12 @@ export const ReplayMetadata = {
             reader.uint32()
           );
           break;
+        case 5:
+          message.repair = ReplayMetadata_Repair.decode(
+            reader,
+            reader.uint32()
+          );
+          break;
         default:
           reader.skipType(tag & 7);
           break;
@@ -100,6 +128,9 @@ export const ReplayMetadata = {
       remoteSide: isSet(object.remoteSide)
         ? ReplayMetadata_Side.fromJSON(object.remoteSide)
         : undefined,
+      repair: isSet(object.repair)
+        ? ReplayMetadata_Repair.fromJSON(object.repair)
+        : undefined,
     };
   },
 
@@ -115,6 +146,10 @@ export const ReplayMetadata = {
       (obj.remoteSide = message.remoteSide
         ? ReplayMetadata_Side.toJSON(message.remoteSide)
         : undefined);
+    message.repair !== undefined &&
+      (obj.repair = message.repair
+        ? ReplayMetadata_Repair.toJSON(message.repair)
+        : undefined);
     return obj;
   },
 
@@ -132,6 +167,10 @@ export const ReplayMetadata = {
       object.remoteSide !== undefined && object.remoteSide !== null
         ? ReplayMetadata_Side.fromPartial(object.remoteSide)
         : undefined;
+    message.repair =
+      object.repair !== undefined && object.repair !== null
+        ? ReplayMetadata_Repair.fromPartial(object.repair)
+        : undefined;
     return message;
   },
 };
@@ -217,7 +256,7 @@ export const ReplayMetadata_GameInfo = {
 };
 
 function createBaseReplayMetadata_GameInfo_Patch(): ReplayMetadata_GameInfo_Patch {
-  return { name: "", version: "" };
+  return { name: "", version: "", hash: new Uint8Array() };
 }
 
 export const ReplayMetadata_GameInfo_Patch = {
@@ -231,6 +270,9 @@ export const ReplayMetadata_GameInfo_Patch = {
     if (message.version !== "") {
       writer.uint32(18).string(message.version);
     }
+    if (message.hash.length !== 0) {
+      writer.uint32(26).bytes(message.hash);
+    }
     return writer;
   },
 
@@ -250,6 +292,9 @@ export const ReplayMetadata_GameInfo_Patch = {
         case 2:
           message.version = reader.string();
           break;
+        case 3:
+          message.hash = reader.bytes();
+          break;
         default:
           reader.skipType(tag & 7);
           break;
@@ -262,6 +307,9 @@ export const ReplayMetadata_GameInfo_Patch = {
     return {
       name: isSet(object.name) ? String(object.name) : "",
       version: isSet(object.version) ? String(object.version) : "",
+      hash: isSet(object.hash)
+        ? bytesFromBase64(object.hash)
+        : new Uint8Array(),
     };
   },
 
@@ -269,6 +317,10 @@ export const ReplayMetadata_GameInfo_Patch = {
     const obj: any = {};
     message.name !== undefined && (obj.name = message.name);
     message.version !== undefined && (obj.version = message.version);
+    message.hash !== undefined &&
+      (obj.hash = base64FromBytes(
+        message.hash !== undefined ? message.hash : new Uint8Array()
+      ));
     return obj;
   },
 
@@ -278,6 +330,7 @@ export const ReplayMetadata_GameInfo_Patch = {
     const message = createBaseReplayMetadata_GameInfo_Patch();
     message.name = object.name ?? "";
     message.version = object.version ?? "";
+    message.hash = object.hash ?? new Uint8Array();
     return message;
   },
 };
@@ -371,6 +424,74 @@ export const ReplayMetadata_Side = {
   },
 };
 
+function createBaseReplayMetadata_Repair(): ReplayMetadata_Repair {
+  return { numInputs: 0, truncatedAt: 0 };
+}
+
+export const ReplayMetadata_Repair = {
+  encode(
+    message: ReplayMetadata_Repair,
+    writer: _m0.Writer = _m0.Writer.create()
+  ): _m0.Writer {
+    if (message.numInputs !== 0) {
+      writer.uint32(8).uint32(message.numInputs);
+    }
+    if (message.truncatedAt !== 0) {
+      writer.uint32(16).uint64(message.truncatedAt);
+    }
+    return writer;
+  },
+
+  decode(
+    input: _m0.Reader | Uint8Array,
+    length?: number
+  ): ReplayMetadata_Repair {
+    const reader = input instanceof _m0.Reader ? input : new _m0.Reader(input);
+    let end = length === undefined ? reader.len : reader.pos + length;
+    const message = createBaseReplayMetadata_Repair();
+    while (reader.pos < end) {
+      const tag = reader.uint32();
+      switch (tag >>> 3) {
+        case 1:
+          message.numInputs = reader.uint32();
+          break;
+        case 2:
+          message.truncatedAt = longToNumber(reader.uint64() as Long);
+          break;
+        default:
+          reader.skipType(tag & 7);
+          break;
+      }
+    }
+    return message;
+  },
+
+  fromJSON(object: any): ReplayMetadata_Repair {
+    return {
+      numInputs: isSet(object.numInputs) ? Number(object.numInputs) : 0,
+      truncatedAt: isSet(object.truncatedAt) ? Number(object.truncatedAt) : 0,
+    };
+  },
+
+  toJSON(message: ReplayMetadata_Repair): unknown {
+    const obj: any = {};
+    message.numInputs !== undefined &&
+      (obj.numInputs = Math.round(message.numInputs));
+    message.truncatedAt !== undefined &&
+      (obj.truncatedAt = Math.round(message.truncatedAt));
+    return obj;
+  },
+
+  fromPartial<I extends Exact<DeepPartial<ReplayMetadata_Repair>, I>>(
+    object: I
+  ): ReplayMetadata_Repair {
+    const message = createBaseReplayMetadata_Repair();
+    message.numInputs = object.numInputs ?? 0;
+    message.truncatedAt = object.truncatedAt ?? 0;
+    return message;
+  },
+};
+
 declare var self: any | undefined;
 declare var window: any | undefined;
 declare var global: any | undefined;
@@ -382,6 +503,29 @@ var globalThis: any = (() => {
   throw "Unable to locate global object";
 })();
 
+const atob: (b64: string) => string =
+  globalThis.atob ||
+  ((b64) => globalThis.Buffer.from(b64, "base64").toString("binary"));
+function bytesFromBase64(b64: string): Uint8Array {
+  const bin = atob(b64);
+  const arr = new Uint8Array(bin.length);
+  for (let i = 0; i < bin.length; ++i) {
+    arr[i] = bin.charCodeAt(i);
+  }
+  return arr;
+}
+
+const btoa: (bin: string) => string =
+  globalThis.btoa ||
+  ((bin) => globalThis.Buffer.from(bin, "binary").toString("base64"));
+function base64FromBytes(arr: Uint8Array): string {
+  const bin: string[] = [];
+  arr.forEach((byte) => {
+    bin.push(String.fromCharCode(byte));
+  });
+  return btoa(bin.join(""));
+}
+
 type Builtin =
   | Date
   | Function
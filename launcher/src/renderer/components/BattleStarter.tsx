@@ -59,7 +59,9 @@ import {
 import {
     GameInfo, GameInfo_Patch, Message, NegotiatedState, SetSettings
 } from "../../protos/generated/lobby";
-import { ReplayMetadata } from "../../protos/generated/replay";
+import {
+    ReplayMetadata, ReplayMetadata_GameInfo
+} from "../../protos/generated/replay";
 import randomCode from "../../randomcode";
 import { FAMILY_BY_ROM_NAME, getROMInfo, KNOWN_ROM_FAMILIES } from "../../rom";
 import { Editor, editorClassForGameFamily } from "../../saveedit";
@@ -171,6 +173,21 @@ function gameInfoMatches(g: GameInfo | null, h: GameInfo) {
   return g.patch!.name == h.patch!.name && g.patch!.version == h.patch!.version;
 }
 
+function replayGameInfo(g: GameInfo): ReplayMetadata_GameInfo {
+  // The patch hash is filled in by the core, which has the patch loaded.
+  return {
+    rom: g.rom,
+    patch:
+      g.patch != null
+        ? {
+            name: g.patch.name,
+            version: g.patch.version,
+            hash: new Uint8Array(),
+          }
+        : undefined,
+  };
+}
+
 export function useGetNetplayCompatibility() {
   const { patches } = usePatches();
   return React.useCallback(
@@ -289,6 +306,7 @@ async function runCallback(
     availableGames: SetSettings["availableGames"];
     getGameTitle: (gameInfo: GameInfo) => string;
     getGameFamilyTitle: (gameInfo: GameInfo) => string;
+    getNetplayCompatibility: (gameInfo: GameInfo) => string | null;
     getPatchInfo: (patchName: string) => PatchInfo | null;
     getPatchPath: (
       rom: string,
@@ -428,40 +446,17 @@ async function runCallback(
 
   if (linkCode == "") {
     // No link code to worry about, just start the game with no settings.
-    const outFullROMName = `${ref.current.gameInfo!.rom}${
-      ref.current.gameInfo!.patch != null
-        ? `+${ref.current.gameInfo!.patch.name}-v${
-            ref.current.gameInfo!.patch.version
-          }`
-        : ""
-    }`;
-    const outROMPath = path.join(
-      ref.current.tempDir,
-      `${outFullROMName.replace(/\0/g, "@")}.gba`
-    );
-
-    let outROM = await readFile(
-      ref.current.getROMPath(ref.current.gameInfo!.rom)
-    );
-    if (ref.current.gameInfo!.patch != null) {
-      outROM = Buffer.from(
-        applyBPS(
-          outROM,
-          await readFile(
-            ref.current.getPatchPath(
-              ref.current.gameInfo!.rom,
-              ref.current.gameInfo!.patch
-            )
-          )
-        )
-      );
-    }
-    await writeFile(outROMPath, outROM);
-
     await core.send({
       smuggleReq: undefined,
       startReq: {
-        romPath: outROMPath,
+        romPath: ref.current.getROMPath(ref.current.gameInfo!.rom),
+        patchPath:
+          ref.current.gameInfo!.patch != null
+            ? ref.current.getPatchPath(
+                ref.current.gameInfo!.rom,
+                ref.current.gameInfo!.patch
+              )
+            : undefined,
         savePath: path.join(
           ref.current.config.paths.saves,
           ref.current.saveName!
@@ -470,6 +465,7 @@ async function runCallback(
         windowScale: ref.current.config.windowScale,
         settings: undefined,
       },
+      forfeitReq: undefined,
     });
   } else {
     requestAttention(app);
@@ -512,6 +508,7 @@ async function runCallback(
         }).finish(),
       },
       startReq: undefined,
+      forfeitReq: undefined,
     });
 
     const remoteChunks = [];
@@ -618,6 +615,7 @@ async function runCallback(
           }).finish(),
         },
         startReq: undefined,
+        forfeitReq: undefined,
       });
 
       if (remoteChunks.length < CHUNKS_REQUIRED) {
@@ -684,60 +682,10 @@ async function runCallback(
         ? `+${ownGameInfo.patch.name}-v${ownGameInfo.patch.version}`
         : ""
     }`;
-    const outOwnROMPath = path.join(
-      ref.current.tempDir,
-      `${ownFullROMName.replace(/\0/g, "@")}.gba`
-    );
-
-    const originalOwnROM = await readFile(
-      ref.current.getROMPath(ownGameInfo.rom)
-    );
-    let outOwnROM = originalOwnROM;
-    if (ownGameInfo.patch != null) {
-      outOwnROM = Buffer.from(
-        applyBPS(
-          originalOwnROM,
-          await readFile(
-            ref.current.getPatchPath(ownGameInfo.rom, ownGameInfo.patch)
-          )
-        )
-      );
-    }
-
-    await writeFile(outOwnROMPath, outOwnROM);
 
     const opponentGameSettings = ref.current.pendingStates!.opponent!.settings;
     const opponentGameInfo = opponentGameSettings.gameInfo!;
 
-    const opponentFullROMName = `${opponentGameInfo.rom}${
-      opponentGameInfo.patch != null
-        ? `+${opponentGameInfo.patch.name}-v${opponentGameInfo.patch.version}`
-        : ""
-    }`;
-    const outOpponentROMPath = path.join(
-      ref.current.tempDir,
-      `${opponentFullROMName.replace(/\0/g, "@")}.gba`
-    );
-
-    const originalOpponentROM = await readFile(
-      ref.current.getROMPath(opponentGameInfo.rom)
-    );
-    let outOpponentROM = originalOpponentROM;
-    if (opponentGameInfo.patch != null) {
-      outOpponentROM = Buffer.from(
-        applyBPS(
-          originalOpponentROM,
-          await readFile(
-            ref.current.getPatchPath(
-              opponentGameInfo.rom,
-              opponentGameInfo.patch
-            )
-          )
-        )
-      );
-    }
-    await writeFile(outOpponentROMPath, outOpponentROM);
-
     const now = new Date();
 
     const prefix = `${datefns.format(
@@ -751,6 +699,25 @@ async function runCallback(
     await writeFile(shadowSavePath, remoteState.saveData);
 
     if (opponentGameSettings.revealSetup) {
+      // The core patches the ROMs itself, but the editor needs a patched copy.
+      const originalOpponentROM = await readFile(
+        ref.current.getROMPath(opponentGameInfo.rom)
+      );
+      let outOpponentROM = originalOpponentROM;
+      if (opponentGameInfo.patch != null) {
+        outOpponentROM = Buffer.from(
+          applyBPS(
+            originalOpponentROM,
+            await readFile(
+              ref.current.getPatchPath(
+                opponentGameInfo.rom,
+                opponentGameInfo.patch
+              )
+            )
+          )
+        );
+      }
+
       const patchInfo =
         opponentGameInfo.patch != null
           ? ref.current.getPatchInfo(opponentGameInfo.patch.name)
@@ -781,7 +748,13 @@ async function runCallback(
     }
 
     const startReq = {
-      romPath: outOwnROMPath,
+      romPath: ref.current.getROMPath(ownGameInfo.rom),
+      patchPath:
+        ownGameInfo.patch != null
+          ? ref.current.getPatchPath(ownGameInfo.rom, ownGameInfo.patch)
+          : undefined,
+      netplayCompatibility:
+        ref.current.getNetplayCompatibility(ownGameInfo) ?? undefined,
       savePath: path.join(
         ref.current.config.paths.saves,
         ref.current.saveName!
@@ -790,7 +763,14 @@ async function runCallback(
       windowScale: config.windowScale,
       settings: {
         shadowSavePath,
-        shadowRomPath: outOpponentROMPath,
+        shadowRomPath: ref.current.getROMPath(opponentGameInfo.rom),
+        shadowPatchPath:
+          opponentGameInfo.patch != null
+            ? ref.current.getPatchPath(
+                opponentGameInfo.rom,
+                opponentGameInfo.patch
+              )
+            : undefined,
         inputDelay: ownGameSettings.inputDelay,
         shadowInputDelay: opponentGameSettings.inputDelay,
         matchType: ownGameSettings.matchType,
@@ -803,14 +783,15 @@ async function runCallback(
           linkCode: linkCode,
           localSide: {
             nickname: ownGameSettings.nickname,
-            gameInfo: ownGameInfo,
+            gameInfo: replayGameInfo(ownGameInfo),
             revealSetup: ownGameSettings.revealSetup,
           },
           remoteSide: {
             nickname: opponentGameSettings.nickname,
-            gameInfo: opponentGameInfo,
+            gameInfo: replayGameInfo(opponentGameInfo),
             revealSetup: opponentGameSettings.revealSetup,
           },
+          repair: undefined,
         }).finish(),
         maxQueueLength: config.maxQueueLength,
         rngSeed,
@@ -826,6 +807,7 @@ async function runCallback(
     await core.send({
       smuggleReq: undefined,
       startReq,
+      forfeitReq: undefined,
     });
 
     ref.current.setRtts([]);
@@ -967,6 +949,7 @@ export default function BattleStarter({
   const getROMPath = useGetROMPath();
   const getPatchPath = useGetPatchPath();
   const getPatchInfo = useGetPatchInfo();
+  const getNetplayCompatibility = useGetNetplayCompatibility();
 
   const availableGames = useAvailableGames();
   const isNetplayCompatible = useIsNetplayCompatible();
@@ -1037,6 +1020,7 @@ export default function BattleStarter({
           }).finish(),
         },
         startReq: undefined,
+        forfeitReq: undefined,
       });
     },
     [saveConfig]
@@ -1092,6 +1076,7 @@ export default function BattleStarter({
     availableGames,
     getGameTitle,
     getGameFamilyTitle,
+    getNetplayCompatibility,
     getPatchPath,
     getPatchInfo,
     getROMPath,
@@ -1629,6 +1614,7 @@ export default function BattleStarter({
                                       }).finish(),
                                     },
                                     startReq: undefined,
+                                    forfeitReq: undefined,
                                   });
                                 } else {
                                   // eslint-disable-next-line no-console
@@ -1643,6 +1629,7 @@ export default function BattleStarter({
                                       }).finish(),
                                     },
                                     startReq: undefined,
+                                    forfeitReq: undefined,
                                   });
                                 }
 
@@ -1764,6 +1751,41 @@ export default function BattleStarter({
                   <Box sx={{ flexGrow: 0, flexShrink: 0, width: 400 }}>
                     <Trans i18nKey="supervisor:error.protocol-version-too-new" />
                   </Box>
+                ) : exitDialogState.exitStatus.exitCode ==
+                  ipc.ExitCode.EXIT_CODE_DESYNC ? (
+                  <Box sx={{ flexGrow: 0, flexShrink: 0, width: 400 }}>
+                    <Trans i18nKey="supervisor:error.desync" />
+                  </Box>
+                ) : exitDialogState.exitStatus.exitCode ==
+                  ipc.ExitCode.EXIT_CODE_PATCH_MISMATCH ? (
+                  <Box sx={{ flexGrow: 0, flexShrink: 0, width: 400 }}>
+                    <Trans i18nKey="supervisor:error.patch-mismatch" />
+                  </Box>
+                ) : exitDialogState.exitStatus.exitCode ==
+                  ipc.ExitCode.EXIT_CODE_MATCH_TYPE_MISMATCH ? (
+                  <Box sx={{ flexGrow: 0, flexShrink: 0, width: 400 }}>
+                    <Trans i18nKey="supervisor:error.match-type-mismatch" />
+                  </Box>
+                ) : exitDialogState.exitStatus.exitCode ==
+                  ipc.ExitCode.EXIT_CODE_INCOMPATIBLE_GAMES ? (
+                  <Box sx={{ flexGrow: 0, flexShrink: 0, width: 400 }}>
+                    <Trans i18nKey="supervisor:error.incompatible-games" />
+                  </Box>
+                ) : exitDialogState.exitStatus.exitCode ==
+                  ipc.ExitCode.EXIT_CODE_GAME_MISMATCH ? (
+                  <Box sx={{ flexGrow: 0, flexShrink: 0, width: 400 }}>
+                    <Trans i18nKey="supervisor:error.game-mismatch" />
+                  </Box>
+                ) : exitDialogState.exitStatus.exitCode ==
+                  ipc.ExitCode.EXIT_CODE_INVALID_COMMITMENT ? (
+                  <Box sx={{ flexGrow: 0, flexShrink: 0, width: 400 }}>
+                    <Trans i18nKey="supervisor:error.invalid-commitment" />
+                  </Box>
+                ) : exitDialogState.exitStatus.exitCode ==
+                  ipc.ExitCode.EXIT_CODE_OPPONENT_LEFT ? (
+                  <Box sx={{ flexGrow: 0, flexShrink: 0, width: 400 }}>
+                    <Trans i18nKey="supervisor:error.opponent-left" />
+                  </Box>
                 ) : (
                   <>
                     <Box sx={{ flexGrow: 0, flexShrink: 0, width: 600 }}>